@@ -0,0 +1,75 @@
+use std::{ cell::RefCell, fmt::Debug, sync::{ Arc, LazyLock, RwLock } };
+
+use chrono::{ DateTime, Utc };
+
+/// Tells the time trades are created and transitions are recorded at.
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+/// The default clock, reading the system time.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Always tells the same time, so time dependent behaviour can be tested exactly.
+pub struct FixedClock(pub DateTime<Utc>);
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0
+    }
+}
+
+/// Shared by every thread, so it can be injected into a multi-threaded server.
+static CLOCK: LazyLock<RwLock<Arc<dyn Clock>>> = LazyLock::new(||
+    RwLock::new(Arc::new(SystemClock))
+);
+
+thread_local! {
+    /// Overrides `CLOCK` on one thread, so a test fixing the time doesn't
+    /// affect those running alongside it.
+    static THREAD_CLOCK: RefCell<Option<Arc<dyn Clock>>> = const { RefCell::new(None) };
+}
+
+/// Replaces the clock used by every thread from now on.
+pub fn set_clock(clock: impl Clock + 'static) {
+    *CLOCK.write().unwrap() = Arc::new(clock);
+}
+
+/// Overrides the clock on the current thread only, until the returned guard is
+/// dropped, when the previous clock is restored.
+pub fn set_thread_clock(clock: impl Clock + 'static) -> ThreadClockGuard {
+    let previous: Option<Arc<dyn Clock>> = THREAD_CLOCK.replace(Some(Arc::new(clock)));
+    ThreadClockGuard { previous }
+}
+
+#[must_use = "the clock is restored as soon as the guard is dropped"]
+#[derive(Debug)]
+/// Restores the current thread's previous clock when dropped.
+pub struct ThreadClockGuard {
+    previous: Option<Arc<dyn Clock>>,
+}
+
+impl Drop for ThreadClockGuard {
+    fn drop(&mut self) {
+        THREAD_CLOCK.set(self.previous.take());
+    }
+}
+
+/// The clock in use on the current thread, for holding onto, e.g. by a service.
+pub fn clock() -> Arc<dyn Clock> {
+    THREAD_CLOCK.with_borrow(|clock: &Option<Arc<dyn Clock>>| clock.clone())
+        .unwrap_or_else(|| CLOCK.read().unwrap().clone())
+}
+
+/// The current time, as told by the current thread's clock.
+pub fn now() -> DateTime<Utc> {
+    clock().now()
+}
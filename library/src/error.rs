@@ -1,49 +1,808 @@
 use std::{ error::Error, fmt::{ self, Display }, marker::PhantomData };
 
-use tonic::Status;
+use rust_decimal::Decimal;
+use tonic::{ Code, Status, metadata::MetadataValue };
 
-use crate::{ state::TradeState };
+use crate::{ state::{ Draft, NeedsReapproval, TradeState }, trade::TradeDetails };
+
+/// Metadata key telling the client whether retrying the failed request,
+/// unchanged, could succeed. Either `true` or `false`.
+pub const RETRYABLE_KEY: &str = "retryable";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Whether a failed request may succeed if it's retried unchanged.
+pub enum ErrorClass {
+    /// A transient failure, e.g. of the store, rather than of the request.
+    Retryable,
+    /// The request itself is at fault (e.g. invalid details), so it will
+    /// fail the same way again.
+    NonRetryable,
+}
+
+impl ErrorClass {
+    /// `Aborted` isn't retryable, as it's a conflict with a concurrent change
+    /// (e.g. a `StaleVersion`), which fails the same way until the client has
+    /// read the trade again.
+    pub fn of(code: Code) -> Self {
+        match code {
+            | Code::Unavailable
+            | Code::DeadlineExceeded
+            | Code::ResourceExhausted => Self::Retryable,
+            _ => Self::NonRetryable,
+        }
+    }
+
+    /// Reads the class back from a status, as marked by `classify`.
+    pub fn from_status(status: &Status) -> Option<Self> {
+        match status.metadata().get(RETRYABLE_KEY)?.to_str().ok()? {
+            "true" => Some(Self::Retryable),
+            "false" => Some(Self::NonRetryable),
+            _ => None,
+        }
+    }
+}
+
+/// Marks the status with its class under `RETRYABLE_KEY`. Every library error
+/// is converted into a status through here, so the mapping is in one place.
+/// Statuses made elsewhere aren't marked, unless their maker does the same.
+pub fn classify(mut status: Status) -> Status {
+    let retryable: &'static str = match ErrorClass::of(status.code()) {
+        ErrorClass::Retryable => "true",
+        ErrorClass::NonRetryable => "false",
+    };
+    status.metadata_mut().insert(RETRYABLE_KEY, MetadataValue::from_static(retryable));
+    status
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Why a requester wasn't allowed to transition a trade.
+pub enum UnauthorisedCause {
+    /// The requester isn't the one who created the trade.
+    NotTradingEntity,
+
+    /// The user doesn't hold the role the transition requires.
+    WrongRole,
+
+    /// The user would be signing off their own trade.
+    SelfApproval,
+}
+
+impl Display for UnauthorisedCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnauthorisedCause::NotTradingEntity => write!(f, "as they didn't create the trade"),
+            UnauthorisedCause::WrongRole => write!(f, "without the required role"),
+            UnauthorisedCause::SelfApproval => write!(f, "as they can't approve their own trade"),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct UnauthorisedRequester<S: TradeState> {
     pub(crate) requester: String,
     pub(crate) action: String,
+    pub(crate) cause: UnauthorisedCause,
     pub(crate) _state: PhantomData<S>,
 }
 
+impl<S: TradeState> UnauthorisedRequester<S> {
+    pub fn cause(&self) -> UnauthorisedCause {
+        self.cause
+    }
+}
+
 impl<S: TradeState> Display for UnauthorisedRequester<S> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Invalid requester user {} attempted to {} from state {}.",
+            "Invalid requester user {} attempted to {} from state {}, {}.",
             self.requester,
             self.action,
-            S::NAME
+            S::NAME,
+            self.cause
         )
     }
 }
 impl<S: TradeState> Error for UnauthorisedRequester<S> {}
 
-impl<S: TradeState> Into<Status> for UnauthorisedRequester<S> {
-    fn into(self) -> Status {
-        Status::unauthenticated(format!("{}", self))
+impl<S: TradeState> From<UnauthorisedRequester<S>> for Status {
+    fn from(error: UnauthorisedRequester<S>) -> Self {
+        let status: Status = match error.cause {
+            UnauthorisedCause::NotTradingEntity => Status::unauthenticated(format!("{}", error)),
+            UnauthorisedCause::WrongRole | UnauthorisedCause::SelfApproval => {
+                Status::permission_denied(format!("{}", error))
+            }
+        };
+        classify(status)
     }
 }
 
 #[derive(Debug)]
-pub struct InvalidDetails {
+pub struct DuplicateApproval {
+    pub(crate) approver: String,
+}
+
+impl Display for DuplicateApproval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Approver {} has already signed off this trade.", self.approver)
+    }
+}
+impl Error for DuplicateApproval {}
+
+impl From<DuplicateApproval> for Status {
+    fn from(error: DuplicateApproval) -> Self {
+        classify(Status::already_exists(format!("{}", error)))
+    }
+}
+
+#[derive(Debug)]
+/// A transition requested of a trade in a terminal state, which it can't leave.
+/// The type state rules this out, so it's for callers dispatching dynamically.
+pub struct TerminalStateError {
+    pub state: &'static str,
+}
+
+impl Display for TerminalStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Trade is {}, so can't transition.", self.state)
+    }
+}
+impl Error for TerminalStateError {}
+
+impl From<TerminalStateError> for Status {
+    fn from(error: TerminalStateError) -> Self {
+        classify(Status::failed_precondition(format!("{}", error)))
+    }
+}
+
+#[derive(Debug)]
+pub struct SelfApproval {
+    pub(crate) approver: String,
+}
+
+impl SelfApproval {
+    pub fn cause(&self) -> UnauthorisedCause {
+        UnauthorisedCause::SelfApproval
+    }
+}
+
+impl Display for SelfApproval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Approver {} was refused sign off, {}.", self.approver, self.cause())
+    }
+}
+impl Error for SelfApproval {}
+
+impl From<SelfApproval> for Status {
+    fn from(error: SelfApproval) -> Self {
+        classify(Status::permission_denied(format!("{}", error)))
+    }
+}
+
+#[derive(Debug)]
+pub struct UnconfirmedBooking {
+    pub(crate) counterparty: String,
+}
+
+impl Display for UnconfirmedBooking {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Trade can't be booked until {} confirms it.", self.counterparty)
+    }
+}
+impl Error for UnconfirmedBooking {}
+
+impl From<UnconfirmedBooking> for Status {
+    fn from(error: UnconfirmedBooking) -> Self {
+        classify(Status::failed_precondition(format!("{}", error)))
+    }
+}
+
+#[derive(Debug)]
+pub struct StaleVersion {
+    pub(crate) expected: u64,
+    pub(crate) actual: u64,
+}
+
+impl Display for StaleVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Trade was changed since version {}, it's now {}.", self.expected, self.actual)
+    }
+}
+impl Error for StaleVersion {}
+
+impl From<StaleVersion> for Status {
+    fn from(error: StaleVersion) -> Self {
+        classify(Status::aborted(format!("{}", error)))
+    }
+}
+
+#[derive(Debug)]
+pub struct StrikeOutOfBand {
+    pub(crate) strike: Decimal,
+    pub(crate) lower: Decimal,
+    pub(crate) upper: Decimal,
+}
+
+impl Display for StrikeOutOfBand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Strike of {} is outside the allowed band of {} to {}.",
+            self.strike,
+            self.lower,
+            self.upper
+        )
+    }
+}
+impl Error for StrikeOutOfBand {}
+
+impl From<StrikeOutOfBand> for Status {
+    fn from(error: StrikeOutOfBand) -> Self {
+        classify(Status::invalid_argument(format!("{}", error)))
+    }
+}
+
+#[derive(Debug)]
+pub struct MissingStrike {
+    pub(crate) style: String,
+}
+
+impl Display for MissingStrike {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "An {} can't be booked without a strike.", self.style)
+    }
+}
+impl Error for MissingStrike {}
+
+impl From<MissingStrike> for Status {
+    fn from(error: MissingStrike) -> Self {
+        classify(Status::invalid_argument(format!("{}", error)))
+    }
+}
+
+#[derive(Debug)]
+/// Why booking a trade sent to the counterparty was refused.
+pub enum InvalidBooking {
+    Unconfirmed(UnconfirmedBooking),
+    Strike(StrikeOutOfBand),
+    MissingStrike(MissingStrike),
+}
+
+impl From<UnconfirmedBooking> for InvalidBooking {
+    fn from(error: UnconfirmedBooking) -> Self {
+        Self::Unconfirmed(error)
+    }
+}
+
+impl From<StrikeOutOfBand> for InvalidBooking {
+    fn from(error: StrikeOutOfBand) -> Self {
+        Self::Strike(error)
+    }
+}
+
+impl From<MissingStrike> for InvalidBooking {
+    fn from(error: MissingStrike) -> Self {
+        Self::MissingStrike(error)
+    }
+}
+
+impl Display for InvalidBooking {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unconfirmed(error) => write!(f, "{}", error),
+            Self::Strike(error) => write!(f, "{}", error),
+            Self::MissingStrike(error) => write!(f, "{}", error),
+        }
+    }
+}
+impl Error for InvalidBooking {}
+
+impl From<InvalidBooking> for Status {
+    fn from(error: InvalidBooking) -> Self {
+        match error {
+            InvalidBooking::Unconfirmed(error) => error.into(),
+            InvalidBooking::Strike(error) => error.into(),
+            InvalidBooking::MissingStrike(error) => error.into(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct UnknownApprover {
+    pub(crate) approver: String,
+}
+
+impl Display for UnknownApprover {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Approver {} isn't registered.", self.approver)
+    }
+}
+impl Error for UnknownApprover {}
+
+impl From<UnknownApprover> for Status {
+    fn from(error: UnknownApprover) -> Self {
+        classify(Status::permission_denied(format!("{}", error)))
+    }
+}
+
+#[derive(Debug)]
+/// Why an approver's sign off of a trade requiring several approvals was refused.
+pub enum InvalidApproval {
+    Duplicate(DuplicateApproval),
+    SelfApproval(SelfApproval),
+}
+
+impl From<DuplicateApproval> for InvalidApproval {
+    fn from(error: DuplicateApproval) -> Self {
+        Self::Duplicate(error)
+    }
+}
+
+impl From<SelfApproval> for InvalidApproval {
+    fn from(error: SelfApproval) -> Self {
+        Self::SelfApproval(error)
+    }
+}
+
+impl Display for InvalidApproval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Duplicate(error) => write!(f, "{}", error),
+            Self::SelfApproval(error) => write!(f, "{}", error),
+        }
+    }
+}
+impl Error for InvalidApproval {}
+
+impl From<InvalidApproval> for Status {
+    fn from(error: InvalidApproval) -> Self {
+        match error {
+            InvalidApproval::Duplicate(error) => error.into(),
+            InvalidApproval::SelfApproval(error) => error.into(),
+        }
+    }
+}
+
+#[derive(Debug)]
+/// A submission requiring no approvals, which would leave nothing to sign off.
+pub struct EmptyQuorum;
+
+impl Display for EmptyQuorum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "At least one approval must be required.")
+    }
+}
+impl Error for EmptyQuorum {}
+
+impl From<EmptyQuorum> for Status {
+    fn from(error: EmptyQuorum) -> Self {
+        classify(Status::invalid_argument(format!("{}", error)))
+    }
+}
+
+#[derive(Debug)]
+/// Why a submission requiring several approvals was refused.
+pub enum InvalidSubmission {
+    Quorum(EmptyQuorum),
+    Unauthorised(UnauthorisedRequester<Draft>),
+}
+
+impl From<EmptyQuorum> for InvalidSubmission {
+    fn from(error: EmptyQuorum) -> Self {
+        Self::Quorum(error)
+    }
+}
+
+impl From<UnauthorisedRequester<Draft>> for InvalidSubmission {
+    fn from(error: UnauthorisedRequester<Draft>) -> Self {
+        Self::Unauthorised(error)
+    }
+}
+
+impl Display for InvalidSubmission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Quorum(error) => write!(f, "{}", error),
+            Self::Unauthorised(error) => write!(f, "{}", error),
+        }
+    }
+}
+impl Error for InvalidSubmission {}
+
+impl From<InvalidSubmission> for Status {
+    fn from(error: InvalidSubmission) -> Self {
+        match error {
+            InvalidSubmission::Quorum(error) => error.into(),
+            InvalidSubmission::Unauthorised(error) => error.into(),
+        }
+    }
+}
+
+#[derive(Debug)]
+/// Why a requester's edit of their draft was refused.
+pub enum InvalidEdit {
+    Details(InvalidDetails),
+    Unauthorised(UnauthorisedRequester<Draft>),
+}
+
+impl From<InvalidDetails> for InvalidEdit {
+    fn from(error: InvalidDetails) -> Self {
+        Self::Details(error)
+    }
+}
+
+impl From<UnauthorisedRequester<Draft>> for InvalidEdit {
+    fn from(error: UnauthorisedRequester<Draft>) -> Self {
+        Self::Unauthorised(error)
+    }
+}
+
+impl Display for InvalidEdit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Details(error) => write!(f, "{}", error),
+            Self::Unauthorised(error) => write!(f, "{}", error),
+        }
+    }
+}
+impl Error for InvalidEdit {}
+
+impl From<InvalidEdit> for Status {
+    fn from(error: InvalidEdit) -> Self {
+        match error {
+            InvalidEdit::Details(error) => error.into(),
+            InvalidEdit::Unauthorised(error) => error.into(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MissingReason;
+
+impl Display for MissingReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "A reason must be given to reject a trade.")
+    }
+}
+impl Error for MissingReason {}
+
+impl From<MissingReason> for Status {
+    fn from(error: MissingReason) -> Self {
+        classify(Status::invalid_argument(format!("{}", error)))
+    }
+}
+
+#[derive(Debug)]
+/// A trade needing reapproval without a recorded amendment to reject, e.g. as
+/// it was evicted from the history.
+pub struct MissingAmendment {
+    pub(crate) trade: String,
+}
+
+impl Display for MissingAmendment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Trade {} has no recorded amendment to reject.", self.trade)
+    }
+}
+impl Error for MissingAmendment {}
+
+impl From<MissingAmendment> for Status {
+    fn from(error: MissingAmendment) -> Self {
+        classify(Status::failed_precondition(format!("{}", error)))
+    }
+}
+
+#[derive(Debug)]
+/// Why a requester's rejection of an amendment to their trade was refused.
+pub enum InvalidRejection {
+    Missing(MissingAmendment),
+    Unauthorised(UnauthorisedRequester<NeedsReapproval>),
+}
+
+impl From<MissingAmendment> for InvalidRejection {
+    fn from(error: MissingAmendment) -> Self {
+        Self::Missing(error)
+    }
+}
+
+impl From<UnauthorisedRequester<NeedsReapproval>> for InvalidRejection {
+    fn from(error: UnauthorisedRequester<NeedsReapproval>) -> Self {
+        Self::Unauthorised(error)
+    }
+}
+
+impl Display for InvalidRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing(error) => write!(f, "{}", error),
+            Self::Unauthorised(error) => write!(f, "{}", error),
+        }
+    }
+}
+impl Error for InvalidRejection {}
+
+impl From<InvalidRejection> for Status {
+    fn from(error: InvalidRejection) -> Self {
+        match error {
+            InvalidRejection::Missing(error) => error.into(),
+            InvalidRejection::Unauthorised(error) => error.into(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct InvalidRevert {
     pub(crate) issue: String,
 }
 
+impl Display for InvalidRevert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to revert the trade. {}.", self.issue)
+    }
+}
+impl Error for InvalidRevert {}
+
+impl From<InvalidRevert> for Status {
+    fn from(error: InvalidRevert) -> Self {
+        classify(Status::failed_precondition(format!("{}.", error.issue)))
+    }
+}
+
+#[derive(Debug)]
+pub struct InvalidSplit {
+    pub(crate) issue: String,
+}
+
+impl Display for InvalidSplit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to split the trade. {}.", self.issue)
+    }
+}
+impl Error for InvalidSplit {}
+
+impl From<InvalidSplit> for Status {
+    fn from(error: InvalidSplit) -> Self {
+        classify(Status::invalid_argument(format!("{}.", error.issue)))
+    }
+}
+
+#[derive(Debug)]
+pub struct OversizedNote {
+    pub(crate) length: usize,
+    pub(crate) limit: usize,
+}
+
+impl Display for OversizedNote {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Note of {} characters exceeds the limit of {}.", self.length, self.limit)
+    }
+}
+impl Error for OversizedNote {}
+
+impl From<OversizedNote> for Status {
+    fn from(error: OversizedNote) -> Self {
+        classify(Status::invalid_argument(format!("{}", error)))
+    }
+}
+
+#[derive(Debug)]
+/// Records of the history timestamped before an earlier record of the same trade.
+pub struct IntegrityError {
+    pub(crate) indices: Vec<usize>,
+}
+
+impl IntegrityError {
+    /// Indices of the out of order records in the history, ascending.
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+}
+
+impl Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let indices: Vec<String> = self.indices
+            .iter()
+            .map(|index: &usize| index.to_string())
+            .collect();
+        write!(f, "History records {} are out of order.", indices.join(", "))
+    }
+}
+impl Error for IntegrityError {}
+
+impl From<IntegrityError> for Status {
+    fn from(error: IntegrityError) -> Self {
+        classify(Status::data_loss(format!("{}", error)))
+    }
+}
+
+#[derive(Debug)]
+/// A row of an import which couldn't be made into a draft.
+pub struct ImportFailure {
+    /// Position of the row in the imported array.
+    pub index: usize,
+    pub issue: String,
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    /// The document isn't a JSON array, so no rows were read.
+    Malformed(String),
+    /// Some rows failed, every other row was still imported.
+    Rows {
+        imported: Vec<TradeDetails<Draft>>,
+        failures: Vec<ImportFailure>,
+    },
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed(issue) => write!(f, "Failed to import drafts. {}.", issue),
+            Self::Rows { failures, .. } => {
+                let issues: Vec<String> = failures
+                    .iter()
+                    .map(|failure: &ImportFailure| {
+                        format!("row {}: {}", failure.index, failure.issue)
+                    })
+                    .collect();
+                write!(f, "Failed to import {} drafts. {}.", failures.len(), issues.join("; "))
+            }
+        }
+    }
+}
+impl Error for ImportError {}
+
+impl From<ImportError> for Status {
+    fn from(error: ImportError) -> Self {
+        classify(Status::invalid_argument(format!("{}", error)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// One problem with a trade's details, tagged with the field at fault.
+pub struct ValidationIssue {
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl ValidationIssue {
+    pub(crate) fn new(field: &'static str, message: impl Into<String>) -> Self {
+        Self { field, message: message.into() }
+    }
+}
+
+impl Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+#[derive(Debug)]
+/// Every problem found with a trade's details, rather than only the first.
+pub struct InvalidDetails {
+    pub(crate) issues: Vec<ValidationIssue>,
+}
+
+impl InvalidDetails {
+    pub fn issues(&self) -> &[ValidationIssue] {
+        &self.issues
+    }
+
+    /// The messages of every issue, joined into one.
+    pub fn issue(&self) -> String {
+        self.issues
+            .iter()
+            .map(|issue: &ValidationIssue| issue.message.as_str())
+            .collect::<Vec<&str>>()
+            .join("; ")
+    }
+}
+
+impl From<ValidationIssue> for InvalidDetails {
+    fn from(issue: ValidationIssue) -> Self {
+        Self { issues: vec![issue] }
+    }
+}
+
 impl Display for InvalidDetails {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Failed to create a draft. {}.", self.issue)
+        write!(f, "Failed to create a draft. {}.", self.issue())
     }
 }
 impl Error for InvalidDetails {}
 
-impl Into<Status> for InvalidDetails {
-    fn into(self) -> Status {
-        Status::invalid_argument(format!("{}.", self.issue))
+impl From<InvalidDetails> for Status {
+    fn from(error: InvalidDetails) -> Self {
+        classify(Status::invalid_argument(format!("{}.", error.issue())))
+    }
+}
+
+#[derive(Debug)]
+pub struct UnknownAction {
+    pub(crate) id: u8,
+}
+
+impl Display for UnknownAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown trade action {}.", self.id)
+    }
+}
+impl Error for UnknownAction {}
+
+impl From<UnknownAction> for Status {
+    fn from(error: UnknownAction) -> Self {
+        classify(Status::invalid_argument(format!("{}", error)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::Code;
+
+    use super::*;
+    use crate::state::PendingApproval;
+
+    fn unauthorised(cause: UnauthorisedCause) -> UnauthorisedRequester<PendingApproval> {
+        UnauthorisedRequester {
+            requester: "TestUser".to_string(),
+            action: "approve".to_string(),
+            cause,
+            _state: PhantomData,
+        }
+    }
+
+    #[test]
+    fn retryable_classification() {
+        let invalid: Status = InvalidDetails::from(
+            ValidationIssue::new("notional_amount", "Amount must be positive")
+        ).into();
+        assert_eq!(invalid.metadata().get(RETRYABLE_KEY).unwrap(), "false");
+        assert_eq!(ErrorClass::from_status(&invalid), Some(ErrorClass::NonRetryable));
+
+        let unavailable: Status = classify(Status::unavailable("Store offline."));
+        assert_eq!(unavailable.metadata().get(RETRYABLE_KEY).unwrap(), "true");
+        assert_eq!(ErrorClass::of(Code::Aborted), ErrorClass::NonRetryable);
+        assert_eq!(ErrorClass::of(Code::PermissionDenied), ErrorClass::NonRetryable);
+
+        // Statuses made without `classify` aren't marked either way.
+        assert_eq!(ErrorClass::from_status(&Status::internal("Unmarked.")), None);
+    }
+
+    #[test]
+    fn unauthorised_causes() {
+        let not_entity: UnauthorisedRequester<PendingApproval> = unauthorised(
+            UnauthorisedCause::NotTradingEntity
+        );
+        let wrong_role: UnauthorisedRequester<PendingApproval> = unauthorised(
+            UnauthorisedCause::WrongRole
+        );
+        let self_approval: UnauthorisedRequester<PendingApproval> = unauthorised(
+            UnauthorisedCause::SelfApproval
+        );
+
+        assert_eq!(
+            not_entity.to_string(),
+            "Invalid requester user TestUser attempted to approve from state PendingApproval, \
+            as they didn't create the trade."
+        );
+        assert_ne!(not_entity.to_string(), wrong_role.to_string());
+        assert_ne!(wrong_role.to_string(), self_approval.to_string());
+        assert_ne!(self_approval.to_string(), not_entity.to_string());
+
+        let status: Status = not_entity.into();
+        assert_eq!(status.code(), Code::Unauthenticated);
+        let status: Status = wrong_role.into();
+        assert_eq!(status.code(), Code::PermissionDenied);
+        let status: Status = self_approval.into();
+        assert_eq!(status.code(), Code::PermissionDenied);
+        assert!(status.message().contains("can't approve their own trade"));
+
+        // Refused sign offs report the same cause.
+        let refused: SelfApproval = SelfApproval { approver: "TestUser".to_string() };
+        assert_eq!(refused.cause(), UnauthorisedCause::SelfApproval);
+        assert_eq!(
+            refused.to_string(),
+            "Approver TestUser was refused sign off, as they can't approve their own trade."
+        );
     }
 }
@@ -1,7 +1,15 @@
-use std::{ sync::{ LazyLock, Mutex } };
-use chrono::{ DateTime, Utc };
-
-use crate::{ state::{ TradeAction, TradeState }, trade::{ TradeDetails, TradeDetailsDiff } };
+use std::{ collections::{ HashMap, VecDeque }, sync::{ LazyLock, Mutex } };
+use chrono::{ DateTime, Duration, Utc };
+#[cfg(feature = "serde")]
+use serde::{ Serialize, Serializer, ser::SerializeStruct };
+use uuid::Uuid;
+
+use crate::{
+    clock,
+    error::{ IntegrityError, OversizedNote },
+    state::{ TradeAction, TradeState, is_terminal_state, state_id_from_name },
+    trade::{ TradeDetails, TradeDetailsDiff },
+};
 
 /// LazyLock static which is evaluated lazily, meaning: first .lock() will
 /// create the initial TradeHistory table.
@@ -11,53 +19,231 @@ pub static HISTORY: LazyLock<Mutex<TradeHistory>> = LazyLock::new(||
 );
 
 #[derive(Debug)]
+/// The records of every trade's transitions, oldest first. Unbounded by
+/// default, but once given a capacity the oldest records are evicted to stay
+/// within it, so a long-running server doesn't grow without limit.
+///
+/// Records are indexed by their position in what's retained, so after an
+/// eviction the index of every remaining record, as for `get_record` and the
+/// cursors of `page`, shifts down by the number evicted.
 pub struct TradeHistory {
-    records: Vec<HistoricalRecord>,
+    records: VecDeque<HistoricalRecord>,
+    capacity: Option<usize>,
+    evicted: usize,
 }
 
 impl TradeHistory {
     pub(crate) fn new() -> Self {
-        Self { records: Vec::new() }
+        Self { records: VecDeque::new(), capacity: None, evicted: 0 }
     }
 
     pub(crate) fn add_record(&mut self, record: HistoricalRecord) {
-        self.records.push(record);
+        self.records.push_back(record);
+        self.evict_over_capacity();
+    }
+
+    /// Caps the records retained, evicting the oldest beyond it straight away.
+    /// `None` retains every record, as by default.
+    pub fn set_capacity(&mut self, capacity: Option<usize>) {
+        self.capacity = capacity;
+        self.evict_over_capacity();
+    }
+
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    /// How many records have been evicted to stay within the capacity.
+    pub fn evicted_count(&self) -> usize {
+        self.evicted
+    }
+
+    fn evict_over_capacity(&mut self) {
+        let Some(capacity) = self.capacity else {
+            return;
+        };
+        let excess: usize = self.records.len().saturating_sub(capacity);
+        self.records.drain(..excess);
+        self.evicted += excess;
     }
 
     pub fn clear(&mut self) {
         self.records.clear();
     }
 
+    /// Records retained, so not counting any evicted.
     pub fn total_record_count(&self) -> usize {
         self.records.len()
     }
 
+    /// Every record, oldest first, without cloning the history.
+    pub fn iter(&self) -> impl Iterator<Item = &HistoricalRecord> {
+        self.records.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
     pub fn get_record(&self, step: usize) -> Option<HistoricalRecord> {
-        if step >= self.records.len() {
+        self.records.get(step).cloned()
+    }
+
+    /// Up to `limit` records following the `after` cursor, oldest first, along
+    /// with the cursor of the next page. The first page is read without a
+    /// cursor, and there's no next cursor once the final page is read. An
+    /// empty page, with a `limit` of zero, returns the same cursor.
+    pub fn page(
+        &self,
+        after: Option<usize>,
+        limit: usize
+    ) -> (Vec<HistoricalRecord>, Option<usize>) {
+        if limit == 0 {
+            return (Vec::new(), after);
+        }
+        let start: usize = after
+            .map_or(0, |cursor: usize| cursor.saturating_add(1))
+            .min(self.records.len());
+        let end: usize = start.saturating_add(limit).min(self.records.len());
+        let page: Vec<HistoricalRecord> = self.records.range(start..end).cloned().collect();
+        let next: Option<usize> = (end < self.records.len()).then(|| end - 1);
+        (page, next)
+    }
+
+    /// Every record of the trade, oldest first.
+    pub fn records_for(&self, id: Uuid) -> Vec<HistoricalRecord> {
+        self.records
+            .iter()
+            .filter(|record: &&HistoricalRecord| record.trade_id == id)
+            .cloned()
+            .collect()
+    }
+
+    /// The most recent record of the trade.
+    pub fn last_record(&self, id: Uuid) -> Option<&HistoricalRecord> {
+        self.records.iter().rev().find(|record: &&HistoricalRecord| record.trade_id == id)
+    }
+
+    /// Removes the trade's most recent record, provided it's of the action,
+    /// for a transition that was abandoned after it was recorded, e.g. as its
+    /// caller couldn't persist it.
+    pub fn retract_last(&mut self, id: Uuid, action: &TradeAction) -> Option<HistoricalRecord> {
+        let index: usize = self.records
+            .iter()
+            .rposition(|record: &HistoricalRecord| record.trade_id == id)?;
+        if self.records[index].action != *action {
             return None;
         }
-        return Some(self.records[step].clone());
+        self.records.remove(index)
+    }
+
+    /// The most recent action on the trade, and when it was made.
+    pub fn last_action_for(&self, id: Uuid) -> Option<(TradeAction, DateTime<Utc>)> {
+        self.last_record(id)
+            .map(|record: &HistoricalRecord| (record.action.clone(), record.timestamp))
+    }
+
+    /// Checks each trade's records are timestamped in the order they were
+    /// added, as an injected clock or imported records could break this.
+    /// Records of different trades may interleave in any order.
+    pub fn integrity_check(&self) -> Result<(), IntegrityError> {
+        let mut latest: HashMap<Uuid, DateTime<Utc>> = HashMap::new();
+        let mut indices: Vec<usize> = Vec::new();
+        for (index, record) in self.records.iter().enumerate() {
+            match latest.get(&record.trade_id) {
+                Some(previous) if record.timestamp < *previous => indices.push(index),
+                _ => {
+                    latest.insert(record.trade_id, record.timestamp);
+                }
+            }
+        }
+        if indices.is_empty() {
+            Ok(())
+        } else {
+            Err(IntegrityError { indices })
+        }
+    }
+
+    /// Time from the trade's first submission to it being booked, cancelled or
+    /// split into tranches, or `None` while its lifecycle is incomplete, or it
+    /// was never submitted, as for a tranche.
+    pub fn lifecycle_duration(&self, id: Uuid) -> Option<Duration> {
+        let submitted: DateTime<Utc> = self.records
+            .iter()
+            .find(|record: &&HistoricalRecord| {
+                record.trade_id == id && matches!(record.action, TradeAction::Submit)
+            })?
+            .timestamp;
+        let ended: DateTime<Utc> = self.last_record(id)
+            .filter(|record: &&HistoricalRecord| {
+                matches!(
+                    record.action,
+                    TradeAction::Book | TradeAction::Cancel | TradeAction::Split
+                )
+            })?
+            .timestamp;
+        Some(ended - submitted)
+    }
+
+    /// The most recent record of the trade, provided it can be reverted.
+    pub fn last_reversible(&self, id: Uuid) -> Option<HistoricalRecord> {
+        self.last_record(id)
+            .filter(|record: &&HistoricalRecord| record.is_reversible())
+            .cloned()
     }
 }
 
 impl IntoIterator for TradeHistory {
     type Item = HistoricalRecord;
 
-    type IntoIter = std::vec::IntoIter<Self::Item>;
+    type IntoIter = std::collections::vec_deque::IntoIter<Self::Item>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.records.into_iter()
     }
 }
 
-#[derive(Debug, Clone)]
+/// Longest note, in characters, accepted by `Note::new`.
+pub const DEFAULT_NOTE_LIMIT: usize = 500;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Free text explaining a transition, which is length checked on creation.
+pub struct Note(String);
+
+impl Note {
+    pub fn new(text: impl Into<String>) -> Result<Self, OversizedNote> {
+        Self::with_limit(text, DEFAULT_NOTE_LIMIT)
+    }
+
+    pub fn with_limit(text: impl Into<String>, limit: usize) -> Result<Self, OversizedNote> {
+        let text: String = text.into();
+        let length: usize = text.chars().count();
+        if length > limit {
+            return Err(OversizedNote { length, limit });
+        }
+        Ok(Self(text))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct HistoricalRecord {
+    trade_id: Uuid,
     timestamp: DateTime<Utc>,
     action: TradeAction,
     user_id: String,
     state_before: &'static str,
     state_after: &'static str,
     difference: Option<TradeDetailsDiff>,
+    note: Option<Note>,
+    linked_trades: Vec<Uuid>,
 }
 
 impl HistoricalRecord {
@@ -65,18 +251,50 @@ impl HistoricalRecord {
         action: TradeAction,
         id: String,
         from: &TradeDetails<From>,
-        to: &TradeDetails<To>
+        to: &TradeDetails<To>,
+        note: Option<Note>
     ) -> Self {
         Self {
-            timestamp: Utc::now(),
+            trade_id: *from.id(),
+            timestamp: clock::now(),
             action: action,
             user_id: id,
             state_before: From::NAME,
             state_after: To::NAME,
             difference: TradeDetailsDiff::new(from, to),
+            note,
+            linked_trades: Vec::new(),
         }
     }
 
+    /// Records a split against `to`, linked to the other trades of the split.
+    /// Against the parent, `from` and `to` are both the parent, which ends its
+    /// lifecycle, and it's linked to the tranches. Against a tranche, `from` is
+    /// the parent it's linked to, with the amount changed between them.
+    pub(crate) fn split<S: TradeState>(
+        id: String,
+        from: &TradeDetails<S>,
+        to: &TradeDetails<S>,
+        linked_trades: Vec<Uuid>,
+        note: Option<Note>
+    ) -> Self {
+        Self {
+            trade_id: *to.id(),
+            timestamp: clock::now(),
+            action: TradeAction::Split,
+            user_id: id,
+            state_before: S::NAME,
+            state_after: S::NAME,
+            difference: TradeDetailsDiff::new(from, to),
+            note,
+            linked_trades,
+        }
+    }
+
+    pub fn trade_id(&self) -> &Uuid {
+        &self.trade_id
+    }
+
     pub fn timestamp(&self) -> &DateTime<Utc> {
         &self.timestamp
     }
@@ -100,6 +318,54 @@ impl HistoricalRecord {
     pub fn changes(&self) -> Option<&TradeDetailsDiff> {
         self.difference.as_ref()
     }
+
+    pub fn note(&self) -> Option<&str> {
+        self.note.as_ref().map(Note::as_str)
+    }
+
+    /// The other trades the record links the trade to, i.e. the tranches
+    /// split from a parent, or the parent of a tranche.
+    pub fn linked_trades(&self) -> &[Uuid] {
+        &self.linked_trades
+    }
+
+    /// Submissions have no prior stored state, terminal states can't be
+    /// left, and reverts aren't themselves reverted. Rejections are undone
+    /// by reopening the trade, which leaves it as a draft, so neither is
+    /// reverted either. Nor are splits, the parent no longer existing.
+    pub fn is_reversible(&self) -> bool {
+        self.action != TradeAction::Submit &&
+            self.action != TradeAction::Split &&
+            self.action != TradeAction::Revert &&
+            self.action != TradeAction::Reject &&
+            self.action != TradeAction::Reopen &&
+            !state_id_from_name(self.state_after).is_some_and(is_terminal_state)
+    }
+}
+
+#[cfg(feature = "serde")]
+/// Serializes for structured log pipelines, with the action by name and the
+/// timestamp in RFC3339. Only the changed fields of the trade are included.
+impl Serialize for HistoricalRecord {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        let mut record = serializer.serialize_struct("HistoricalRecord", 9)?;
+        record.serialize_field("trade_id", &self.trade_id.to_string())?;
+        record.serialize_field("timestamp", &self.timestamp.to_rfc3339())?;
+        record.serialize_field("action", &self.action.to_string())?;
+        record.serialize_field("user_id", &self.user_id)?;
+        record.serialize_field("state_before", self.state_before)?;
+        record.serialize_field("state_after", self.state_after)?;
+        record.serialize_field("changes", &self.difference)?;
+        record.serialize_field("note", &self.note())?;
+        record.serialize_field(
+            "linked_trades",
+            &self.linked_trades
+                .iter()
+                .map(|linked: &Uuid| linked.to_string())
+                .collect::<Vec<String>>()
+        )?;
+        record.end()
+    }
 }
 
 /// Retrieves the relevant record from the trade submission history.
@@ -107,6 +373,20 @@ pub fn get_historical_record(step: usize) -> Option<HistoricalRecord> {
     HISTORY.lock().unwrap().get_record(step)
 }
 
+/// Time elapsed since the trade's most recent record, for SLA monitoring, so a
+/// draft counts from when it was last edited or reopened. Trades without any
+/// history, such as drafts never edited, have no time in state.
+pub fn time_in_current_state(id: Uuid) -> Option<Duration> {
+    let entered: DateTime<Utc> = *HISTORY.lock().unwrap().last_record(id)?.timestamp();
+    Some(clock::now() - entered)
+}
+
+/// How long the trade took from submission to being booked or cancelled,
+/// see `TradeHistory::lifecycle_duration`.
+pub fn lifecycle_duration(id: Uuid) -> Option<Duration> {
+    HISTORY.lock().unwrap().lifecycle_duration(id)
+}
+
 /// Total amount of trade submission changes made.
 pub fn total_historical_record_count() -> usize {
     HISTORY.lock().unwrap().total_record_count()
@@ -114,38 +394,523 @@ pub fn total_historical_record_count() -> usize {
 
 #[cfg(test)]
 mod tests {
+    use chrono::{ DateTime, Duration, Utc };
+    use rust_decimal::Decimal;
+    use uuid::Uuid;
+
     use crate::{
+        clock::{ self, FixedClock },
+        error::IntegrityError,
         history::{
             HISTORY,
             HistoricalRecord,
+            TradeHistory,
+            Note,
             get_historical_record,
+            lifecycle_duration,
+            time_in_current_state,
             total_historical_record_count,
         },
-        state::{ Draft, NeedsReapproval, PendingApproval, TradeAction },
-        trade::{ Direction, MutTradeDetails, TradeDetails },
+        state::{
+            Approved,
+            Draft,
+            Executed,
+            NeedsReapproval,
+            PartiallyApproved,
+            PendingApproval,
+            SentToCounterparty,
+            TradeAction,
+            TradeState,
+        },
+        trade::{
+            CounterpartyConfirmation,
+            Direction,
+            MutTradeDetails,
+            QuorumProgress,
+            TradeDetails,
+        },
         users::{ Approver, Requester, User },
     };
 
+    #[test]
+    fn reverting_an_update() {
+        // Draft
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let details: TradeDetails<Draft> = crate::trade::tests::mock_draft(&requester);
+        let id = *details.id();
+
+        // Submit, which can't be reverted.
+        let details: TradeDetails<PendingApproval> = details.submit(&requester, None).unwrap();
+        assert!(HISTORY.lock().unwrap().last_reversible(id).is_none());
+        let original: MutTradeDetails = details.grab_mut_details();
+
+        // Update
+        let approver: User<Approver> = User::sign_in("Admin");
+        let mut new_details: MutTradeDetails = details.grab_mut_details();
+        new_details.direction = Direction::SELL;
+        let details: TradeDetails<NeedsReapproval> = details
+            .update(&approver, new_details, None)
+            .unwrap();
+
+        // Revert
+        let record: HistoricalRecord = HISTORY.lock().unwrap().last_reversible(id).unwrap();
+        assert_eq!(record.action(), &TradeAction::Update);
+        let details: TradeDetails<PendingApproval> = details
+            .revert::<PendingApproval>(&approver, &record, None)
+            .unwrap();
+        assert_eq!(details.grab_mut_details(), original);
+
+        // The revert itself can't be reverted.
+        assert!(HISTORY.lock().unwrap().last_reversible(id).is_none());
+        let wrong_state = details.clone().revert::<Approved>(&approver, &record, None);
+        assert!(wrong_state.is_err());
+
+        // Nor can a record superseded by a later transition be.
+        let mut new_details: MutTradeDetails = details.grab_mut_details();
+        new_details.direction = Direction::SELL;
+        let details: TradeDetails<NeedsReapproval> = details
+            .update(&approver, new_details, None)
+            .unwrap();
+        let stale = details.revert::<PendingApproval>(&approver, &record, None).unwrap_err();
+        assert_eq!(stale.issue, "Record isn't the trade's most recent");
+    }
+
+    #[test]
+    fn reverting_sign_offs() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let first: User<Approver> = User::sign_in("Admin");
+        let second: User<Approver> = User::sign_in("SecondAdmin");
+        let details: TradeDetails<PartiallyApproved> = crate::trade::tests
+            ::mock_draft(&requester)
+            .submit_for_approvals(&requester, 2, None)
+            .unwrap();
+        let id = *details.id();
+        let sign_off = |details: TradeDetails<PartiallyApproved>, approver: &User<Approver>| {
+            details.accept(approver, None).unwrap()
+        };
+
+        // Withdrawing a sign off short of the quorum.
+        let QuorumProgress::Pending(details) = sign_off(details, &first) else {
+            panic!("Trade was approved before the quorum was met.");
+        };
+        let record: HistoricalRecord = HISTORY.lock().unwrap().last_reversible(id).unwrap();
+        let details: TradeDetails<PartiallyApproved> = details
+            .revert::<PartiallyApproved>(&first, &record, None)
+            .unwrap();
+        assert!(details.signed_off().is_empty());
+        assert_eq!(details.approvals_remaining(), 2);
+
+        // Withdrawing the sign off which met the quorum.
+        let QuorumProgress::Pending(details) = sign_off(details, &first) else {
+            panic!("Trade was approved before the quorum was met.");
+        };
+        let QuorumProgress::Approved(details) = sign_off(details, &second) else {
+            panic!("Trade wasn't approved once the quorum was met.");
+        };
+        let record: HistoricalRecord = HISTORY.lock().unwrap().last_reversible(id).unwrap();
+        let details: TradeDetails<PartiallyApproved> = details
+            .revert::<PartiallyApproved>(&first, &record, None)
+            .unwrap();
+        assert_eq!(details.signed_off(), ["Admin".to_string()]);
+        assert_eq!(details.approvals_remaining(), 1);
+    }
+
+    #[test]
+    fn noting_an_update() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let details: TradeDetails<PendingApproval> = crate::trade::tests
+            ::mock_draft(&requester)
+            .submit(&requester, None)
+            .unwrap();
+        let id = *details.id();
+
+        let approver: User<Approver> = User::sign_in("Admin");
+        let mut new_details: MutTradeDetails = details.grab_mut_details();
+        new_details.direction = Direction::SELL;
+        let note: Note = Note::new("Counterparty is selling, not buying").unwrap();
+        let _: TradeDetails<NeedsReapproval> = details
+            .update(&approver, new_details, Some(note))
+            .unwrap();
+
+        let record: HistoricalRecord = HISTORY.lock().unwrap().last_reversible(id).unwrap();
+        assert_eq!(record.action(), &TradeAction::Update);
+        assert_eq!(record.note(), Some("Counterparty is selling, not buying"));
+    }
+
+    #[test]
+    fn paging_through_history() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let draft: TradeDetails<Draft> = crate::trade::tests::mock_draft(&requester);
+        let pending: TradeDetails<PendingApproval> = draft.clone().force_transition();
+
+        let mut history: TradeHistory = TradeHistory::new();
+        for step in 0..5 {
+            history.add_record(
+                HistoricalRecord::new(
+                    TradeAction::Submit,
+                    step.to_string(),
+                    &draft,
+                    &pending,
+                    None
+                )
+            );
+        }
+        let user_ids = |page: &[HistoricalRecord]| -> Vec<String> {
+            page.iter()
+                .map(|record: &HistoricalRecord| record.user_id().to_string())
+                .collect()
+        };
+
+        let (first, cursor) = history.page(None, 2);
+        assert_eq!(user_ids(&first), vec!["0", "1"]);
+        assert_eq!(cursor, Some(1));
+
+        let (middle, cursor) = history.page(cursor, 2);
+        assert_eq!(user_ids(&middle), vec!["2", "3"]);
+        assert_eq!(cursor, Some(3));
+
+        // The final page is short, and has no next cursor.
+        let (last, cursor) = history.page(cursor, 2);
+        assert_eq!(user_ids(&last), vec!["4"]);
+        assert_eq!(cursor, None);
+
+        // Past the end is empty, rather than a panic.
+        assert!(history.page(Some(10), 2).0.is_empty());
+
+        // An empty page stays where it was, so records after it aren't skipped.
+        let (empty, cursor) = history.page(Some(1), 0);
+        assert!(empty.is_empty());
+        assert_eq!(cursor, Some(1));
+        assert_eq!(user_ids(&history.page(cursor, 2).0), vec!["2", "3"]);
+    }
+
+    #[test]
+    fn iterating_by_reference() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let draft: TradeDetails<Draft> = crate::trade::tests::mock_draft(&requester);
+        let pending: TradeDetails<PendingApproval> = draft.clone().force_transition();
+
+        let mut history: TradeHistory = TradeHistory::new();
+        assert!(history.is_empty());
+        for user_id in ["First", "Second"] {
+            history.add_record(
+                HistoricalRecord::new(
+                    TradeAction::Submit,
+                    user_id.to_string(),
+                    &draft,
+                    &pending,
+                    None
+                )
+            );
+        }
+
+        let user_ids: Vec<&str> = history
+            .iter()
+            .map(HistoricalRecord::user_id)
+            .collect();
+        assert_eq!(user_ids, vec!["First", "Second"]);
+        assert_eq!(history.iter().count(), history.len());
+        assert!(!history.is_empty());
+
+        // Still usable by value afterwards.
+        assert_eq!(history.into_iter().count(), 2);
+    }
+
+    #[test]
+    fn last_action() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let draft: TradeDetails<Draft> = crate::trade::tests::mock_draft(&requester);
+        let pending: TradeDetails<PendingApproval> = draft.clone().force_transition();
+        let updated: TradeDetails<NeedsReapproval> = crate::trade::tests::with_notional_amount(
+            pending.clone().force_transition(),
+            Decimal::from(250)
+        );
+
+        let mut history: TradeHistory = TradeHistory::new();
+        assert_eq!(history.last_action_for(*draft.id()), None);
+        history.add_record(
+            HistoricalRecord::new(TradeAction::Submit, "TestUser".into(), &draft, &pending, None)
+        );
+        history.add_record(
+            HistoricalRecord::new(TradeAction::Update, "Admin".into(), &pending, &updated, None)
+        );
+
+        let (action, timestamp) = history.last_action_for(*draft.id()).unwrap();
+        assert_eq!(action, TradeAction::Update);
+        assert_eq!(&timestamp, history.last_record(*draft.id()).unwrap().timestamp());
+        assert_eq!(history.last_action_for(Uuid::new_v4()), None);
+    }
+
+    #[test]
+    fn retracting_the_last_record() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let draft: TradeDetails<Draft> = crate::trade::tests::mock_draft(&requester);
+        let pending: TradeDetails<PendingApproval> = draft.clone().force_transition();
+        let approved: TradeDetails<Approved> = pending.clone().force_transition();
+
+        let mut history: TradeHistory = TradeHistory::new();
+        history.add_record(
+            HistoricalRecord::new(TradeAction::Submit, "TestUser".into(), &draft, &pending, None)
+        );
+        history.add_record(
+            HistoricalRecord::new(TradeAction::Accept, "Admin".into(), &pending, &approved, None)
+        );
+
+        // Only the most recent record is retracted, and only if it's of the action.
+        assert!(history.retract_last(*draft.id(), &TradeAction::Submit).is_none());
+        assert!(history.retract_last(*draft.id(), &TradeAction::Accept).is_some());
+        assert_eq!(history.last_action_for(*draft.id()).unwrap().0, TradeAction::Submit);
+        assert!(history.retract_last(Uuid::new_v4(), &TradeAction::Submit).is_none());
+    }
+
+    #[test]
+    fn timestamp_integrity() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let draft: TradeDetails<Draft> = crate::trade::tests::mock_draft(&requester);
+        let pending: TradeDetails<PendingApproval> = draft.clone().force_transition();
+        let other: TradeDetails<Draft> = crate::trade::tests::mock_draft(&requester);
+        let record = |from: &TradeDetails<Draft>, minutes: i64| -> HistoricalRecord {
+            let mut record: HistoricalRecord = HistoricalRecord::new(
+                TradeAction::Submit,
+                "TestUser".into(),
+                from,
+                &pending,
+                None
+            );
+            record.timestamp = DateTime::UNIX_EPOCH + Duration::minutes(minutes);
+            record
+        };
+
+        // Other trades' records interleave, and equal timestamps are in order.
+        let mut history: TradeHistory = TradeHistory::new();
+        assert!(history.integrity_check().is_ok());
+        history.add_record(record(&draft, 10));
+        history.add_record(record(&other, 0));
+        history.add_record(record(&draft, 10));
+        history.add_record(record(&draft, 20));
+        assert!(history.integrity_check().is_ok());
+
+        // Each is compared to the latest record of the trade, not just the last.
+        history.add_record(record(&draft, 15));
+        history.add_record(record(&other, 5));
+        history.add_record(record(&draft, 19));
+        let error: IntegrityError = history.integrity_check().unwrap_err();
+        assert_eq!(error.indices(), &[4, 6]);
+        assert_eq!(error.to_string(), "History records 4, 6 are out of order.");
+    }
+
+    #[test]
+    fn evicted_beyond_capacity() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let draft: TradeDetails<Draft> = crate::trade::tests::mock_draft(&requester);
+        let pending: TradeDetails<PendingApproval> = draft.clone().force_transition();
+        let record = |user_id: &str| -> HistoricalRecord {
+            HistoricalRecord::new(TradeAction::Submit, user_id.into(), &draft, &pending, None)
+        };
+
+        let mut history: TradeHistory = TradeHistory::new();
+        history.set_capacity(Some(2));
+        history.add_record(record("First"));
+        history.add_record(record("Second"));
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.evicted_count(), 0);
+
+        // The oldest is evicted, so the others' indices shift down.
+        history.add_record(record("Third"));
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.evicted_count(), 1);
+        assert_eq!(history.get_record(0).unwrap().user_id(), "Second");
+        assert_eq!(history.get_record(1).unwrap().user_id(), "Third");
+        assert!(history.get_record(2).is_none());
+
+        // Lowering the capacity evicts straight away.
+        history.set_capacity(Some(1));
+        assert_eq!(history.get_record(0).unwrap().user_id(), "Third");
+        assert_eq!(history.evicted_count(), 2);
+
+        history.set_capacity(None);
+        history.add_record(record("Fourth"));
+        assert_eq!(history.len(), 2);
+    }
+
+    #[test]
+    fn unchanged_update() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let details: TradeDetails<PendingApproval> = crate::trade::tests
+            ::mock_draft(&requester)
+            .submit(&requester, None)
+            .unwrap();
+        let id = *details.id();
+
+        // Setting a field to the value it already has.
+        let approver: User<Approver> = User::sign_in("Admin");
+        let mut new_details: MutTradeDetails = details.grab_mut_details();
+        new_details.direction = details.direction().clone();
+        let _: TradeDetails<NeedsReapproval> = details
+            .update(&approver, new_details, None)
+            .unwrap();
+
+        // The change of state is still recorded, without a diff.
+        let record: HistoricalRecord = HISTORY.lock().unwrap().last_record(id).unwrap().clone();
+        assert_eq!(record.action(), &TradeAction::Update);
+        assert_eq!(record.state_after(), NeedsReapproval::NAME);
+        assert!(record.changes().is_none());
+    }
+
+    #[test]
+    fn unchanged_edit_unrecorded() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let draft: TradeDetails<Draft> = crate::trade::tests::mock_draft(&requester);
+        let id = *draft.id();
+        let edited: TradeDetails<Draft> = draft
+            .clone()
+            .edit(&requester, draft.snapshot(), None)
+            .unwrap();
+
+        // Neither the state nor anything about the trade changed.
+        assert!(edited.content_eq(&draft));
+        assert!(HISTORY.lock().unwrap().records_for(id).is_empty());
+    }
+
+    #[test]
+    fn accept_recorded_without_changes() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let details: TradeDetails<PendingApproval> = crate::trade::tests
+            ::mock_draft(&requester)
+            .submit(&requester, None)
+            .unwrap();
+        let id = *details.id();
+
+        let approver: User<Approver> = User::sign_in("Admin");
+        let _: TradeDetails<Approved> = details.accept(&approver, None).unwrap();
+
+        let record: HistoricalRecord = HISTORY.lock().unwrap().last_record(id).unwrap().clone();
+        assert_eq!(record.action(), &TradeAction::Accept);
+        assert_eq!(record.user_id(), "Admin");
+        assert_eq!(record.state_before(), PendingApproval::NAME);
+        assert_eq!(record.state_after(), Approved::NAME);
+        assert!(record.changes().is_none());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serializing_a_record() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let details: TradeDetails<PendingApproval> = crate::trade::tests
+            ::mock_draft(&requester)
+            .submit(&requester, None)
+            .unwrap();
+        let id = *details.id();
+
+        let approver: User<Approver> = User::sign_in("Admin");
+        let mut new_details: MutTradeDetails = details.grab_mut_details();
+        new_details.direction = Direction::SELL;
+        new_details.notional_amount = Decimal::from(200);
+        let _: TradeDetails<NeedsReapproval> = details
+            .update(&approver, new_details, None)
+            .unwrap();
+
+        let record: HistoricalRecord = HISTORY.lock().unwrap().last_record(id).unwrap().clone();
+        let json: serde_json::Value = serde_json::to_value(&record).unwrap();
+        assert_eq!(json["trade_id"], id.to_string());
+        assert_eq!(json["timestamp"], record.timestamp().to_rfc3339());
+        assert_eq!(json["action"], "update");
+        assert_eq!(json["user_id"], "Admin");
+        assert_eq!(json["state_before"], "PendingApproval");
+        assert_eq!(json["state_after"], "NeedsReapproval");
+        // Only the changed fields are present, keyed as in `MutTradeDetails`.
+        assert_eq!(
+            json["changes"],
+            serde_json::json!({
+                "direction": { "from": "BUY", "to": "SELL" },
+                "notional_amount": { "from": "100", "to": "200" }
+            })
+        );
+        assert!(json["note"].is_null());
+    }
+
+    #[test]
+    fn lifecycle_elapsed() {
+        let submitted: DateTime<Utc> = Utc::now();
+        let _clock = clock::set_thread_clock(FixedClock(submitted));
+
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let approver: User<Approver> = User::sign_in("Admin");
+        let details: TradeDetails<Draft> = crate::trade::tests::mock_draft(&requester);
+        let id = *details.id();
+        let details: TradeDetails<PendingApproval> = details.submit(&requester, None).unwrap();
+        assert!(lifecycle_duration(id).is_none());
+
+        let _clock = clock::set_thread_clock(FixedClock(submitted + Duration::hours(1)));
+        let details: TradeDetails<SentToCounterparty> = details
+            .accept(&approver, None)
+            .unwrap()
+            .send_to_execute(&approver, None);
+        assert!(lifecycle_duration(id).is_none());
+
+        let _clock = clock::set_thread_clock(FixedClock(submitted + Duration::hours(3)));
+        let confirmation: CounterpartyConfirmation = CounterpartyConfirmation::new("CP-REF-1");
+        let details: TradeDetails<Executed> = details
+            .book(1000, Some(&confirmation), &approver, None)
+            .unwrap();
+        assert_eq!(lifecycle_duration(id), Some(Duration::hours(3)));
+        assert_eq!(details.lifecycle_duration(), Some(Duration::hours(3)));
+
+        // Cancelling a submitted trade ends its lifecycle too.
+        let pending: TradeDetails<PendingApproval> = crate::trade::tests::mock_draft(&requester)
+            .submit(&requester, None)
+            .unwrap();
+        let pending_id = *pending.id();
+        let _clock = clock::set_thread_clock(FixedClock(submitted + Duration::hours(4)));
+        pending.cancel_as_approver(&approver, None);
+        assert_eq!(lifecycle_duration(pending_id), Some(Duration::hours(1)));
+    }
+
+    #[test]
+    fn time_in_state() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let details: TradeDetails<Draft> = crate::trade::tests::mock_draft(&requester);
+        let id = *details.id();
+        assert!(time_in_current_state(id).is_none());
+
+        let details: TradeDetails<PendingApproval> = details.submit(&requester, None).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        let elapsed: Duration = time_in_current_state(id).unwrap();
+        assert!(elapsed > Duration::zero());
+        assert!(details.duration_in_state().unwrap() >= elapsed);
+    }
+
+    #[test]
+    fn oversized_notes() {
+        assert!(Note::new("a".repeat(500)).is_ok());
+        assert!(Note::new("a".repeat(501)).is_err());
+        assert!(Note::with_limit("Too long", 3).is_err());
+        // The limit is in characters rather than bytes.
+        assert!(Note::with_limit("£££", 3).is_ok());
+    }
+
     #[test]
     fn adding_records_to_lazy_history() {
         let user = User::<Requester>::sign_in("Test123");
-        let mut our_history = HISTORY.lock().unwrap();
-        our_history.clear();
+        let draft: TradeDetails<Draft> = crate::trade::tests::mock_draft(&user);
+        let id: Uuid = *draft.id();
 
-        assert_eq!(our_history.total_record_count(), 0);
+        // Other tests share the global history, so only this trade's records are counted.
+        let mut our_history = HISTORY.lock().unwrap();
+        assert!(our_history.records_for(id).is_empty());
 
         our_history.add_record(
             HistoricalRecord::new::<Draft, Draft>(
                 crate::state::TradeAction::Submit,
                 user.to_string(),
-                &crate::trade::tests::mock_draft(&user),
-                &crate::trade::tests::mock_draft(&user)
+                &draft,
+                &draft,
+                None
             )
         );
 
-        assert_eq!(our_history.total_record_count(), 1);
-        assert!(our_history.get_record(0).is_some());
-        assert!(our_history.get_record(1).is_none());
+        assert_eq!(our_history.records_for(id).len(), 1);
+        assert_eq!(our_history.last_record(id).unwrap().trade_id(), &id);
     }
 
     // Recommend this test is run with `cargo test -- --test-threads=1 --ignored`
@@ -161,7 +926,10 @@ mod tests {
         let details: TradeDetails<Draft> = crate::trade::tests::mock_draft(&requester);
 
         // Submit
-        let wrapped_details: Result<TradeDetails<PendingApproval>, _> = details.submit(&requester);
+        let wrapped_details: Result<TradeDetails<PendingApproval>, _> = details.submit(
+            &requester,
+            None
+        );
         assert!(wrapped_details.is_ok());
         let details: TradeDetails<PendingApproval> = wrapped_details.unwrap();
 
@@ -178,7 +946,8 @@ mod tests {
         new_details.direction = Direction::SELL;
         let wrapped_details: Result<TradeDetails<NeedsReapproval>, _> = details.update(
             &approver,
-            new_details
+            new_details,
+            None
         );
         assert!(wrapped_details.is_ok());
         let _: TradeDetails<NeedsReapproval> = wrapped_details.unwrap();
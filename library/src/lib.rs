@@ -3,3 +3,5 @@ pub mod trade;
 pub mod users;
 pub mod error;
 pub mod history;
+pub mod policy;
+pub mod clock;
@@ -0,0 +1,190 @@
+use std::{ collections::{ HashMap, HashSet }, sync::{ LazyLock, RwLock } };
+
+use iso_currency::Currency;
+use rust_decimal::Decimal;
+
+use crate::{ error::StrikeOutOfBand, trade::Counterparty };
+
+/// The policy every trade is validated against, which is permissive by default,
+/// other than forbidding self-approval. Deployments may change it on startup
+/// with `set_policy`.
+pub static POLICY: LazyLock<RwLock<TradePolicy>> = LazyLock::new(||
+    RwLock::new(TradePolicy::default())
+);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Optional validation of trade details, on top of the checks always made.
+pub struct TradePolicy {
+    /// Rejects trades whose underlying currencies don't all have the same
+    /// minor units (e.g. JPY with 0 and USD with 2) as the notional currency.
+    pub strict_exponents: bool,
+
+    /// Requires the notional currency to be the base (first) currency of the
+    /// underlying, rather than anywhere in it.
+    pub notional_first: bool,
+
+    /// Only allows trades with known counterparties, when set.
+    pub counterparties: Option<CounterpartyRegistry>,
+
+    /// Only allows trades in the given currencies, when set.
+    pub currencies: Option<CurrencyPolicy>,
+
+    /// Caps the notional amount of a single trade, per currency.
+    pub notional_limits: NotionalLimits,
+
+    /// Lets an approver sign off trades entered under their own id, which is
+    /// otherwise rejected.
+    pub allow_self_approval: bool,
+
+    /// Case-folds counterparties and unknown styles as they're normalized, so
+    /// names differing only by case are the same, which they otherwise aren't.
+    pub fold_case: bool,
+
+    /// Only books trades at strikes within the band, when set, as a strike of
+    /// zero or far off the reference likely comes from a bug. There's a single
+    /// band whatever the currency pair, so it only suits a desk trading pairs
+    /// quoted at similar strikes, unlike e.g. EURUSD and USDJPY.
+    pub strike_band: Option<StrikeBand>,
+
+    /// Refuses to book an option at a strike of zero, which other styles may
+    /// be booked at when there's no strike to agree.
+    pub options_need_strike: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// An allow-list of the counterparties trades may be made with.
+pub struct CounterpartyRegistry {
+    known: HashSet<String>,
+}
+
+impl CounterpartyRegistry {
+    pub fn new(known: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self { known: known.into_iter().map(Into::into).collect() }
+    }
+
+    pub fn register(&mut self, counterparty: impl Into<String>) {
+        self.known.insert(counterparty.into());
+    }
+
+    pub fn is_known(&self, counterparty: &Counterparty) -> bool {
+        self.known.contains(counterparty.name())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// The ids of the users trusted to sign in as approvers.
+pub struct ApproverRegistry {
+    known: HashSet<String>,
+}
+
+impl ApproverRegistry {
+    pub fn new(known: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self { known: known.into_iter().map(Into::into).collect() }
+    }
+
+    pub fn register(&mut self, approver: impl Into<String>) {
+        self.known.insert(approver.into());
+    }
+
+    pub fn is_known(&self, approver: &str) -> bool {
+        self.known.contains(approver)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.known.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// The currencies a desk may trade, as the notional and in the underlying.
+pub struct CurrencyPolicy {
+    notional: HashSet<Currency>,
+    underlying: HashSet<Currency>,
+}
+
+impl CurrencyPolicy {
+    pub fn new(
+        notional: impl IntoIterator<Item = Currency>,
+        underlying: impl IntoIterator<Item = Currency>
+    ) -> Self {
+        Self {
+            notional: notional.into_iter().collect(),
+            underlying: underlying.into_iter().collect(),
+        }
+    }
+
+    pub fn allows_notional(&self, currency: &Currency) -> bool {
+        self.notional.contains(currency)
+    }
+
+    pub fn allows_underlying(&self, currency: &Currency) -> bool {
+        self.underlying.contains(currency)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// The largest notional amount of a single trade in each currency.
+/// Currencies without a limit are unlimited.
+pub struct NotionalLimits {
+    limits: HashMap<Currency, Decimal>,
+}
+
+impl NotionalLimits {
+    pub fn new(limits: impl IntoIterator<Item = (Currency, Decimal)>) -> Self {
+        Self { limits: limits.into_iter().collect() }
+    }
+
+    pub fn set_limit(&mut self, currency: Currency, limit: Decimal) {
+        self.limits.insert(currency, limit);
+    }
+
+    pub fn limit_for(&self, currency: &Currency) -> Option<Decimal> {
+        self.limits.get(currency).copied()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The strikes within `max_multiple` times a reference strike either way,
+/// e.g. from 500 to 2000 for a reference of 1000 and a multiple of 2.
+pub struct StrikeBand {
+    reference: Decimal,
+    max_multiple: Decimal,
+}
+
+impl StrikeBand {
+    /// A multiple below 1 would leave no strike in the band, so is taken as 1.
+    pub fn new(reference: impl Into<Decimal>, max_multiple: impl Into<Decimal>) -> Self {
+        Self { reference: reference.into(), max_multiple: max_multiple.into().max(Decimal::ONE) }
+    }
+
+    pub fn lower(&self) -> Decimal {
+        self.reference / self.max_multiple
+    }
+
+    pub fn upper(&self) -> Decimal {
+        self.reference * self.max_multiple
+    }
+
+    /// Strikes must also be positive, whatever the reference.
+    pub fn contains(&self, strike: Decimal) -> bool {
+        strike > Decimal::ZERO && strike >= self.lower() && strike <= self.upper()
+    }
+
+    /// Rejects a strike outside the band.
+    pub fn check(&self, strike: Decimal) -> Result<(), StrikeOutOfBand> {
+        if !self.contains(strike) {
+            return Err(StrikeOutOfBand { strike, lower: self.lower(), upper: self.upper() });
+        }
+        Ok(())
+    }
+}
+
+/// Replaces the policy trades are validated against from now on.
+pub fn set_policy(policy: TradePolicy) {
+    *POLICY.write().unwrap() = policy;
+}
+
+/// The policy trades are currently validated against.
+pub fn current_policy() -> TradePolicy {
+    POLICY.read().unwrap().clone()
+}
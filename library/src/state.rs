@@ -1,5 +1,7 @@
 use std::fmt::{ Debug, Display };
 
+use crate::error::UnknownAction;
+
 /// This trait is a marker trait, that acts as our for
 /// our generic for the type state pattern.
 /// The type state pattern allows us to enforce that
@@ -9,6 +11,8 @@ use std::fmt::{ Debug, Display };
 pub trait TradeState: Debug + Display {
     const NAME: &'static str;
     const ID: u8;
+    /// Whether a trade can never leave the state.
+    const IS_TERMINAL: bool;
 }
 
 /// For any state which implements this marker trait, the trade its associated with can be cancelled.
@@ -26,6 +30,7 @@ impl Display for Draft {
 impl TradeState for Draft {
     const NAME: &'static str = "Draft";
     const ID: u8 = 0;
+    const IS_TERMINAL: bool = false;
 }
 
 #[derive(Debug)]
@@ -40,6 +45,7 @@ impl Display for PendingApproval {
 impl TradeState for PendingApproval {
     const NAME: &'static str = "PendingApproval";
     const ID: u8 = 1;
+    const IS_TERMINAL: bool = false;
 }
 impl CancellableState for PendingApproval {}
 
@@ -56,9 +62,27 @@ impl Display for NeedsReapproval {
 impl TradeState for NeedsReapproval {
     const NAME: &'static str = "NeedsReapproval";
     const ID: u8 = 2;
+    const IS_TERMINAL: bool = false;
 }
 impl CancellableState for NeedsReapproval {}
 
+#[derive(Debug)]
+/// The trade requires sign off from several approvers, and
+/// some are still outstanding.
+pub struct PartiallyApproved;
+
+impl Display for PartiallyApproved {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Self::NAME)
+    }
+}
+impl TradeState for PartiallyApproved {
+    const NAME: &'static str = "PartiallyApproved";
+    const ID: u8 = 7;
+    const IS_TERMINAL: bool = false;
+}
+impl CancellableState for PartiallyApproved {}
+
 #[derive(Debug)]
 /// The trade has been approved and is ready to send to the
 /// counterparty.
@@ -72,6 +96,7 @@ impl Display for Approved {
 impl TradeState for Approved {
     const NAME: &'static str = "Approved";
     const ID: u8 = 3;
+    const IS_TERMINAL: bool = false;
 }
 impl CancellableState for Approved {}
 
@@ -87,6 +112,7 @@ impl Display for SentToCounterparty {
 impl TradeState for SentToCounterparty {
     const NAME: &'static str = "SentToCounterparty";
     const ID: u8 = 4;
+    const IS_TERMINAL: bool = false;
 }
 impl CancellableState for SentToCounterparty {}
 
@@ -102,6 +128,7 @@ impl Display for Executed {
 impl TradeState for Executed {
     const NAME: &'static str = "Executed";
     const ID: u8 = 5;
+    const IS_TERMINAL: bool = true;
 }
 
 #[derive(Debug)]
@@ -116,8 +143,68 @@ impl Display for Cancelled {
 impl TradeState for Cancelled {
     const NAME: &'static str = "Cancelled";
     const ID: u8 = 6;
+    const IS_TERMINAL: bool = true;
 }
 
+#[derive(Debug)]
+/// The approver declined the trade, giving a reason. The requester may reopen
+/// it as a draft to revise it.
+pub struct Rejected;
+
+impl Display for Rejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Self::NAME)
+    }
+}
+impl TradeState for Rejected {
+    const NAME: &'static str = "Rejected";
+    const ID: u8 = 8;
+    const IS_TERMINAL: bool = false;
+}
+
+/// Marks `From` to `To` as an edge of the state machine, so every transition
+/// is checked at compile time, rather than relying on each caller of
+/// `force_transition` to only make legal ones. It's implemented on
+/// `StateMachine` for the edges of `TRANSITIONS`, along with the edges a
+/// revert steps back along.
+///
+/// ```compile_fail
+/// use library::state::{ AllowedTransition, Draft, Executed, StateMachine };
+///
+/// fn allowed<M: AllowedTransition<Executed, Draft>>() {}
+///
+/// // Executed trades are final, so can't become drafts again.
+/// allowed::<StateMachine>();
+/// ```
+pub trait AllowedTransition<From: TradeState, To: TradeState> {}
+
+#[derive(Debug)]
+/// The state machine the legal edges are implemented on.
+pub struct StateMachine;
+
+impl AllowedTransition<Draft, Draft> for StateMachine {}
+impl AllowedTransition<Draft, PendingApproval> for StateMachine {}
+impl AllowedTransition<Draft, PartiallyApproved> for StateMachine {}
+impl AllowedTransition<PendingApproval, Approved> for StateMachine {}
+impl AllowedTransition<PendingApproval, NeedsReapproval> for StateMachine {}
+impl AllowedTransition<PendingApproval, Rejected> for StateMachine {}
+impl AllowedTransition<Rejected, Draft> for StateMachine {}
+impl AllowedTransition<PartiallyApproved, PartiallyApproved> for StateMachine {}
+impl AllowedTransition<PartiallyApproved, Approved> for StateMachine {}
+impl AllowedTransition<NeedsReapproval, Approved> for StateMachine {}
+impl AllowedTransition<NeedsReapproval, PendingApproval> for StateMachine {}
+impl AllowedTransition<Approved, SentToCounterparty> for StateMachine {}
+impl AllowedTransition<Approved, NeedsReapproval> for StateMachine {}
+impl AllowedTransition<SentToCounterparty, Executed> for StateMachine {}
+impl AllowedTransition<SentToCounterparty, Approved> for StateMachine {}
+impl<S: CancellableState> AllowedTransition<S, Cancelled> for StateMachine {}
+
+// Only reachable by reverting an accept, so not in `TRANSITIONS`. Whereas
+// splitting, from approved to approved, makes new trades rather than
+// transitioning one, so isn't implemented.
+impl AllowedTransition<Approved, PendingApproval> for StateMachine {}
+impl AllowedTransition<Approved, PartiallyApproved> for StateMachine {}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TradeAction {
     Cancel,
@@ -127,6 +214,50 @@ pub enum TradeAction {
     Approve,
     SendToExecute,
     Book,
+    Revert,
+    Reject,
+    Reopen,
+    Split,
+}
+
+impl TradeAction {
+    /// The stable integer the action is sent as, unlike its name which is for display.
+    pub const fn id(&self) -> u8 {
+        match self {
+            TradeAction::Cancel => 0,
+            TradeAction::Submit => 1,
+            TradeAction::Accept => 2,
+            TradeAction::Update => 3,
+            TradeAction::Approve => 4,
+            TradeAction::SendToExecute => 5,
+            TradeAction::Book => 6,
+            TradeAction::Revert => 7,
+            TradeAction::Reject => 8,
+            TradeAction::Reopen => 9,
+            TradeAction::Split => 10,
+        }
+    }
+}
+
+impl TryFrom<u8> for TradeAction {
+    type Error = UnknownAction;
+
+    fn try_from(id: u8) -> Result<Self, Self::Error> {
+        match id {
+            0 => Ok(TradeAction::Cancel),
+            1 => Ok(TradeAction::Submit),
+            2 => Ok(TradeAction::Accept),
+            3 => Ok(TradeAction::Update),
+            4 => Ok(TradeAction::Approve),
+            5 => Ok(TradeAction::SendToExecute),
+            6 => Ok(TradeAction::Book),
+            7 => Ok(TradeAction::Revert),
+            8 => Ok(TradeAction::Reject),
+            9 => Ok(TradeAction::Reopen),
+            10 => Ok(TradeAction::Split),
+            _ => Err(UnknownAction { id }),
+        }
+    }
 }
 
 impl ToString for TradeAction {
@@ -139,7 +270,272 @@ impl ToString for TradeAction {
             TradeAction::Approve => "approve",
             TradeAction::SendToExecute => "send to execute",
             TradeAction::Book => "book",
+            TradeAction::Revert => "revert",
+            TradeAction::Reject => "reject",
+            TradeAction::Reopen => "reopen",
+            TradeAction::Split => "split",
         };
         x.to_string()
     }
 }
+
+/// Every state, as `(ID, NAME, IS_TERMINAL)`.
+pub const STATES: [(u8, &str, bool); 9] = [
+    (Draft::ID, Draft::NAME, Draft::IS_TERMINAL),
+    (PendingApproval::ID, PendingApproval::NAME, PendingApproval::IS_TERMINAL),
+    (NeedsReapproval::ID, NeedsReapproval::NAME, NeedsReapproval::IS_TERMINAL),
+    (Approved::ID, Approved::NAME, Approved::IS_TERMINAL),
+    (SentToCounterparty::ID, SentToCounterparty::NAME, SentToCounterparty::IS_TERMINAL),
+    (Executed::ID, Executed::NAME, Executed::IS_TERMINAL),
+    (Cancelled::ID, Cancelled::NAME, Cancelled::IS_TERMINAL),
+    (PartiallyApproved::ID, PartiallyApproved::NAME, PartiallyApproved::IS_TERMINAL),
+    (Rejected::ID, Rejected::NAME, Rejected::IS_TERMINAL),
+];
+
+/// The name of the state with the given ID, e.g. when receiving a numeric state.
+pub fn state_name_from_id(id: u8) -> Option<&'static str> {
+    STATES.iter()
+        .find(|(state_id, _, _)| *state_id == id)
+        .map(|(_, name, _)| *name)
+}
+
+/// The ID of the state with the given name, the inverse of `state_name_from_id`.
+pub fn state_id_from_name(name: &str) -> Option<u8> {
+    STATES.iter()
+        .find(|(_, state_name, _)| *state_name == name)
+        .map(|(id, _, _)| *id)
+}
+
+/// Whether the state with the given ID is terminal, false for unknown IDs.
+pub fn is_terminal_state(id: u8) -> bool {
+    STATES.iter().any(|(state_id, _, terminal)| *state_id == id && *terminal)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Which kind of user may perform a transition.
+pub enum Authority {
+    /// Either the trading entity or an approver.
+    Any,
+    /// Only the trading entity of the trade.
+    Requester,
+    Approver,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// An edge of the state machine, mirroring the transitions
+/// implemented on `TradeDetails`.
+pub struct Transition {
+    pub from: u8,
+    pub action: TradeAction,
+    pub to: u8,
+    pub authority: Authority,
+}
+
+/// The full state machine as data, for consumers that can't rely on the
+/// type state pattern (e.g. gRPC clients).
+pub const TRANSITIONS: [Transition; 21] = [
+    Transition {
+        from: Draft::ID,
+        action: TradeAction::Update,
+        to: Draft::ID,
+        authority: Authority::Requester,
+    },
+    Transition {
+        from: Draft::ID,
+        action: TradeAction::Submit,
+        to: PendingApproval::ID,
+        authority: Authority::Requester,
+    },
+    Transition {
+        from: PendingApproval::ID,
+        action: TradeAction::Accept,
+        to: Approved::ID,
+        authority: Authority::Approver,
+    },
+    Transition {
+        from: PendingApproval::ID,
+        action: TradeAction::Update,
+        to: NeedsReapproval::ID,
+        authority: Authority::Approver,
+    },
+    Transition {
+        from: PendingApproval::ID,
+        action: TradeAction::Cancel,
+        to: Cancelled::ID,
+        authority: Authority::Any,
+    },
+    Transition {
+        from: PendingApproval::ID,
+        action: TradeAction::Reject,
+        to: Rejected::ID,
+        authority: Authority::Approver,
+    },
+    Transition {
+        from: Rejected::ID,
+        action: TradeAction::Reopen,
+        to: Draft::ID,
+        authority: Authority::Requester,
+    },
+    Transition {
+        from: Draft::ID,
+        action: TradeAction::Submit,
+        to: PartiallyApproved::ID,
+        authority: Authority::Requester,
+    },
+    Transition {
+        from: PartiallyApproved::ID,
+        action: TradeAction::Accept,
+        to: PartiallyApproved::ID,
+        authority: Authority::Approver,
+    },
+    Transition {
+        from: PartiallyApproved::ID,
+        action: TradeAction::Accept,
+        to: Approved::ID,
+        authority: Authority::Approver,
+    },
+    Transition {
+        from: PartiallyApproved::ID,
+        action: TradeAction::Cancel,
+        to: Cancelled::ID,
+        authority: Authority::Any,
+    },
+    Transition {
+        from: NeedsReapproval::ID,
+        action: TradeAction::Approve,
+        to: Approved::ID,
+        authority: Authority::Requester,
+    },
+    Transition {
+        from: NeedsReapproval::ID,
+        action: TradeAction::Reject,
+        to: PendingApproval::ID,
+        authority: Authority::Requester,
+    },
+    Transition {
+        from: NeedsReapproval::ID,
+        action: TradeAction::Cancel,
+        to: Cancelled::ID,
+        authority: Authority::Any,
+    },
+    Transition {
+        from: Approved::ID,
+        action: TradeAction::SendToExecute,
+        to: SentToCounterparty::ID,
+        authority: Authority::Approver,
+    },
+    Transition {
+        from: Approved::ID,
+        action: TradeAction::Update,
+        to: NeedsReapproval::ID,
+        authority: Authority::Approver,
+    },
+    Transition {
+        from: Approved::ID,
+        action: TradeAction::Split,
+        to: Approved::ID,
+        authority: Authority::Approver,
+    },
+    Transition {
+        from: Approved::ID,
+        action: TradeAction::Cancel,
+        to: Cancelled::ID,
+        authority: Authority::Any,
+    },
+    Transition {
+        from: SentToCounterparty::ID,
+        action: TradeAction::Book,
+        to: Executed::ID,
+        authority: Authority::Any,
+    },
+    Transition {
+        from: SentToCounterparty::ID,
+        action: TradeAction::Update,
+        to: Approved::ID,
+        authority: Authority::Approver,
+    },
+    Transition {
+        from: SentToCounterparty::ID,
+        action: TradeAction::Cancel,
+        to: Cancelled::ID,
+        authority: Authority::Any,
+    },
+];
+
+/// The transitions available from the given state.
+pub fn transitions_from(state_id: u8) -> impl Iterator<Item = &'static Transition> {
+    TRANSITIONS.iter().filter(move |transition: &&Transition| transition.from == state_id)
+}
+
+/// The distinct actions which may be taken from the given state, in the order
+/// of `TRANSITIONS`, so clients needn't hardcode the graph. An empty list for
+/// terminal or unknown states.
+pub fn allowed_actions(state_id: u8) -> Vec<TradeAction> {
+    let mut actions: Vec<TradeAction> = Vec::new();
+    for transition in transitions_from(state_id) {
+        if !actions.contains(&transition.action) {
+            actions.push(transition.action.clone());
+        }
+    }
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_lookups_round_trip() {
+        for (id, name, _) in STATES {
+            assert_eq!(state_name_from_id(id), Some(name));
+            assert_eq!(state_id_from_name(name), Some(id));
+        }
+        assert_eq!(state_name_from_id(Cancelled::ID), Some("Cancelled"));
+        assert_eq!(state_id_from_name("PendingApproval"), Some(PendingApproval::ID));
+        assert_eq!(state_name_from_id(u8::MAX), None);
+        assert_eq!(state_id_from_name("Unknown"), None);
+    }
+
+    #[test]
+    fn terminal_flags() {
+        for (id, name, terminal) in STATES {
+            let expected: bool = [Executed::NAME, Cancelled::NAME].contains(&name);
+            assert_eq!(terminal, expected, "{} has the wrong terminal flag", name);
+            assert_eq!(is_terminal_state(id), expected);
+            // Terminal states are exactly those without any way out.
+            assert_eq!(transitions_from(id).next().is_none(), terminal, "{}", name);
+        }
+        assert!(!is_terminal_state(u8::MAX));
+    }
+
+    #[test]
+    fn actions_from_states() {
+        let pending: Vec<TradeAction> = allowed_actions(PendingApproval::ID);
+        assert!(pending.contains(&TradeAction::Accept));
+        assert!(pending.contains(&TradeAction::Update));
+        assert!(pending.contains(&TradeAction::Cancel));
+        assert!(!pending.contains(&TradeAction::Book));
+
+        // Accepting a partially approved trade may or may not complete it, but
+        // is the one action either way.
+        let partially_approved: Vec<TradeAction> = allowed_actions(PartiallyApproved::ID);
+        assert_eq!(partially_approved, vec![TradeAction::Accept, TradeAction::Cancel]);
+        assert_eq!(allowed_actions(Draft::ID), vec![TradeAction::Update, TradeAction::Submit]);
+        assert!(allowed_actions(Executed::ID).is_empty());
+        assert!(allowed_actions(u8::MAX).is_empty());
+    }
+
+    #[test]
+    fn action_ids_round_trip() {
+        let actions: Vec<TradeAction> = (0..=u8::MAX)
+            .filter_map(|id: u8| TradeAction::try_from(id).ok())
+            .collect();
+        assert_eq!(actions.len(), 11);
+        for action in actions {
+            assert_eq!(TradeAction::try_from(action.id()).unwrap(), action);
+        }
+        assert_eq!(TradeAction::Reopen.id(), 9);
+        assert_eq!(TradeAction::Split.id(), 10);
+        assert_eq!(TradeAction::try_from(11).unwrap_err().to_string(), "Unknown trade action 11.");
+    }
+}
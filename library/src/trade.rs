@@ -1,14 +1,71 @@
-use std::{ fmt::Display, marker::PhantomData };
-
-use chrono::{ DateTime, Utc };
+use std::{
+    convert::Infallible,
+    fmt::Display,
+    hash::{ DefaultHasher, Hash, Hasher },
+    marker::PhantomData,
+    str::FromStr,
+};
+
+use chrono::{ DateTime, TimeDelta, Utc };
 use iso_currency::Currency;
+use rust_decimal::{ Decimal, RoundingStrategy };
+#[cfg(feature = "serde")]
+use serde::{ Deserialize, Deserializer, Serialize, Serializer, de, ser::SerializeMap };
 use tonic::Status;
+use uuid::Uuid;
+
+use crate::{
+    clock,
+    error::{
+        DuplicateApproval,
+        EmptyQuorum,
+        InvalidApproval,
+        InvalidBooking,
+        InvalidDetails,
+        InvalidEdit,
+        InvalidRejection,
+        InvalidRevert,
+        InvalidSplit,
+        InvalidSubmission,
+        MissingAmendment,
+        MissingReason,
+        MissingStrike,
+        SelfApproval,
+        StaleVersion,
+        UnauthorisedRequester,
+        UnconfirmedBooking,
+        ValidationIssue,
+    },
+    history::{ HISTORY, HistoricalRecord, Note, lifecycle_duration, time_in_current_state },
+    policy::{ TradePolicy, current_policy },
+    state::*,
+    users::*,
+};
+#[cfg(feature = "serde")]
+use crate::error::{ ImportError, ImportFailure };
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(from = "String"))]
+/// The entity on the other side of the trade. The name is private, so every
+/// counterparty made outside of this module is normalized by `new`.
+pub struct Counterparty(String);
+
+impl Counterparty {
+    /// Normalizes the name under the current policy, see `new_with`.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self::new_with(name, &current_policy())
+    }
 
-use crate::{ error::{ InvalidDetails, UnauthorisedRequester }, state::*, users::* };
+    /// Trims the name, so names differing only by surrounding whitespace are
+    /// the same counterparty, and case-folds it when the policy does.
+    pub fn new_with(name: impl Into<String>, policy: &TradePolicy) -> Self {
+        Self(normalize(&name.into(), policy))
+    }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-/// The entity on the other side of the trade.
-pub struct Counterparty(pub String);
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
 
 impl Display for Counterparty {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -16,22 +73,139 @@ impl Display for Counterparty {
     }
 }
 
+impl From<String> for Counterparty {
+    fn from(name: String) -> Self {
+        Self::new(name)
+    }
+}
+
+/// Trims free-form names, case-folding them too under a policy which does.
+fn normalize(raw: &str, policy: &TradePolicy) -> String {
+    let trimmed: &str = raw.trim();
+    if policy.fold_case {
+        trimmed.to_lowercase()
+    } else {
+        trimmed.to_string()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Style(pub String);
+/// The counterparty's agreement to the trade sent to them, identified by
+/// their reference for it, without which the trade can't be booked.
+pub struct CounterpartyConfirmation {
+    reference: String,
+}
+
+impl CounterpartyConfirmation {
+    pub fn new(reference: impl Into<String>) -> Self {
+        Self { reference: reference.into() }
+    }
+
+    pub fn reference(&self) -> &str {
+        &self.reference
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(from = "String", into = "String")
+)]
+/// The kind of contract traded, which is serialized as its name.
+/// Every booked trade has a strike, though only options need a non-zero one,
+/// which the policy may enforce with `options_need_strike`.
+pub enum Style {
+    Forward,
+    Option,
+    Swap,
+    /// Any style outside of those known, kept as given once normalized.
+    Other(String),
+}
+
+impl Style {
+    /// Normalizes the style under the current policy, see `new_with`.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self::new_with(name, &current_policy())
+    }
+
+    /// Matches known styles as `from_str` does. Any other is normalized as a
+    /// counterparty's name is, see `Counterparty::new_with`.
+    pub fn new_with(name: impl Into<String>, policy: &TradePolicy) -> Self {
+        let Ok(style) = name.into().parse::<Style>();
+        match style {
+            Style::Other(other) => Style::Other(normalize(&other, policy)),
+            known => known,
+        }
+    }
+}
 
 impl Display for Style {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        match self {
+            Style::Forward => write!(f, "Forward"),
+            Style::Option => write!(f, "Option"),
+            Style::Swap => write!(f, "Swap"),
+            Style::Other(style) => write!(f, "{}", style),
+        }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Known styles are matched regardless of case and surrounding whitespace,
+/// anything else is `Other`, trimmed.
+impl FromStr for Style {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s: &str = s.trim();
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "forward" => Style::Forward,
+            "option" => Style::Option,
+            "swap" => Style::Swap,
+            _ => Style::Other(s.to_string()),
+        })
+    }
+}
+
+impl From<String> for Style {
+    fn from(style: String) -> Self {
+        Self::new(style)
+    }
+}
+
+impl From<Style> for String {
+    fn from(style: Style) -> Self {
+        style.to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 /// Specifies whether the trade is a "Buy" or "Sell".
 pub enum Direction {
     BUY,
     SELL,
 }
 
+impl Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Direction::BUY => write!(f, "BUY"),
+            Direction::SELL => write!(f, "SELL"),
+        }
+    }
+}
+
+impl Direction {
+    /// The other side, e.g. to hedge a trade.
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::BUY => Direction::SELL,
+            Direction::SELL => Direction::BUY,
+        }
+    }
+}
+
 impl Into<i32> for &Direction {
     fn into(self) -> i32 {
         match self {
@@ -53,7 +227,8 @@ impl TryFrom<i32> for Direction {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct MutTradeDetails {
     /// The entity on the other side of the trade.
     pub counterparty: Counterparty,
@@ -67,11 +242,13 @@ pub struct MutTradeDetails {
     /// Currency of the notional amount (e.g., EUR, GBP, USD).
     pub notional_currency: Currency,
 
-    /// The size of the trade in the selected notional currency.
-    pub notional_amount: u64,
+    /// The size of the trade in the selected notional currency, in major units.
+    /// It may carry no more decimal places than the currency's exponent.
+    pub notional_amount: Decimal,
 
     /// A combination of eligible notional currencies.
     /// The notional currency selected must be part of the underlying.
+    /// Stored in canonical order, see `MutTradeDetails::canonicalise`.
     pub underlying: Vec<Currency>,
 
     /// The date when the trade value is realized.
@@ -81,7 +258,40 @@ pub struct MutTradeDetails {
     pub delivery_date: DateTime<Utc>,
 }
 
-#[derive(Debug, Default, Clone)]
+impl MutTradeDetails {
+    /// Puts the underlying into its canonical order: the base currency first,
+    /// as given, then the others by numeric code, as their order carries no
+    /// meaning. Details are canonicalised whenever they're stored, so baskets
+    /// differing only in the order of their other currencies are the same.
+    pub fn canonicalise(&mut self) {
+        if let Some((_, others)) = self.underlying.split_first_mut() {
+            others.sort_by_key(|currency: &Currency| currency.numeric());
+        }
+    }
+
+    pub fn canonicalised(mut self) -> Self {
+        self.canonicalise();
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// How an executed trade settles, see `TradeDetails::<Executed>::settlement`.
+pub struct SettlementDetails {
+    /// When the currencies are exchanged, the trade's delivery date.
+    pub settlement_date: DateTime<Utc>,
+
+    /// The currency the notional is exchanged for at the strike, the first of
+    /// the underlying other than the notional currency, if there is one.
+    pub settlement_currency: Currency,
+
+    /// The notional at the strike, in the settlement currency and rounded to
+    /// its minor units. Negative when paid, as on a BUY, and positive when
+    /// received, as on a SELL.
+    pub net_amount: Decimal,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct TradeDetailsDiff {
     pub(crate) counterparty: Option<(Counterparty, Counterparty)>,
 
@@ -91,7 +301,7 @@ pub struct TradeDetailsDiff {
 
     pub(crate) notional_currency: Option<(Currency, Currency)>,
 
-    pub(crate) notional_amount: Option<(u64, u64)>,
+    pub(crate) notional_amount: Option<(Decimal, Decimal)>,
 
     pub(crate) underlying: Option<(Vec<Currency>, Vec<Currency>)>,
 
@@ -99,7 +309,9 @@ pub struct TradeDetailsDiff {
 
     pub(crate) delivery_date: Option<(DateTime<Utc>, DateTime<Utc>)>,
 
-    pub(crate) strike: Option<u64>,
+    pub(crate) strike: Option<Decimal>,
+
+    pub(crate) confirmation_reference: Option<String>,
 }
 
 impl TradeDetailsDiff {
@@ -119,7 +331,7 @@ impl TradeDetailsDiff {
         self.notional_currency.as_ref()
     }
 
-    pub fn changed_amount(&self) -> Option<(u64, u64)> {
+    pub fn changed_amount(&self) -> Option<(Decimal, Decimal)> {
         self.notional_amount
     }
 
@@ -135,20 +347,63 @@ impl TradeDetailsDiff {
         self.delivery_date.as_ref()
     }
 
-    pub fn changed_strike(&self) -> Option<u64> {
+    pub fn changed_strike(&self) -> Option<Decimal> {
         self.strike
     }
 
+    /// The counterparty's reference the trade was booked against.
+    pub fn changed_confirmation_reference(&self) -> Option<&str> {
+        self.confirmation_reference.as_deref()
+    }
+
+    /// Whether no field was changed, which `new` never returns.
+    pub fn is_empty(&self) -> bool {
+        self.counterparty.is_none() &&
+            self.direction.is_none() &&
+            self.style.is_none() &&
+            self.notional_currency.is_none() &&
+            self.notional_amount.is_none() &&
+            self.underlying.is_none() &&
+            self.value_date.is_none() &&
+            self.delivery_date.is_none() &&
+            self.strike.is_none() &&
+            self.confirmation_reference.is_none()
+    }
+
+    /// Restores the values from before the change.
+    pub(crate) fn revert(&self, details: &mut MutTradeDetails) {
+        if let Some((from, _)) = &self.counterparty {
+            details.counterparty = from.clone();
+        }
+        if let Some((from, _)) = &self.direction {
+            details.direction = from.clone();
+        }
+        if let Some((from, _)) = &self.style {
+            details.style = from.clone();
+        }
+        if let Some((from, _)) = &self.notional_currency {
+            details.notional_currency = *from;
+        }
+        if let Some((from, _)) = &self.notional_amount {
+            details.notional_amount = *from;
+        }
+        if let Some((from, _)) = &self.underlying {
+            details.underlying = from.clone();
+        }
+        if let Some((from, _)) = &self.value_date {
+            details.value_date = *from;
+        }
+        if let Some((from, _)) = &self.delivery_date {
+            details.delivery_date = *from;
+        }
+    }
+
     pub(crate) fn new<From: TradeState, To: TradeState>(
         from_details: &TradeDetails<From>,
         to_details: &TradeDetails<To>
     ) -> Option<Self> {
         let from: &MutTradeDetails = &from_details.mutable_details;
         let to: &MutTradeDetails = &to_details.mutable_details;
-        if from == to && to_details.strike.is_none() {
-            return None;
-        }
-
         let mut diff: Self = Self::default();
         if from.counterparty != to.counterparty {
             diff.counterparty = Some((from.counterparty.clone(), to.counterparty.clone()));
@@ -168,6 +423,7 @@ impl TradeDetailsDiff {
         if from.notional_amount != to.notional_amount {
             diff.notional_amount = Some((from.notional_amount, to.notional_amount));
         }
+        // Both are canonical, so a reordering of the basket alone isn't a change.
         if from.underlying != to.underlying {
             diff.underlying = Some((from.underlying.clone(), to.underlying.clone()));
         }
@@ -177,13 +433,216 @@ impl TradeDetailsDiff {
         if from.delivery_date != to.delivery_date {
             diff.delivery_date = Some((from.delivery_date.clone(), to.delivery_date.clone()));
         }
-        diff.strike = to_details.strike;
-        Some(diff)
+        if from_details.strike != to_details.strike {
+            diff.strike = to_details.strike;
+        }
+        if from_details.confirmation_reference != to_details.confirmation_reference {
+            diff.confirmation_reference = to_details.confirmation_reference.clone();
+        }
+        (!diff.is_empty()).then_some(diff)
+    }
+}
+
+/// Only executed trades have a strike, which is set on booking, and every
+/// executed trade has one. This can't be expressed by the type state alone.
+fn check_strike<S: TradeState>(strike: Option<Decimal>) -> Result<(), String> {
+    match (strike, S::ID == Executed::ID) {
+        (Some(_), false) => Err(format!("{} trades can't have a strike", S::NAME)),
+        (None, true) => Err(format!("{} trades must have a strike", S::NAME)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+/// How trades are serialized, with the state named alongside the details.
+struct StoredTrade {
+    id: String,
+    state: String,
+    trading_entity: String,
+    details: MutTradeDetails,
+    trade_date: DateTime<Utc>,
+    strike: Option<Decimal>,
+    #[serde(default)]
+    confirmation_reference: Option<String>,
+    quorum: Option<Quorum>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+#[cfg(feature = "serde")]
+impl<S: TradeState> Serialize for TradeDetails<S> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        StoredTrade {
+            id: self.id.to_string(),
+            state: S::NAME.to_string(),
+            trading_entity: self.trading_entity.to_string(),
+            details: self.mutable_details.clone(),
+            trade_date: self.trade_date,
+            strike: self.strike,
+            confirmation_reference: self.confirmation_reference.clone(),
+            quorum: self.quorum.clone(),
+            expires_at: self.expires_at,
+        }.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+/// Rejects trades stored in another state, or breaking the strike invariant.
+impl<'de, S: TradeState> Deserialize<'de> for TradeDetails<S> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let stored: StoredTrade = StoredTrade::deserialize(deserializer)?;
+        if stored.state != S::NAME {
+            return Err(
+                de::Error::custom(format!("Expected a {} trade, not {}", S::NAME, stored.state))
+            );
+        }
+        check_strike::<S>(stored.strike).map_err(de::Error::custom)?;
+        Ok(TradeDetails {
+            id: Uuid::parse_str(&stored.id).map_err(de::Error::custom)?,
+            trading_entity: User::sign_in(&stored.trading_entity),
+            mutable_details: stored.details.canonicalised(),
+            trade_date: stored.trade_date,
+            strike: stored.strike,
+            confirmation_reference: stored.confirmation_reference,
+            quorum: stored.quorum,
+            expires_at: stored.expires_at,
+            _state: PhantomData,
+        })
+    }
+}
+
+/// Joins currencies by their alphabetic codes, e.g. `USD,GBP`.
+fn format_currencies(currencies: &[Currency]) -> String {
+    currencies
+        .iter()
+        .map(|c: &Currency| c.code())
+        .collect::<Vec<&str>>()
+        .join(",")
+}
+
+#[cfg(feature = "serde")]
+#[derive(Serialize)]
+/// A single field's change, as serialized.
+struct Change<T> {
+    from: T,
+    to: T,
+}
+
+#[cfg(feature = "serde")]
+/// Alphabetic codes of the currencies, e.g. `["USD", "GBP"]`.
+fn currency_codes(currencies: &[Currency]) -> Vec<&'static str> {
+    currencies
+        .iter()
+        .map(|c: &Currency| c.code())
+        .collect()
+}
+
+#[cfg(feature = "serde")]
+/// Serializes only the populated fields, each as its `from` and `to` values,
+/// keyed as in `MutTradeDetails`, with currencies as alphabetic codes and
+/// dates in RFC3339.
+impl Serialize for TradeDetailsDiff {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        if let Some((from, to)) = &self.counterparty {
+            map.serialize_entry("counterparty", &Change { from, to })?;
+        }
+        if let Some((from, to)) = &self.direction {
+            map.serialize_entry("direction", &Change { from, to })?;
+        }
+        if let Some((from, to)) = &self.style {
+            map.serialize_entry("style", &Change { from, to })?;
+        }
+        if let Some((from, to)) = &self.notional_currency {
+            map.serialize_entry(
+                "notional_currency",
+                &Change { from: from.code(), to: to.code() }
+            )?;
+        }
+        if let Some((from, to)) = &self.notional_amount {
+            map.serialize_entry("notional_amount", &Change { from, to })?;
+        }
+        if let Some((from, to)) = &self.underlying {
+            map.serialize_entry(
+                "underlying",
+                &Change { from: currency_codes(from), to: currency_codes(to) }
+            )?;
+        }
+        if let Some((from, to)) = &self.value_date {
+            map.serialize_entry(
+                "value_date",
+                &Change { from: from.to_rfc3339(), to: to.to_rfc3339() }
+            )?;
+        }
+        if let Some((from, to)) = &self.delivery_date {
+            map.serialize_entry(
+                "delivery_date",
+                &Change { from: from.to_rfc3339(), to: to.to_rfc3339() }
+            )?;
+        }
+        if let Some(strike) = &self.strike {
+            map.serialize_entry("strike", strike)?;
+        }
+        if let Some(reference) = &self.confirmation_reference {
+            map.serialize_entry("confirmation_reference", reference)?;
+        }
+        map.end()
+    }
+}
+
+/// Renders only the populated fields, e.g. `direction: BUY -> SELL; amount: 1 -> 1000`.
+impl Display for TradeDetailsDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut clauses: Vec<String> = Vec::new();
+        if let Some((from, to)) = &self.counterparty {
+            clauses.push(format!("counterparty: {} -> {}", from, to));
+        }
+        if let Some((from, to)) = &self.direction {
+            clauses.push(format!("direction: {} -> {}", from, to));
+        }
+        if let Some((from, to)) = &self.style {
+            clauses.push(format!("style: {} -> {}", from, to));
+        }
+        if let Some((from, to)) = &self.notional_currency {
+            clauses.push(format!("currency: {} -> {}", from.code(), to.code()));
+        }
+        if let Some((from, to)) = &self.notional_amount {
+            clauses.push(format!("amount: {} -> {}", from, to));
+        }
+        if let Some((from, to)) = &self.underlying {
+            clauses.push(
+                format!("underlying: {} -> {}", format_currencies(from), format_currencies(to))
+            );
+        }
+        if let Some((from, to)) = &self.value_date {
+            clauses.push(format!("value_date: {} -> {}", from.to_rfc3339(), to.to_rfc3339()));
+        }
+        if let Some((from, to)) = &self.delivery_date {
+            clauses.push(format!("delivery_date: {} -> {}", from.to_rfc3339(), to.to_rfc3339()));
+        }
+        if let Some(strike) = &self.strike {
+            clauses.push(format!("strike: {}", strike));
+        }
+        if let Some(reference) = &self.confirmation_reference {
+            clauses.push(format!("confirmation_reference: {}", reference));
+        }
+        write!(f, "{}", clauses.join("; "))
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// Sign offs for a trade requiring several approvers.
+pub(crate) struct Quorum {
+    required: usize,
+    signed_off: Vec<String>,
+}
+
 #[derive(Debug)]
 pub struct TradeDetails<S = Draft> where S: TradeState {
+    /// Identifies the trade across its transitions.
+    id: Uuid,
+
     /// Legal entity conducting the trade.
     pub(crate) trading_entity: User<Requester>,
 
@@ -194,12 +653,25 @@ pub struct TradeDetails<S = Draft> where S: TradeState {
     trade_date: DateTime<Utc>,
 
     /// Agreed rate. This information is only available after trades are executed.
-    strike: Option<u64>,
+    strike: Option<Decimal>,
+
+    /// The counterparty's reference for their confirmation, set with the strike.
+    confirmation_reference: Option<String>,
+
+    /// Only present for trades submitted for several approvals.
+    quorum: Option<Quorum>,
+
+    /// When the trade lapses if it hasn't progressed, if ever.
+    expires_at: Option<DateTime<Utc>>,
 
     _state: PhantomData<S>,
 }
 
 impl<S: TradeState> TradeDetails<S> {
+    pub fn id(&self) -> &Uuid {
+        &self.id
+    }
+
     pub fn trading_entity(&self) -> &User<Requester> {
         &self.trading_entity
     }
@@ -220,10 +692,26 @@ impl<S: TradeState> TradeDetails<S> {
         &self.mutable_details.notional_currency
     }
 
-    pub fn amount(&self) -> u64 {
+    pub fn amount(&self) -> Decimal {
         self.mutable_details.notional_amount
     }
 
+    /// The notional amount in major units, to exactly the currency's decimal
+    /// places (e.g. 12.30 USD, but 1230 JPY) for reporting. Checked amounts
+    /// have at most that many places, but one read without being checked,
+    /// e.g. deserialized, is rounded half away from zero, not truncated.
+    pub fn notional_in_major_units(&self) -> Decimal {
+        let mut amount: Decimal = self.amount();
+        if let Some(exponent) = self.currency().exponent() {
+            amount = amount.round_dp_with_strategy(
+                exponent as u32,
+                RoundingStrategy::MidpointAwayFromZero
+            );
+            amount.rescale(exponent as u32);
+        }
+        amount
+    }
+
     pub fn underlying(&self) -> &Vec<Currency> {
         &self.mutable_details.underlying
     }
@@ -240,50 +728,385 @@ impl<S: TradeState> TradeDetails<S> {
         &self.trade_date
     }
 
-    pub fn strike(&self) -> Option<u64> {
+    pub fn strike(&self) -> Option<Decimal> {
         self.strike
     }
 
+    /// The counterparty's reference for the trade, once it's been booked.
+    pub fn confirmation_reference(&self) -> Option<&str> {
+        self.confirmation_reference.as_deref()
+    }
+
+    /// Whole days from the trade date to the delivery date. Never negative,
+    /// as delivery can't precede the trade date.
+    pub fn tenor_days(&self) -> i64 {
+        (self.mutable_details.delivery_date - self.trade_date).num_days()
+    }
+
+    /// Whole days from the trade date to the value date. Never negative,
+    /// as the value date can't precede the trade date.
+    pub fn value_tenor_days(&self) -> i64 {
+        (self.mutable_details.value_date - self.trade_date).num_days()
+    }
+
+    /// A one line summary for people, e.g. in logs or a UI, such as
+    /// `BUY 1000 USD vs [USD, GBP] with Maggie, value 2026-01-01, Approved`.
+    pub fn describe(&self) -> String {
+        let underlying: Vec<&str> = self.mutable_details.underlying
+            .iter()
+            .map(|c: &Currency| c.code())
+            .collect();
+        format!(
+            "{} {} {} vs [{}] with {}, value {}, {}",
+            self.mutable_details.direction,
+            self.mutable_details.notional_amount,
+            self.mutable_details.notional_currency.code(),
+            underlying.join(", "),
+            self.mutable_details.counterparty,
+            self.mutable_details.value_date.format("%Y-%m-%d"),
+            S::NAME
+        )
+    }
+
+    pub fn expires_at(&self) -> Option<&DateTime<Utc>> {
+        self.expires_at.as_ref()
+    }
+
+    pub fn is_expired(&self, now: &DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at: DateTime<Utc>| *now >= expires_at)
+    }
+
+    /// The notional signed by direction, positive for BUY and negative for SELL,
+    /// in the notional currency. Summed per counterparty, this gives net exposure.
+    pub fn counterparty_exposure_sign(&self) -> Decimal {
+        match self.mutable_details.direction {
+            Direction::BUY => self.mutable_details.notional_amount,
+            Direction::SELL => -self.mutable_details.notional_amount,
+        }
+    }
+
+    /// A hash of the editable details, for optimistic concurrency. A client
+    /// reads it with the trade and expects it unchanged when it edits, so
+    /// concurrent edits don't clobber one another. The basket is canonical, so
+    /// reordering it alone doesn't change the version. It's only stable within
+    /// a build of the server, so shouldn't be persisted.
+    pub fn version_hash(&self) -> u64 {
+        let mut hasher: DefaultHasher = DefaultHasher::new();
+        self.mutable_details.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Rejects an edit made against another version of the trade than its own.
+    pub fn check_version(&self, expected: u64) -> Result<(), StaleVersion> {
+        let actual: u64 = self.version_hash();
+        if expected != actual {
+            return Err(StaleVersion { expected, actual });
+        }
+        Ok(())
+    }
+
+    /// A read-only copy of the editable details, in any state.
+    pub fn snapshot(&self) -> MutTradeDetails {
+        self.mutable_details.clone()
+    }
+
+    /// A new draft mirroring the trade, for back-to-back and hedging workflows,
+    /// with the opposite direction against `counterparty`. The economic terms
+    /// and trade date are kept, and are checked again as for any new draft.
+    pub fn mirror(
+        &self,
+        counterparty: Counterparty
+    ) -> Result<TradeDetails<Draft>, InvalidDetails> {
+        let details: MutTradeDetails = MutTradeDetails {
+            counterparty,
+            direction: self.mutable_details.direction.opposite(),
+            ..self.snapshot()
+        };
+        TradeDetails::<Draft>::new_with_trade_date(&self.trading_entity, details, self.trade_date)
+    }
+
+    /// How long ago the trade was made, by the current clock.
+    pub fn age(&self) -> TimeDelta {
+        clock::now() - self.trade_date
+    }
+
+    /// How long the trade took from submission to being booked or cancelled,
+    /// see `history::lifecycle_duration`.
+    pub fn lifecycle_duration(&self) -> Option<TimeDelta> {
+        lifecycle_duration(self.id)
+    }
+
+    /// How long the trade has been in its current state, see `history::time_in_current_state`.
+    pub fn duration_in_state(&self) -> Option<TimeDelta> {
+        time_in_current_state(self.id)
+    }
+
+    /// Compares the economic details of two trades, regardless of state,
+    /// ignoring the strike and trade date. A trade before and after booking
+    /// should only ever differ by its strike.
+    pub fn equal_ignoring_strike<S2: TradeState>(&self, other: &TradeDetails<S2>) -> bool {
+        self.trading_entity == other.trading_entity &&
+            self.mutable_details == other.mutable_details
+    }
+
+    /// Whether moving to the other details would neither change the state nor
+    /// anything about the trade, including its sign offs.
+    pub(crate) fn is_unchanged_by<S2: TradeState>(&self, other: &TradeDetails<S2>) -> bool {
+        S::NAME == S2::NAME && self.content_eq(other) && self.quorum == other.quorum
+    }
+
+    /// Compares the content of two trades, regardless of state. The id,
+    /// approvals and expiry are bookkeeping, so aren't compared.
+    pub fn content_eq<S2: TradeState>(&self, other: &TradeDetails<S2>) -> bool {
+        self.equal_ignoring_strike(other) &&
+            self.trade_date == other.trade_date &&
+            self.strike == other.strike &&
+            self.confirmation_reference == other.confirmation_reference
+    }
+
+    /// Transitions as `force_transition` does, but only along an edge of the
+    /// state machine, so an illegal transition fails to compile.
+    pub(crate) fn map_state<To: TradeState>(self) -> TradeDetails<To>
+        where StateMachine: AllowedTransition<S, To>
+    {
+        self.force_transition()
+    }
+
     /// This consumes self, creating a new type with the next transition.
     /// It isn't public, as it would allow a transition from any state to another.
     /// Once optimized, this should effectively be a noop.
     pub(crate) fn force_transition<To: TradeState>(self) -> TradeDetails<To> {
-        TradeDetails {
+        let details: TradeDetails<To> = TradeDetails {
+            id: self.id,
             trading_entity: self.trading_entity,
-            mutable_details: self.mutable_details.clone(),
+            mutable_details: self.mutable_details,
             trade_date: self.trade_date,
             strike: self.strike,
+            confirmation_reference: self.confirmation_reference,
+            quorum: self.quorum,
+            expires_at: self.expires_at,
             _state: PhantomData,
-        }
+        };
+        debug_assert!(
+            check_strike::<To>(details.strike).is_ok(),
+            "Transitioned to {} with strike {:?}.",
+            To::NAME,
+            details.strike
+        );
+        details
     }
 
-    /// Common checks that need to be made on every mutation.
+    /// Common checks that need to be made on every mutation, under the current policy.
     fn check_details(&self, mut_details: &MutTradeDetails) -> Result<(), InvalidDetails> {
+        self.check_details_with(mut_details, &current_policy())
+    }
+
+    pub(crate) fn check_details_with(
+        &self,
+        mut_details: &MutTradeDetails,
+        policy: &TradePolicy
+    ) -> Result<(), InvalidDetails> {
+        let mut issues: Vec<ValidationIssue> = Vec::new();
+
         if
             mut_details.value_date < self.trade_date ||
             mut_details.delivery_date < self.trade_date ||
             mut_details.delivery_date < mut_details.value_date
         {
-            return Err(InvalidDetails {
-                issue: "Dates must be chronologically ordered".to_string(),
-            });
+            issues.push(
+                ValidationIssue::new("dates", "Dates must be chronologically ordered")
+            );
+        } else if mut_details.delivery_date == mut_details.value_date {
+            issues.push(
+                ValidationIssue::new("dates", "Delivery date must be after the value date")
+            );
+        }
+
+        if mut_details.notional_amount <= Decimal::ZERO {
+            issues.push(
+                ValidationIssue::new("notional_amount", "Notional amount must be positive")
+            );
+        }
+
+        if
+            let Err(error) = validate_currency_precision_for_amount(
+                &mut_details.notional_currency,
+                mut_details.notional_amount
+            )
+        {
+            issues.extend(error.issues);
+        }
+
+        if !is_tradeable(&mut_details.notional_currency) {
+            issues.push(
+                ValidationIssue::new(
+                    "notional_currency",
+                    format!(
+                        "Currency {} isn't tradeable",
+                        mut_details.notional_currency.code()
+                    )
+                )
+            );
+        }
+        let untradeable: Option<&Currency> = mut_details.underlying
+            .iter()
+            .find(|c: &&Currency| !is_tradeable(c));
+        if let Some(untradeable) = untradeable {
+            issues.push(
+                ValidationIssue::new(
+                    "underlying",
+                    format!("Underlying currency {} isn't tradeable", untradeable.code())
+                )
+            );
         }
 
         if !mut_details.underlying.contains(&mut_details.notional_currency) {
-            return Err(InvalidDetails {
-                issue: format!(
-                    "Currency {} not listed in the underlying {}",
-                    mut_details.notional_currency,
-                    mut_details.underlying
-                        .clone()
-                        .into_iter()
-                        .map(|c| format!("{},", c.to_string()))
-                        .collect::<String>()
-                        .trim_end_matches(",")
-                ),
-            });
+            issues.push(
+                ValidationIssue::new(
+                    "underlying",
+                    format!(
+                        "Currency {} not listed in the underlying {}",
+                        mut_details.notional_currency,
+                        mut_details.underlying
+                            .clone()
+                            .into_iter()
+                            .map(|c| format!("{},", c.to_string()))
+                            .collect::<String>()
+                            .trim_end_matches(",")
+                    )
+                )
+            );
+        } else if
+            policy.notional_first &&
+            mut_details.underlying.first() != Some(&mut_details.notional_currency)
+        {
+            issues.push(
+                ValidationIssue::new(
+                    "underlying",
+                    format!(
+                        "Currency {} isn't the base currency of the underlying",
+                        mut_details.notional_currency.code()
+                    )
+                )
+            );
+        }
+
+        if
+            let Some(registry) = &policy.counterparties &&
+            !registry.is_known(&mut_details.counterparty)
+        {
+            issues.push(
+                ValidationIssue::new(
+                    "counterparty",
+                    format!("Counterparty {} isn't known", mut_details.counterparty)
+                )
+            );
+        }
+
+        if
+            let Some(limit) = policy.notional_limits.limit_for(&mut_details.notional_currency) &&
+            mut_details.notional_amount > limit
+        {
+            issues.push(
+                ValidationIssue::new(
+                    "notional_amount",
+                    format!(
+                        "Notional amount {} exceeds the limit of {} for {}",
+                        mut_details.notional_amount,
+                        limit,
+                        mut_details.notional_currency.code()
+                    )
+                )
+            );
+        }
+
+        if
+            let Some(currencies) = &policy.currencies &&
+            !currencies.allows_notional(&mut_details.notional_currency)
+        {
+            issues.push(
+                ValidationIssue::new(
+                    "notional_currency",
+                    format!(
+                        "Notional currency {} isn't allowed",
+                        mut_details.notional_currency.code()
+                    )
+                )
+            );
+        }
+        if
+            let Some(currencies) = &policy.currencies &&
+            let Some(disallowed) = mut_details.underlying
+                .iter()
+                .find(|c: &&Currency| !currencies.allows_underlying(c))
+        {
+            issues.push(
+                ValidationIssue::new(
+                    "underlying",
+                    format!("Underlying currency {} isn't allowed", disallowed.code())
+                )
+            );
+        }
+
+        if policy.strict_exponents {
+            let exponent: Option<u16> = mut_details.notional_currency.exponent();
+            let mismatched: Option<&Currency> = mut_details.underlying
+                .iter()
+                .find(|c: &&Currency| c.exponent() != exponent);
+            if let Some(mismatched) = mismatched {
+                issues.push(
+                    ValidationIssue::new(
+                        "underlying",
+                        format!(
+                            "Underlying currency {} doesn't have the same decimal places as {}",
+                            mismatched.code(),
+                            mut_details.notional_currency.code()
+                        )
+                    )
+                );
+            }
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(InvalidDetails { issues })
+        }
+    }
+
+    /// Rejects an approver signing off a trade entered under their own id,
+    /// unless the current policy allows it.
+    pub fn check_approver(&self, approver: &User<Approver>) -> Result<(), SelfApproval> {
+        self.check_approver_with(approver, &current_policy())
+    }
+
+    pub fn check_approver_with(
+        &self,
+        approver: &User<Approver>,
+        policy: &TradePolicy
+    ) -> Result<(), SelfApproval> {
+        let approver_id: String = approver.to_string();
+        if !policy.allow_self_approval && approver_id == self.trading_entity.to_string() {
+            return Err(SelfApproval { approver: approver_id });
         }
+        Ok(())
+    }
+
+    /// Rejects booking an option at a strike of zero, if the current policy
+    /// requires options to carry a strike.
+    pub fn check_option_strike(&self, strike: Decimal) -> Result<(), MissingStrike> {
+        self.check_option_strike_with(strike, &current_policy())
+    }
 
+    pub fn check_option_strike_with(
+        &self,
+        strike: Decimal,
+        policy: &TradePolicy
+    ) -> Result<(), MissingStrike> {
+        let option: bool = self.mutable_details.style == Style::Option;
+        if policy.options_need_strike && option && strike.is_zero() {
+            return Err(MissingStrike { style: self.mutable_details.style.to_string() });
+        }
         Ok(())
     }
 
@@ -301,6 +1124,7 @@ impl<S: TradeState> TradeDetails<S> {
     /// The full list is available at IBAN Currency Codes.
     /// 
     /// `amount` - The size of the trade in the selected notional currency.
+    /// Integer amounts (e.g. `u64`) convert directly.
     /// 
     /// `underlying` - A combination of eligible notional currencies. 
     /// The notional currency selected must be part of the underlying.
@@ -315,26 +1139,50 @@ impl<S: TradeState> TradeDetails<S> {
         direction: Direction,
         style: Style,
         currency: Currency,
-        amount: u64,
+        amount: impl Into<Decimal>,
         underlying: Vec<Currency>,
         value_date: DateTime<Utc>,
         delivery_date: DateTime<Utc>
     ) -> Result<TradeDetails<Draft>, InvalidDetails> {
-        let details = TradeDetails {
-            trading_entity: user.clone(),
-            mutable_details: MutTradeDetails {
-                counterparty,
-                direction,
-                style,
-                notional_currency: currency,
-                notional_amount: amount,
-                underlying,
-                value_date,
-                delivery_date,
-            },
-            trade_date: Utc::now(),
-            strike: None,
-            _state: PhantomData,
+        let details: MutTradeDetails = MutTradeDetails {
+            counterparty,
+            direction,
+            style,
+            notional_currency: currency,
+            notional_amount: amount.into(),
+            underlying,
+            value_date,
+            delivery_date,
+        };
+        Self::new_with_trade_date(user, details, clock::now())
+    }
+
+    /// Creates a Draft Trade Request of the `details`, as `new` does, but with
+    /// an explicit `trade_date` for entering historical trades. The trade date
+    /// can't be in the future, and the value and delivery dates must still follow it.
+    pub fn new_with_trade_date(
+        user: &User<Requester>,
+        details: MutTradeDetails,
+        trade_date: DateTime<Utc>
+    ) -> Result<TradeDetails<Draft>, InvalidDetails> {
+        if trade_date > clock::now() {
+            return Err(
+                InvalidDetails::from(
+                    ValidationIssue::new("trade_date", "Trade date can't be in the future")
+                )
+            );
+        }
+
+        let details = TradeDetails {
+            id: Uuid::new_v4(),
+            trading_entity: user.clone(),
+            mutable_details: details.canonicalised(),
+            trade_date,
+            strike: None,
+            confirmation_reference: None,
+            quorum: None,
+            expires_at: None,
+            _state: PhantomData,
         };
 
         details.check_details(&details.mutable_details)?;
@@ -345,91 +1193,699 @@ impl<S: TradeState> TradeDetails<S> {
     pub fn state() -> &'static str {
         S::NAME
     }
+
+    /// Steps the trade back to the state before its most recent transition,
+    /// restoring any details that transition changed, and withdrawing the
+    /// sign off of an accept towards a quorum. The `record` must be that
+    /// transition, see `TradeHistory::last_reversible`, so a record
+    /// superseded since it was read is rejected.
+    pub fn revert<To: TradeState>(
+        self,
+        approver: &User<Approver>,
+        record: &HistoricalRecord,
+        note: Option<Note>
+    ) -> Result<TradeDetails<To>, InvalidRevert>
+        where StateMachine: AllowedTransition<S, To>
+    {
+        if record.trade_id() != &self.id {
+            return Err(InvalidRevert { issue: "Record belongs to another trade".to_string() });
+        }
+        let latest: bool = HISTORY.lock()
+            .unwrap()
+            .last_record(self.id)
+            .is_some_and(|last: &HistoricalRecord| last == record);
+        if !latest {
+            return Err(InvalidRevert { issue: "Record isn't the trade's most recent".to_string() });
+        }
+        if !record.is_reversible() {
+            return Err(InvalidRevert {
+                issue: format!("A {} can't be reverted", record.action().to_string()),
+            });
+        }
+        if record.state_after() != S::NAME || record.state_before() != To::NAME {
+            return Err(InvalidRevert {
+                issue: format!(
+                    "Record moved from {} to {}, not from {} to {}",
+                    record.state_before(),
+                    record.state_after(),
+                    To::NAME,
+                    S::NAME
+                ),
+            });
+        }
+
+        Ok(
+            approver.transition::<S, To>(
+                self,
+                |details| {
+                    if let Some(diff) = record.changes() {
+                        diff.revert(&mut details.mutable_details);
+                    }
+                    if
+                        *record.action() == TradeAction::Accept &&
+                        let Some(quorum) = details.quorum.as_mut()
+                    {
+                        quorum.signed_off.retain(|id: &String| id != record.user_id());
+                    }
+                },
+                TradeAction::Revert,
+                note
+            )
+        )
+    }
 }
 
 impl<S: TradeState> Clone for TradeDetails<S> {
     fn clone(&self) -> Self {
         Self {
+            id: self.id,
             trading_entity: self.trading_entity.clone(),
             mutable_details: self.mutable_details.clone(),
             trade_date: self.trade_date.clone(),
             strike: self.strike.clone(),
+            confirmation_reference: self.confirmation_reference.clone(),
+            quorum: self.quorum.clone(),
+            expires_at: self.expires_at,
             _state: PhantomData,
         }
     }
 }
 
+impl<S: TradeState> PartialEq for TradeDetails<S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.content_eq(other)
+    }
+}
+impl<S: TradeState> Eq for TradeDetails<S> {}
+
 impl<S: CancellableState> TradeDetails<S> {
-    pub fn cancel<U: Transitioner>(self, user: &U) -> U::TransitionResult<S, Cancelled> {
-        user.transition(self, |_| {}, TradeAction::Cancel)
+    /// Cancels the trade as the requester, which is only authorised
+    /// when they're the trading entity of the trade.
+    pub fn cancel_as_requester(
+        self,
+        requester: &User<Requester>,
+        note: Option<Note>
+    ) -> Result<TradeDetails<Cancelled>, UnauthorisedRequester<S>> {
+        requester.transition(self, |_| {}, TradeAction::Cancel, note)
+    }
+
+    /// Cancels the trade as the approver, who may cancel any trade.
+    pub fn cancel_as_approver(
+        self,
+        approver: &User<Approver>,
+        note: Option<Note>
+    ) -> TradeDetails<Cancelled> {
+        approver.transition(self, |_| {}, TradeAction::Cancel, note)
+    }
+
+    /// Cancels the trade as either kind of user, the result depending on the
+    /// user's `Transitioner` implementation. Prefer `cancel_as_requester` or
+    /// `cancel_as_approver`, whose authorisation is explicit.
+    pub fn cancel<U: Transitioner>(
+        self,
+        user: &U,
+        note: Option<Note>
+    ) -> U::TransitionResult<S, Cancelled> {
+        user.transition(self, |_| {}, TradeAction::Cancel, note)
     }
 }
 
 impl TradeDetails<Draft> {
+    /// Revises the draft before it's submitted, which only its trading entity may do.
+    pub fn edit(
+        self,
+        requester: &User<Requester>,
+        new_details: MutTradeDetails,
+        note: Option<Note>
+    ) -> Result<TradeDetails<Draft>, InvalidEdit> {
+        self.check_details(&new_details)?;
+        Ok(
+            requester.transition::<Draft, Draft>(
+                self,
+                |details| {
+                    details.mutable_details = new_details.canonicalised();
+                },
+                TradeAction::Update,
+                note
+            )?
+        )
+    }
+
     pub fn submit(
         self,
-        requester: &User<Requester>
+        requester: &User<Requester>,
+        note: Option<Note>
     ) -> Result<TradeDetails<PendingApproval>, UnauthorisedRequester<Draft>> {
-        requester.transition::<Draft, PendingApproval>(self, |_| {}, TradeAction::Submit)
+        requester.transition::<Draft, PendingApproval>(self, |_| {}, TradeAction::Submit, note)
+    }
+
+    /// Submits the trade requiring sign off from `approvals_required`
+    /// distinct approvers before it's approved. Requiring none is rejected.
+    pub fn submit_for_approvals(
+        self,
+        requester: &User<Requester>,
+        approvals_required: usize,
+        note: Option<Note>
+    ) -> Result<TradeDetails<PartiallyApproved>, InvalidSubmission> {
+        if approvals_required == 0 {
+            return Err(EmptyQuorum.into());
+        }
+        Ok(
+            requester.transition::<Draft, PartiallyApproved>(
+                self,
+                |details| {
+                    details.quorum = Some(Quorum {
+                        required: approvals_required,
+                        signed_off: Vec::new(),
+                    });
+                },
+                TradeAction::Submit,
+                note
+            )?
+        )
+    }
+
+    /// Replaces the randomly generated id. Only drafts may be given another id,
+    /// as nothing has been recorded against them yet.
+    pub fn with_id(mut self, id: Uuid) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// Sets the trade to lapse `ttl` after its trade date.
+    pub fn expire_after(mut self, ttl: TimeDelta) -> Self {
+        self.expires_at = Some(self.trade_date + ttl);
+        self
+    }
+
+    /// Sets the value and delivery dates together, validating the pair
+    /// against the trade date in one go. Editing them one at a time could
+    /// otherwise pass through an invalid ordering.
+    pub fn with_dates(
+        mut self,
+        value_date: DateTime<Utc>,
+        delivery_date: DateTime<Utc>
+    ) -> Result<Self, InvalidDetails> {
+        let mut new_details: MutTradeDetails = self.mutable_details.clone();
+        new_details.value_date = value_date;
+        new_details.delivery_date = delivery_date;
+        self.check_details(&new_details)?;
+        self.mutable_details = new_details;
+        Ok(self)
     }
 }
 
 impl TradeDetails<PendingApproval> {
-    pub fn accept(self, approver: &User<Approver>) -> TradeDetails<Approved> {
-        approver.transition::<PendingApproval, Approved>(self, |_| {}, TradeAction::Accept)
+    pub fn accept(
+        self,
+        approver: &User<Approver>,
+        note: Option<Note>
+    ) -> Result<TradeDetails<Approved>, SelfApproval> {
+        self.check_approver(approver)?;
+        Ok(
+            approver.transition::<PendingApproval, Approved>(
+                self,
+                |_| {},
+                TradeAction::Accept,
+                note
+            )
+        )
+    }
+
+    /// Declines the trade as the approver, recording the reason against the
+    /// transition. Unlike `cancel`, only an approver may reject.
+    pub fn reject_with_reason(
+        self,
+        approver: &User<Approver>,
+        reason: Note
+    ) -> Result<TradeDetails<Rejected>, MissingReason> {
+        if reason.as_str().trim().is_empty() {
+            return Err(MissingReason);
+        }
+        Ok(
+            approver.transition::<PendingApproval, Rejected>(
+                self,
+                |_| {},
+                TradeAction::Reject,
+                Some(reason)
+            )
+        )
     }
 
     pub fn grab_mut_details(&self) -> MutTradeDetails {
-        self.mutable_details.clone()
+        self.snapshot()
     }
 
     pub fn update(
         self,
         approver: &User<Approver>,
-        new_details: MutTradeDetails
+        new_details: MutTradeDetails,
+        note: Option<Note>
     ) -> Result<TradeDetails<NeedsReapproval>, InvalidDetails> {
         self.check_details(&new_details)?;
         Ok(
             approver.transition::<PendingApproval, NeedsReapproval>(
                 self,
                 |details| {
-                    details.mutable_details = new_details;
+                    details.mutable_details = new_details.canonicalised();
                 },
-                TradeAction::Update
+                TradeAction::Update,
+                note
             )
         )
     }
+
+    /// Updates just the underlying basket, which must still include the
+    /// notional currency.
+    pub fn with_updated_underlying(
+        self,
+        approver: &User<Approver>,
+        underlying: Vec<Currency>,
+        note: Option<Note>
+    ) -> Result<TradeDetails<NeedsReapproval>, InvalidDetails> {
+        let mut new_details: MutTradeDetails = self.snapshot();
+        new_details.underlying = underlying;
+        self.update(approver, new_details, note)
+    }
+}
+
+impl TradeDetails<Rejected> {
+    /// Returns the trade to a draft with its details intact, so the original
+    /// requester can revise and resubmit it.
+    pub fn reopen(
+        self,
+        requester: &User<Requester>,
+        note: Option<Note>
+    ) -> Result<TradeDetails<Draft>, UnauthorisedRequester<Rejected>> {
+        requester.transition(self, |_| {}, TradeAction::Reopen, note)
+    }
+}
+
+/// The result of an approver signing off a trade requiring several approvals.
+pub enum QuorumProgress {
+    /// Sign offs are still outstanding.
+    Pending(TradeDetails<PartiallyApproved>),
+    /// The final sign off was made.
+    Approved(TradeDetails<Approved>),
+}
+
+impl TradeDetails<PartiallyApproved> {
+    /// Ids of the approvers who have signed off so far.
+    pub fn signed_off(&self) -> &[String] {
+        match &self.quorum {
+            Some(quorum) => &quorum.signed_off,
+            None => &[],
+        }
+    }
+
+    pub fn approvals_remaining(&self) -> usize {
+        self.quorum
+            .as_ref()
+            .map_or(1, |quorum: &Quorum| quorum.required.saturating_sub(quorum.signed_off.len()))
+    }
+
+    /// Signs off the trade as the approver, only approving it once the
+    /// quorum is met. Each approver may sign off once.
+    pub fn accept(
+        self,
+        approver: &User<Approver>,
+        note: Option<Note>
+    ) -> Result<QuorumProgress, InvalidApproval> {
+        self.check_approver(approver)?;
+        let approver_id: String = approver.to_string();
+        if self.signed_off().contains(&approver_id) {
+            return Err(DuplicateApproval { approver: approver_id }.into());
+        }
+
+        let final_sign_off: bool = self.approvals_remaining() <= 1;
+        let sign_off = move |details: &mut Self| {
+            if let Some(quorum) = details.quorum.as_mut() {
+                quorum.signed_off.push(approver_id);
+            }
+        };
+        if final_sign_off {
+            Ok(
+                QuorumProgress::Approved(
+                    approver.transition::<PartiallyApproved, Approved>(
+                        self,
+                        sign_off,
+                        TradeAction::Accept,
+                        note
+                    )
+                )
+            )
+        } else {
+            Ok(
+                QuorumProgress::Pending(
+                    approver.transition::<PartiallyApproved, PartiallyApproved>(
+                        self,
+                        sign_off,
+                        TradeAction::Accept,
+                        note
+                    )
+                )
+            )
+        }
+    }
 }
 
 impl TradeDetails<NeedsReapproval> {
     pub fn approve(
         self,
-        requester: &User<Requester>
+        requester: &User<Requester>,
+        note: Option<Note>
     ) -> Result<TradeDetails<Approved>, UnauthorisedRequester<NeedsReapproval>> {
-        requester.transition(self, |_| {}, TradeAction::Approve)
+        requester.transition(self, |_| {}, TradeAction::Approve, note)
+    }
+
+    /// Rejects the approver's amendment, restoring the details from before it
+    /// as recorded in the history, and returns the trade for approval.
+    /// Only its trading entity may do so, and only while the amendment is
+    /// still recorded, as the details otherwise can't be restored.
+    pub fn reject_changes(
+        self,
+        requester: &User<Requester>,
+        note: Option<Note>
+    ) -> Result<TradeDetails<PendingApproval>, InvalidRejection> {
+        let amendment: Option<HistoricalRecord> = HISTORY.lock()
+            .unwrap()
+            .records_for(self.id)
+            .into_iter()
+            .rev()
+            .find(|record: &HistoricalRecord| record.state_after() == NeedsReapproval::NAME);
+        let Some(amendment) = amendment else {
+            return Err(MissingAmendment { trade: self.id.to_string() }.into());
+        };
+        Ok(
+            requester.transition(
+                self,
+                |details| {
+                    if let Some(diff) = amendment.changes() {
+                        diff.revert(&mut details.mutable_details);
+                    }
+                },
+                TradeAction::Reject,
+                note
+            )?
+        )
     }
 }
 
 impl TradeDetails<Approved> {
-    pub fn send_to_execute(self, approver: &User<Approver>) -> TradeDetails<SentToCounterparty> {
+    /// Reopens the approved trade for a late correction, which the requester
+    /// must then approve again, as for an update pending approval.
+    pub fn amend(
+        self,
+        approver: &User<Approver>,
+        new_details: MutTradeDetails,
+        note: Option<Note>
+    ) -> Result<TradeDetails<NeedsReapproval>, InvalidDetails> {
+        self.check_details(&new_details)?;
+        Ok(
+            approver.transition::<Approved, NeedsReapproval>(
+                self,
+                |details| {
+                    details.mutable_details = new_details.canonicalised();
+                },
+                TradeAction::Update,
+                note
+            )
+        )
+    }
+
+    /// Splits the trade into two tranches to be executed separately, each a
+    /// new trade of its own amount, which must be positive and sum exactly to
+    /// the notional. This trade, which is consumed, records the split naming
+    /// both tranches, ending its lifecycle, and each tranche records it linked
+    /// back to this trade.
+    pub fn split(
+        self,
+        approver: &User<Approver>,
+        amounts: (Decimal, Decimal),
+        note: Option<Note>
+    ) -> Result<(TradeDetails<Approved>, TradeDetails<Approved>), InvalidSplit> {
+        let (first, second) = amounts;
+        if first <= Decimal::ZERO || second <= Decimal::ZERO {
+            return Err(InvalidSplit { issue: "Both tranches must be positive".to_string() });
+        }
+        if first + second != self.mutable_details.notional_amount {
+            return Err(InvalidSplit {
+                issue: format!(
+                    "Tranches of {} and {} don't sum to the notional amount of {}",
+                    first,
+                    second,
+                    self.mutable_details.notional_amount
+                ),
+            });
+        }
+        let tranche = |amount: Decimal| -> Result<TradeDetails<Approved>, InvalidSplit> {
+            let mut tranche: TradeDetails<Approved> = self.clone();
+            tranche.id = Uuid::new_v4();
+            tranche.mutable_details.notional_amount = amount;
+            self.check_details(&tranche.mutable_details)
+                .map_err(|e: InvalidDetails| InvalidSplit { issue: e.issue() })?;
+            Ok(tranche)
+        };
+        let tranches: (TradeDetails<Approved>, TradeDetails<Approved>) = (
+            tranche(first)?,
+            tranche(second)?,
+        );
+
+        // Recorded against the parent too, which is left for the tranches.
+        let mut history = HISTORY.lock().unwrap();
+        let ids: Vec<Uuid> = vec![tranches.0.id, tranches.1.id];
+        history.add_record(
+            HistoricalRecord::split(approver.to_string(), &self, &self, ids, note.clone())
+        );
+        for tranche in [&tranches.0, &tranches.1] {
+            history.add_record(
+                HistoricalRecord::split(
+                    approver.to_string(),
+                    &self,
+                    tranche,
+                    vec![self.id],
+                    note.clone()
+                )
+            );
+        }
+        Ok(tranches)
+    }
+
+    pub fn send_to_execute(
+        self,
+        approver: &User<Approver>,
+        note: Option<Note>
+    ) -> TradeDetails<SentToCounterparty> {
         approver.transition::<Approved, SentToCounterparty>(
             self,
             |_| {},
-            TradeAction::SendToExecute
+            TradeAction::SendToExecute,
+            note
         )
     }
 }
 
+impl TradeDetails<Executed> {
+    /// How the trade settles, derived from its terms and the strike it was
+    /// booked at, none of which can change once executed. Only a pair, the
+    /// notional currency and one other, has a single currency to settle in.
+    pub fn settlement(&self) -> Option<SettlementDetails> {
+        let details: &MutTradeDetails = &self.mutable_details;
+        let [first, second] = details.underlying.as_slice() else {
+            return None;
+        };
+        let is_notional = |currency: &Currency| -> bool { *currency == details.notional_currency };
+        let settlement_currency: Currency = match (is_notional(first), is_notional(second)) {
+            (true, false) => *second,
+            (false, true) => *first,
+            _ => return None,
+        };
+        let gross: Decimal = details.notional_amount * self.strike.unwrap_or_default();
+        let gross: Decimal = match settlement_currency.exponent() {
+            Some(exponent) => gross.round_dp(exponent as u32),
+            None => gross,
+        };
+        Some(SettlementDetails {
+            settlement_date: details.delivery_date,
+            settlement_currency,
+            net_amount: match details.direction {
+                Direction::BUY => -gross,
+                Direction::SELL => gross,
+            },
+        })
+    }
+}
+
 impl TradeDetails<SentToCounterparty> {
+    /// Corrects the details of a trade which hasn't been booked yet (e.g. a
+    /// slipped delivery date), returning it to approved to be sent again.
+    pub fn amend_before_book(
+        self,
+        approver: &User<Approver>,
+        new_details: MutTradeDetails,
+        note: Option<Note>
+    ) -> Result<TradeDetails<Approved>, InvalidDetails> {
+        self.check_details(&new_details)?;
+        Ok(
+            approver.transition::<SentToCounterparty, Approved>(
+                self,
+                |details| {
+                    details.mutable_details = new_details.canonicalised();
+                },
+                TradeAction::Update,
+                note
+            )
+        )
+    }
+
+    /// Books the trade at the strike, once the counterparty has confirmed it,
+    /// keeping their reference for the confirmation on the executed trade.
+    /// A missing confirmation, or one without a reference, is rejected, as is
+    /// a strike the current policy doesn't allow, see `book_with`.
     pub fn book<U: Transitioner>(
         self,
-        strike_price: u64,
-        user: &U
-    ) -> U::TransitionResult<SentToCounterparty, Executed> {
+        strike_price: impl Into<Decimal>,
+        confirmation: Option<&CounterpartyConfirmation>,
+        user: &U,
+        note: Option<Note>
+    ) -> Result<U::TransitionResult<SentToCounterparty, Executed>, InvalidBooking> {
+        self.book_with(strike_price, confirmation, user, note, &current_policy())
+    }
+
+    /// Books the trade as `book` does, but with the strike checked against the
+    /// given policy's band and option strike requirement.
+    pub(crate) fn book_with<U: Transitioner>(
+        self,
+        strike_price: impl Into<Decimal>,
+        confirmation: Option<&CounterpartyConfirmation>,
+        user: &U,
+        note: Option<Note>,
+        policy: &TradePolicy
+    ) -> Result<U::TransitionResult<SentToCounterparty, Executed>, InvalidBooking> {
+        let reference: Option<String> = confirmation
+            .map(|confirmation: &CounterpartyConfirmation| confirmation.reference().trim())
+            .filter(|reference: &&str| !reference.is_empty())
+            .map(str::to_string);
+        let Some(reference) = reference else {
+            return Err(
+                InvalidBooking::from(
+                    UnconfirmedBooking { counterparty: self.counterparty().to_string() }
+                )
+            );
+        };
+        let strike_price: Decimal = strike_price.into();
+        if let Some(band) = &policy.strike_band {
+            band.check(strike_price)?;
+        }
+        self.check_option_strike_with(strike_price, policy)?;
         let mutation = |s: &mut Self| -> () {
+            let before: Self = s.clone();
             s.strike = Some(strike_price);
+            s.confirmation_reference = Some(reference);
+            debug_assert!(
+                before.equal_ignoring_strike(s),
+                "Booking may only set the strike and confirmation."
+            );
         };
-        user.transition::<SentToCounterparty, Executed>(self, mutation, TradeAction::Book)
+        Ok(
+            user.transition::<SentToCounterparty, Executed>(
+                self,
+                mutation,
+                TradeAction::Book,
+                note
+            )
+        )
+    }
+}
+
+/// Whether the ISO 4217 code is a currency which can be traded. XTS is
+/// reserved for testing and XXX denotes no currency at all, so neither is,
+/// even though both deserialize like any other code.
+fn is_tradeable(currency: &Currency) -> bool {
+    !["XTS", "XXX"].contains(&currency.code())
+}
+
+/// Amounts are held in major units (e.g. 12.34 USD, 1234 JPY), and must be
+/// expressible in whole minor units of the currency, so may carry no more
+/// decimal places than its exponent. Currencies without minor units, such as
+/// precious metals, accept any precision.
+pub fn validate_currency_precision_for_amount(
+    currency: &Currency,
+    amount: Decimal
+) -> Result<(), InvalidDetails> {
+    if let Some(exponent) = currency.exponent() && amount.normalize().scale() > (exponent as u32) {
+        let message: String = format!(
+            "Notional amount {} has more than the {} decimal places allowed for {}",
+            amount,
+            exponent,
+            currency.code()
+        );
+        return Err(InvalidDetails::from(ValidationIssue::new("notional_amount", message)));
+    }
+    Ok(())
+}
+
+/// Converts an amount in major units to whole minor units of the currency
+/// (e.g. 12.34 USD to 1234 cents). `None` if the currency has no minor units,
+/// or the amount isn't a whole number of them.
+pub fn to_minor_units(currency: &Currency, amount: Decimal) -> Option<i64> {
+    let exponent: u32 = currency.exponent()? as u32;
+    let minor: Decimal = amount.checked_mul(Decimal::from(10_i64.pow(exponent)))?;
+    if !minor.fract().is_zero() {
+        return None;
+    }
+    i64::try_from(minor).ok()
+}
+
+/// Converts whole minor units of the currency to an amount in major units
+/// (e.g. 1234 cents to 12.34 USD). `None` if the currency has no minor units.
+pub fn from_minor_units(currency: &Currency, minor: i64) -> Option<Decimal> {
+    Some(Decimal::new(minor, currency.exponent()? as u32))
+}
+
+#[cfg(feature = "serde")]
+/// Creates drafts for the user from a JSON array of `MutTradeDetails`, as the
+/// details are described on the wire. Every row is validated as `new` would,
+/// with any failing rows reported alongside the drafts which were imported.
+pub fn import_drafts(
+    json: &str,
+    user: &User<Requester>
+) -> Result<Vec<TradeDetails<Draft>>, ImportError> {
+    let rows: Vec<serde_json::Value> = serde_json
+        ::from_str(json)
+        .map_err(|e: serde_json::Error| ImportError::Malformed(e.to_string()))?;
+
+    let mut imported: Vec<TradeDetails<Draft>> = Vec::new();
+    let mut failures: Vec<ImportFailure> = Vec::new();
+    for (index, row) in rows.into_iter().enumerate() {
+        let draft: Result<TradeDetails<Draft>, String> = serde_json
+            ::from_value::<MutTradeDetails>(row)
+            .map_err(|e: serde_json::Error| e.to_string())
+            .and_then(|details: MutTradeDetails| {
+                TradeDetails::<Draft>
+                    ::new(
+                        user,
+                        details.counterparty,
+                        details.direction,
+                        details.style,
+                        details.notional_currency,
+                        details.notional_amount,
+                        details.underlying,
+                        details.value_date,
+                        details.delivery_date
+                    )
+                    .map_err(|e: InvalidDetails| e.issue())
+            });
+        match draft {
+            Ok(draft) => imported.push(draft),
+            Err(issue) => failures.push(ImportFailure { index, issue }),
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(imported)
+    } else {
+        Err(ImportError::Rows { imported, failures })
     }
 }
 
@@ -438,6 +1894,11 @@ pub(crate) mod tests {
     use std::{ time::Duration };
 
     use super::*;
+    use crate::{
+        clock::FixedClock,
+        error::StrikeOutOfBand,
+        policy::{ CounterpartyRegistry, CurrencyPolicy, NotionalLimits, StrikeBand },
+    };
 
     #[test]
     fn bad_drafts() {
@@ -450,7 +1911,7 @@ pub(crate) mod tests {
                 &requester,
                 Counterparty("TestCounterParty".to_string()),
                 Direction::BUY,
-                Style("Some Style".to_string()),
+                Style::Other("Some Style".to_string()),
                 Currency::GBP,
                 100,
                 vec![Currency::EUR],
@@ -468,7 +1929,7 @@ pub(crate) mod tests {
                 &requester,
                 Counterparty("TestCounterParty".to_string()),
                 Direction::BUY,
-                Style("Some Style".to_string()),
+                Style::Other("Some Style".to_string()),
                 Currency::USD,
                 100,
                 vec![Currency::USD, Currency::GBP, Currency::EUR],
@@ -480,66 +1941,829 @@ pub(crate) mod tests {
         }
     }
 
-    pub(crate) fn mock_draft(requester: &User<Requester>) -> TradeDetails<Draft> {
-        let offset: Duration = Duration::from_secs(20);
-        let value_date: DateTime<Utc> = Utc::now() + offset;
-        let delivery_date: DateTime<Utc> = value_date + offset;
-        let wrapped_details: Result<TradeDetails, _> = TradeDetails::<Draft>::new(
+    #[test]
+    fn same_value_and_delivery_date() {
+        let requester: User<Requester> = User::<Requester>::sign_in("Naughty");
+        let value_date: DateTime<Utc> = Utc::now() + Duration::from_secs(20);
+        let wrapped_details: Result<TradeDetails, InvalidDetails> = TradeDetails::<Draft>::new(
             &requester,
             Counterparty("TestCounterParty".to_string()),
             Direction::BUY,
-            Style("Some Style".to_string()),
+            Style::Other("Some Style".to_string()),
             Currency::GBP,
             100,
-            vec![Currency::GBP, Currency::EUR],
+            vec![Currency::GBP],
             value_date,
-            delivery_date
+            value_date
         );
 
-        assert!(wrapped_details.is_ok());
-        wrapped_details.unwrap()
+        assert_eq!(
+            wrapped_details.unwrap_err().to_string(),
+            "Failed to create a draft. Delivery date must be after the value date."
+        );
     }
 
     #[test]
-    fn submitting_and_approving_a_trade() {
-        // Draft
+    fn several_problems_reported() {
+        let requester: User<Requester> = User::<Requester>::sign_in("Naughty");
+        let value_date: DateTime<Utc> = Utc::now() + Duration::from_secs(20);
+        let delivery_date: DateTime<Utc> = value_date - Duration::from_secs(10);
+        let error: InvalidDetails = TradeDetails::<Draft>::new(
+            &requester,
+            Counterparty("TestCounterParty".to_string()),
+            Direction::BUY,
+            Style::Other("Some Style".to_string()),
+            Currency::GBP,
+            100,
+            vec![Currency::EUR],
+            value_date,
+            delivery_date
+        ).unwrap_err();
+
+        let fields: Vec<&str> = error
+            .issues()
+            .iter()
+            .map(|issue: &ValidationIssue| issue.field)
+            .collect();
+        assert_eq!(fields, vec!["dates", "underlying"]);
+        assert!(error.issue().starts_with("Dates must be chronologically ordered; "));
+        assert!(error.to_string().contains("not listed in the underlying"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn importing_drafts() {
         let requester: User<Requester> = User::sign_in("TestUser");
-        let details: TradeDetails<Draft> = mock_draft(&requester);
+        let json: &str =
+            r#"[
+            {
+                "counterparty": "TestCounterParty",
+                "direction": "BUY",
+                "style": "Some Style",
+                "notional_currency": "GBP",
+                "notional_amount": "100.50",
+                "underlying": ["GBP", "EUR"],
+                "value_date": "2099-01-01T00:00:00Z",
+                "delivery_date": "2099-02-01T00:00:00Z"
+            },
+            {
+                "counterparty": "TestCounterParty",
+                "direction": "SELL",
+                "style": "Some Style",
+                "notional_currency": "GBP",
+                "notional_amount": "100",
+                "underlying": ["GBP"],
+                "value_date": "2099-02-01T00:00:00Z",
+                "delivery_date": "2099-01-01T00:00:00Z"
+            }
+        ]"#;
+
+        let Err(ImportError::Rows { imported, failures }) = import_drafts(json, &requester) else {
+            panic!("The date-inverted row should fail.");
+        };
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].amount(), Decimal::new(10050, 2));
+        assert_eq!(imported[0].direction(), &Direction::BUY);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].index, 1);
+        assert_eq!(failures[0].issue, "Dates must be chronologically ordered");
+
+        assert!(matches!(import_drafts("{}", &requester), Err(ImportError::Malformed(_))));
+    }
 
-        // Submit
-        let wrapped_details: Result<TradeDetails<PendingApproval>, _> = details.submit(&requester);
-        assert!(wrapped_details.is_ok());
-        let details: TradeDetails<PendingApproval> = wrapped_details.unwrap();
+    #[cfg(feature = "serde")]
+    #[test]
+    fn importing_unknown_currencies() {
+        let row = |notional: &str, underlying: &str| {
+            format!(
+                r#"{{
+                    "counterparty": "TestCounterParty",
+                    "direction": "BUY",
+                    "style": "Some Style",
+                    "notional_currency": "{}",
+                    "notional_amount": "100",
+                    "underlying": ["{}"],
+                    "value_date": "2099-01-01T00:00:00Z",
+                    "delivery_date": "2099-02-01T00:00:00Z"
+                }}"#,
+                notional,
+                underlying
+            )
+        };
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let json: String = format!(
+            "[{}, {}, {}, {}]",
+            row("ZZZ", "ZZZ"),
+            row("826", "826"),
+            row("XTS", "XTS"),
+            row("GBP", "XXX")
+        );
 
-        // Approve
-        let approver: User<Approver> = User::<Approver>::sign_in("Admin");
-        let _: TradeDetails<Approved> = details.accept(&approver);
+        let Err(ImportError::Rows { imported, failures }) = import_drafts(&json, &requester) else {
+            panic!("Every row has an unknown or untradeable currency.");
+        };
+        assert!(imported.is_empty());
+        let indices: Vec<usize> = failures
+            .iter()
+            .map(|failure: &ImportFailure| failure.index)
+            .collect();
+        assert_eq!(indices, vec![0, 1, 2, 3]);
+        assert!(failures[2].issue.starts_with("Currency XTS isn't tradeable"));
+        assert!(failures[3].issue.contains("Underlying currency XXX isn't tradeable"));
     }
 
     #[test]
-    fn updating_a_trade_detail() {
-        // Draft
+    fn known_counterparties() {
         let requester: User<Requester> = User::sign_in("TestUser");
         let details: TradeDetails<Draft> = mock_draft(&requester);
+        let policy: TradePolicy = TradePolicy {
+            counterparties: Some(CounterpartyRegistry::new(["TestCounterParty", "Maggie"])),
+            ..TradePolicy::default()
+        };
 
-        // Submit
-        let wrapped_details: Result<TradeDetails<PendingApproval>, _> = details.submit(&requester);
-        assert!(wrapped_details.is_ok());
-        let details: TradeDetails<PendingApproval> = wrapped_details.unwrap();
+        let allowed: MutTradeDetails = details.mutable_details.clone();
+        assert!(details.check_details_with(&allowed, &policy).is_ok());
 
-        // Update
-        let approver: User<Approver> = User::sign_in("Admin");
-        let mut new_details: MutTradeDetails = details.grab_mut_details();
-        new_details.direction = Direction::SELL;
-        let wrapped_details: Result<TradeDetails<NeedsReapproval>, _> = details.update(
-            &approver,
-            new_details
-        );
-        assert!(wrapped_details.is_ok());
-        let details: TradeDetails<NeedsReapproval> = wrapped_details.unwrap();
+        let mut typo: MutTradeDetails = details.mutable_details.clone();
+        typo.counterparty = Counterparty("TestCounterPraty".to_string());
+        let error: InvalidDetails = details.check_details_with(&typo, &policy).unwrap_err();
+        assert_eq!(error.issue(), "Counterparty TestCounterPraty isn't known");
 
-        // Approve
-        let wrapped_details: Result<TradeDetails<Approved>, _> = details.approve(&requester);
+        // Any counterparty is allowed without a registry.
+        assert!(details.check_details_with(&typo, &TradePolicy::default()).is_ok());
+    }
+
+    #[test]
+    fn backdated_trades() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let trade_date: DateTime<Utc> = Utc::now() - TimeDelta::days(30);
+        let backdated = |trade_date: DateTime<Utc>| {
+            let details: MutTradeDetails = MutTradeDetails {
+                value_date: trade_date + TimeDelta::days(2),
+                delivery_date: trade_date + TimeDelta::days(4),
+                ..mock_draft(&requester).snapshot()
+            };
+            TradeDetails::<Draft>::new_with_trade_date(&requester, details, trade_date)
+        };
+
+        let details: TradeDetails<Draft> = backdated(trade_date).unwrap();
+        assert_eq!(details.trade_date(), &trade_date);
+
+        let error: InvalidDetails = backdated(Utc::now() + TimeDelta::days(1)).unwrap_err();
+        assert_eq!(error.issue(), "Trade date can't be in the future");
+    }
+
+    #[test]
+    fn currency_precision() {
+        let validate = validate_currency_precision_for_amount;
+        assert!(validate(&Currency::USD, Decimal::new(1234, 2)).is_ok());
+        assert!(validate(&Currency::USD, Decimal::new(12345, 3)).is_err());
+        // Trailing zeros don't count towards the precision.
+        assert!(validate(&Currency::USD, Decimal::new(12340, 3)).is_ok());
+        assert!(validate(&Currency::JPY, Decimal::from(1234)).is_ok());
+        assert!(validate(&Currency::JPY, Decimal::new(12345, 1)).is_err());
+
+        assert_eq!(to_minor_units(&Currency::USD, Decimal::new(1234, 2)), Some(1234));
+        assert_eq!(to_minor_units(&Currency::USD, Decimal::from(12)), Some(1200));
+        assert_eq!(to_minor_units(&Currency::USD, Decimal::new(12345, 3)), None);
+        assert_eq!(to_minor_units(&Currency::JPY, Decimal::from(1234)), Some(1234));
+        assert_eq!(to_minor_units(&Currency::JPY, Decimal::new(5, 1)), None);
+
+        assert_eq!(from_minor_units(&Currency::USD, 1234), Some(Decimal::new(1234, 2)));
+        assert_eq!(from_minor_units(&Currency::JPY, 1234), Some(Decimal::from(1234)));
+    }
+
+    #[test]
+    fn notional_in_major_units() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let mut details: TradeDetails<Draft> = mock_draft(&requester);
+
+        details.mutable_details.notional_currency = Currency::USD;
+        details.mutable_details.notional_amount = Decimal::new(12340, 3);
+        assert_eq!(details.notional_in_major_units().to_string(), "12.34");
+        details.mutable_details.notional_amount = Decimal::from(12);
+        assert_eq!(details.notional_in_major_units().to_string(), "12.00");
+
+        // Excess places, which checked amounts never have, are rounded.
+        details.mutable_details.notional_amount = Decimal::new(12345, 3);
+        assert_eq!(details.notional_in_major_units().to_string(), "12.35");
+        details.mutable_details.notional_amount = Decimal::new(-12345, 3);
+        assert_eq!(details.notional_in_major_units().to_string(), "-12.35");
+
+        // Yen have no minor units, so whole amounts are left as they are.
+        details.mutable_details.notional_currency = Currency::JPY;
+        details.mutable_details.notional_amount = Decimal::from(1234);
+        assert_eq!(details.notional_in_major_units().to_string(), "1234");
+        assert_eq!(details.notional_in_major_units(), details.amount());
+    }
+
+    #[test]
+    fn notional_as_base_currency() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let details: TradeDetails<Draft> = mock_draft(&requester);
+        let mut quoted: MutTradeDetails = details.mutable_details.clone();
+        quoted.underlying = vec![Currency::USD, Currency::GBP];
+        quoted.notional_currency = Currency::GBP;
+
+        let strict: TradePolicy = TradePolicy {
+            notional_first: true,
+            ..TradePolicy::default()
+        };
+        let error: InvalidDetails = details.check_details_with(&quoted, &strict).unwrap_err();
+        assert_eq!(error.issue(), "Currency GBP isn't the base currency of the underlying");
+
+        // Anywhere in the underlying by default.
+        assert!(details.check_details_with(&quoted, &TradePolicy::default()).is_ok());
+
+        let mut based: MutTradeDetails = quoted.clone();
+        based.underlying = vec![Currency::GBP, Currency::USD];
+        assert!(details.check_details_with(&based, &strict).is_ok());
+    }
+
+    #[test]
+    fn reordered_basket() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let draft: TradeDetails<Draft> = mock_draft(&requester);
+        let mut basket: MutTradeDetails = draft.snapshot();
+        basket.underlying = vec![Currency::GBP, Currency::EUR, Currency::USD];
+        let mut reordered: MutTradeDetails = basket.clone();
+        reordered.underlying = vec![Currency::GBP, Currency::USD, Currency::EUR];
+
+        // The base currency stays first, the others are ordered by numeric code.
+        assert_ne!(basket, reordered);
+        assert_eq!(basket.clone().canonicalised(), reordered.clone().canonicalised());
+        assert_eq!(reordered.clone().canonicalised().underlying, reordered.underlying);
+
+        let strict: TradePolicy = TradePolicy {
+            notional_first: true,
+            ..TradePolicy::default()
+        };
+        assert!(draft.check_details_with(&basket, &strict).is_ok());
+        assert!(draft.check_details_with(&reordered, &strict).is_ok());
+
+        let edited: TradeDetails<Draft> = draft.edit(&requester, basket, None).unwrap();
+        assert_eq!(edited.underlying(), &reordered.underlying);
+        let records_for = |id: &Uuid| -> usize {
+            crate::history::HISTORY.lock().unwrap().records_for(*id).len()
+        };
+        let records: usize = records_for(edited.id());
+
+        // Reordering the basket alone leaves the trade as it was, so isn't recorded.
+        let unchanged: TradeDetails<Draft> = edited
+            .clone()
+            .edit(&requester, reordered, None)
+            .unwrap();
+        assert!(TradeDetailsDiff::new(&edited, &unchanged).is_none());
+        assert_eq!(records_for(unchanged.id()), records);
+    }
+
+    #[test]
+    fn mirrored_trade() {
+        assert_eq!(Direction::BUY.opposite(), Direction::SELL);
+        assert_eq!(Direction::SELL.opposite(), Direction::BUY);
+
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let draft: TradeDetails<Draft> = mock_draft(&requester);
+        let hedge: Counterparty = Counterparty("HedgeCounterParty".to_string());
+        let mirror: TradeDetails<Draft> = draft.mirror(hedge.clone()).unwrap();
+
+        assert_ne!(mirror.id(), draft.id());
+        assert_eq!(mirror.direction(), &Direction::SELL);
+        assert_eq!(mirror.counterparty(), &hedge);
+        assert_eq!(mirror.trading_entity(), draft.trading_entity());
+        assert_eq!(mirror.trade_date(), draft.trade_date());
+        let mut expected: MutTradeDetails = draft.snapshot();
+        expected.counterparty = hedge;
+        expected.direction = Direction::SELL;
+        assert_eq!(mirror.snapshot(), expected);
+        let back: TradeDetails<Draft> = mirror.mirror(draft.counterparty().clone()).unwrap();
+        assert_eq!(back.snapshot(), draft.snapshot());
+    }
+
+    #[test]
+    fn mixed_exponent_basket() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let details: TradeDetails<Draft> = mock_draft(&requester);
+        let mut mixed: MutTradeDetails = details.mutable_details.clone();
+        mixed.underlying = vec![Currency::USD, Currency::JPY];
+        mixed.notional_currency = Currency::USD;
+
+        let strict: TradePolicy = TradePolicy {
+            strict_exponents: true,
+            ..TradePolicy::default()
+        };
+        let error: InvalidDetails = details.check_details_with(&mixed, &strict).unwrap_err();
+        assert_eq!(
+            error.issue(),
+            "Underlying currency JPY doesn't have the same decimal places as USD"
+        );
+
+        // Permissive by default.
+        assert!(details.check_details_with(&mixed, &TradePolicy::default()).is_ok());
+
+        let mut matching: MutTradeDetails = mixed.clone();
+        matching.underlying = vec![Currency::USD, Currency::GBP];
+        assert!(details.check_details_with(&matching, &strict).is_ok());
+    }
+
+    #[test]
+    fn amending_an_approved_trade() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let approver: User<Approver> = User::sign_in("Admin");
+        let approved: TradeDetails<Approved> = mock_draft(&requester)
+            .submit(&requester, None)
+            .unwrap()
+            .accept(&approver, None)
+            .unwrap();
+
+        let mut invalid: MutTradeDetails = approved.snapshot();
+        invalid.notional_amount = Decimal::ZERO;
+        assert!(approved.clone().amend(&approver, invalid, None).is_err());
+
+        let mut corrected: MutTradeDetails = approved.snapshot();
+        corrected.notional_amount = Decimal::from(250);
+        let amended: TradeDetails<NeedsReapproval> = approved
+            .amend(&approver, corrected.clone(), None)
+            .unwrap();
+        assert_eq!(amended.snapshot(), corrected);
+
+        let record: HistoricalRecord = crate::history::HISTORY
+            .lock()
+            .unwrap()
+            .last_record(*amended.id())
+            .unwrap()
+            .clone();
+        assert_eq!(record.state_before(), Approved::NAME);
+        assert_eq!(
+            record.changes().unwrap().changed_amount(),
+            Some((Decimal::from(100), Decimal::from(250)))
+        );
+
+        // The requester must approve the correction again.
+        let reapproved: TradeDetails<Approved> = amended.approve(&requester, None).unwrap();
+        assert_eq!(reapproved.amount(), Decimal::from(250));
+    }
+
+    #[test]
+    fn describing_a_trade() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let pending: TradeDetails<PendingApproval> = mock_draft(&requester)
+            .submit(&requester, None)
+            .unwrap();
+
+        let expected: String = format!(
+            "BUY 100 GBP vs [GBP, EUR] with TestCounterParty, value {}, PendingApproval",
+            pending.value_date().format("%Y-%m-%d")
+        );
+        assert_eq!(pending.describe(), expected);
+    }
+
+    #[test]
+    fn editing_a_draft() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let malicious: User<Requester> = User::sign_in("Malicious");
+        let draft: TradeDetails<Draft> = mock_draft(&requester);
+
+        let mut revised: MutTradeDetails = draft.snapshot();
+        revised.notional_amount = Decimal::from(250);
+        let error: InvalidEdit = draft
+            .clone()
+            .edit(&malicious, revised.clone(), None)
+            .unwrap_err();
+        assert!(matches!(error, InvalidEdit::Unauthorised(_)));
+
+        let mut invalid: MutTradeDetails = draft.snapshot();
+        invalid.notional_amount = Decimal::ZERO;
+        let error: InvalidEdit = draft.clone().edit(&requester, invalid, None).unwrap_err();
+        assert!(matches!(error, InvalidEdit::Details(_)));
+
+        let edited: TradeDetails<Draft> = draft.edit(&requester, revised.clone(), None).unwrap();
+        assert_eq!(edited.snapshot(), revised);
+
+        let record: HistoricalRecord = crate::history::HISTORY
+            .lock()
+            .unwrap()
+            .last_record(*edited.id())
+            .unwrap()
+            .clone();
+        assert_eq!(record.action(), &TradeAction::Update);
+        assert_eq!(record.state_before(), Draft::NAME);
+        assert_eq!(record.state_after(), Draft::NAME);
+        assert_eq!(
+            record.changes().unwrap().changed_amount(),
+            Some((Decimal::from(100), Decimal::from(250)))
+        );
+
+        // Still a draft, submitted as usual.
+        assert!(edited.submit(&requester, None).is_ok());
+    }
+
+    #[test]
+    fn amending_before_booking() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let approver: User<Approver> = User::sign_in("Admin");
+        let sent: TradeDetails<SentToCounterparty> = mock_draft(&requester)
+            .submit(&requester, None)
+            .unwrap()
+            .accept(&approver, None)
+            .unwrap()
+            .send_to_execute(&approver, None);
+
+        let mut invalid: MutTradeDetails = sent.snapshot();
+        invalid.delivery_date = invalid.value_date;
+        assert!(sent.clone().amend_before_book(&approver, invalid, None).is_err());
+
+        let mut slipped: MutTradeDetails = sent.snapshot();
+        slipped.delivery_date = slipped.delivery_date + TimeDelta::days(2);
+        let amended: TradeDetails<Approved> = sent
+            .clone()
+            .amend_before_book(&approver, slipped.clone(), None)
+            .unwrap();
+        assert_eq!(amended.snapshot(), slipped);
+
+        let record: HistoricalRecord = crate::history::HISTORY
+            .lock()
+            .unwrap()
+            .last_record(*amended.id())
+            .unwrap()
+            .clone();
+        assert_eq!(record.state_before(), SentToCounterparty::NAME);
+        assert_eq!(record.state_after(), Approved::NAME);
+        assert_eq!(
+            record.changes().unwrap().changed_delivery_date(),
+            Some(&(*sent.delivery_date(), slipped.delivery_date))
+        );
+
+        // The corrected trade is sent again, and booked as usual.
+        let executed: TradeDetails<Executed> = amended
+            .send_to_execute(&approver, None)
+            .book(Decimal::from(1), Some(&confirmation()), &approver, None)
+            .unwrap();
+        assert_eq!(*executed.delivery_date(), slipped.delivery_date);
+    }
+
+    #[test]
+    fn updating_the_basket() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let approver: User<Approver> = User::sign_in("Admin");
+        let details: TradeDetails<PendingApproval> = mock_draft(&requester)
+            .submit(&requester, None)
+            .unwrap();
+
+        let updated: TradeDetails<NeedsReapproval> = details
+            .clone()
+            .with_updated_underlying(&approver, vec![Currency::GBP, Currency::USD], None)
+            .unwrap();
+        assert_eq!(updated.underlying(), &vec![Currency::GBP, Currency::USD]);
+        assert_eq!(updated.currency(), details.currency());
+
+        // Dropping the notional currency from the basket.
+        let error: InvalidDetails = details
+            .with_updated_underlying(&approver, vec![Currency::USD], None)
+            .unwrap_err();
+        assert!(error.issue().contains("not listed in the underlying"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn strike_only_when_executed() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let approver: User<Approver> = User::sign_in("Admin");
+        let approved: TradeDetails<Approved> = mock_draft(&requester)
+            .submit(&requester, None)
+            .unwrap()
+            .accept(&approver, None)
+            .unwrap();
+        let executed: TradeDetails<Executed> = approved
+            .clone()
+            .send_to_execute(&approver, None)
+            .book(1000, Some(&confirmation()), &approver, None)
+            .unwrap();
+
+        // Both round trip as they are.
+        let json: serde_json::Value = serde_json::to_value(&approved).unwrap();
+        let read: TradeDetails<Approved> = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(read, approved);
+        let executed_json: serde_json::Value = serde_json::to_value(&executed).unwrap();
+        let read: TradeDetails<Executed> = serde_json::from_value(executed_json.clone()).unwrap();
+        assert_eq!(read, executed);
+
+        let mut with_strike: serde_json::Value = json.clone();
+        with_strike["strike"] = serde_json::json!("1000");
+        assert!(serde_json::from_value::<TradeDetails<Approved>>(with_strike).is_err());
+
+        let mut without_strike: serde_json::Value = executed_json.clone();
+        without_strike["strike"] = serde_json::Value::Null;
+        assert!(serde_json::from_value::<TradeDetails<Executed>>(without_strike).is_err());
+
+        // Nor may a trade be read into another state.
+        assert!(serde_json::from_value::<TradeDetails<PendingApproval>>(json).is_err());
+    }
+
+    #[test]
+    fn parsing_styles() {
+        assert_eq!("Forward".parse::<Style>(), Ok(Style::Forward));
+        assert_eq!("option".parse::<Style>(), Ok(Style::Option));
+        assert_eq!("SWAP".parse::<Style>(), Ok(Style::Swap));
+        assert_eq!(Style::Option.to_string(), "Option");
+
+        // Unknown styles are kept as given.
+        let other: Style = "Forward Contract Currency Exchange".parse().unwrap();
+        assert_eq!(other, Style::Other("Forward Contract Currency Exchange".to_string()));
+        assert_eq!(other.to_string(), "Forward Contract Currency Exchange");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serializing_names() {
+        // Styles are serialized by name, as the free text style was.
+        assert_eq!(serde_json::to_string(&Style::Swap).unwrap(), "\"Swap\"");
+        assert_eq!(
+            serde_json::from_str::<Style>("\"Some Style\"").unwrap(),
+            Style::Other("Some Style".to_string())
+        );
+
+        // Counterparties are normalized on the way in.
+        let maggie: Counterparty = Counterparty::new_with("Maggie", &TradePolicy::default());
+        assert_eq!(serde_json::from_str::<Counterparty>("\" Maggie \"").unwrap(), maggie);
+    }
+
+    #[test]
+    fn normalized_names() {
+        let policy: TradePolicy = TradePolicy::default();
+        let maggie: Counterparty = Counterparty::new_with("Maggie", &policy);
+        assert_eq!(Counterparty::new_with(" Maggie\t", &policy), maggie);
+        assert_eq!(Counterparty::new_with(" Maggie\t", &policy).to_string(), "Maggie");
+        assert_eq!(Style::new_with(" forward ", &policy), Style::Forward);
+        assert_eq!(Style::new_with(" Barrier ", &policy), Style::Other("Barrier".to_string()));
+
+        // Case only matters without folding.
+        assert_ne!(Counterparty::new_with("maggie ", &policy), maggie);
+        let folding: TradePolicy = TradePolicy {
+            fold_case: true,
+            ..TradePolicy::default()
+        };
+        assert_eq!(Counterparty::new_with("maggie ", &folding), Counterparty::new("maggie"));
+        assert_eq!(
+            Counterparty::new_with("maggie ", &folding),
+            Counterparty::new_with("Maggie", &folding)
+        );
+        assert_eq!(Style::new_with(" Barrier ", &folding), Style::Other("barrier".to_string()));
+
+        // So padding the names alone leaves the trade as it was.
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let draft: TradeDetails<Draft> = mock_draft(&requester);
+        let mut padded: MutTradeDetails = draft.snapshot();
+        let counterparty: String = format!("  {} ", draft.counterparty());
+        padded.counterparty = Counterparty::new_with(counterparty, &policy);
+        padded.style = Style::new_with(format!("{}\n", draft.style()), &policy);
+        let edited: TradeDetails<Draft> = draft.clone().edit(&requester, padded, None).unwrap();
+        assert!(TradeDetailsDiff::new(&draft, &edited).is_none());
+    }
+
+    #[test]
+    fn fixed_clock() {
+        let fixed: DateTime<Utc> = DateTime::parse_from_rfc3339("2025-01-02T03:04:05Z")
+            .unwrap()
+            .to_utc();
+        let _clock = clock::set_thread_clock(FixedClock(fixed));
+
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let details: TradeDetails<Draft> = TradeDetails::<Draft>
+            ::new(
+                &requester,
+                Counterparty("TestCounterParty".to_string()),
+                Direction::BUY,
+                Style::Other("Some Style".to_string()),
+                Currency::GBP,
+                100,
+                vec![Currency::GBP],
+                fixed + TimeDelta::days(2),
+                fixed + TimeDelta::days(4)
+            )
+            .unwrap();
+        assert_eq!(details.trade_date(), &fixed);
+
+        let details: TradeDetails<PendingApproval> = details.submit(&requester, None).unwrap();
+        let record: HistoricalRecord = crate::history::HISTORY
+            .lock()
+            .unwrap()
+            .last_record(*details.id())
+            .unwrap()
+            .clone();
+        assert_eq!(record.timestamp(), &fixed);
+        assert_eq!(details.duration_in_state(), Some(TimeDelta::zero()));
+        assert_eq!(details.age(), TimeDelta::zero());
+
+        let _clock = clock::set_thread_clock(FixedClock(fixed + TimeDelta::days(3)));
+        assert_eq!(details.age(), TimeDelta::days(3));
+    }
+
+    #[test]
+    fn one_year_tenor() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let trade_date: DateTime<Utc> = Utc::now() - TimeDelta::days(1);
+        let details: MutTradeDetails = MutTradeDetails {
+            value_date: trade_date + TimeDelta::days(2),
+            delivery_date: trade_date + TimeDelta::days(365),
+            ..mock_draft(&requester).snapshot()
+        };
+        let details: TradeDetails<Draft> = TradeDetails::<Draft>
+            ::new_with_trade_date(&requester, details, trade_date)
+            .unwrap();
+        assert_eq!(details.tenor_days(), 365);
+        assert_eq!(details.value_tenor_days(), 2);
+    }
+
+    #[test]
+    fn notional_limits() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let details: TradeDetails<Draft> = mock_draft(&requester);
+        let mut mut_details: MutTradeDetails = details.mutable_details.clone();
+        mut_details.notional_currency = Currency::GBP;
+
+        let policy: TradePolicy = TradePolicy {
+            notional_limits: NotionalLimits::new([(Currency::GBP, Decimal::from(1_000_000))]),
+            ..TradePolicy::default()
+        };
+        mut_details.notional_amount = Decimal::from(1_000_000);
+        assert!(details.check_details_with(&mut_details, &policy).is_ok());
+
+        mut_details.notional_amount = Decimal::new(100_000_001, 2);
+        let error: InvalidDetails = details.check_details_with(&mut_details, &policy).unwrap_err();
+        assert_eq!(error.issue(), "Notional amount 1000000.01 exceeds the limit of 1000000 for GBP");
+
+        // Currencies without a limit are unlimited.
+        mut_details.notional_currency = Currency::EUR;
+        assert!(details.check_details_with(&mut_details, &policy).is_ok());
+    }
+
+    #[test]
+    fn currency_allow_list() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let details: TradeDetails<Draft> = mock_draft(&requester);
+        let mut mut_details: MutTradeDetails = details.mutable_details.clone();
+        mut_details.notional_currency = Currency::GBP;
+        mut_details.underlying = vec![Currency::GBP, Currency::EUR];
+
+        let policy: TradePolicy = TradePolicy {
+            currencies: Some(
+                CurrencyPolicy::new([Currency::GBP], [Currency::GBP, Currency::EUR])
+            ),
+            ..TradePolicy::default()
+        };
+        assert!(details.check_details_with(&mut_details, &policy).is_ok());
+
+        let mut notional: MutTradeDetails = mut_details.clone();
+        notional.notional_currency = Currency::EUR;
+        let error: InvalidDetails = details.check_details_with(&notional, &policy).unwrap_err();
+        assert_eq!(error.issue(), "Notional currency EUR isn't allowed");
+
+        // Only disallowed in the underlying.
+        let mut underlying: MutTradeDetails = mut_details.clone();
+        underlying.underlying.push(Currency::USD);
+        let error: InvalidDetails = details.check_details_with(&underlying, &policy).unwrap_err();
+        assert_eq!(error.issue(), "Underlying currency USD isn't allowed");
+
+        // Every currency is allowed by default.
+        assert!(details.check_details_with(&underlying, &TradePolicy::default()).is_ok());
+    }
+
+    #[test]
+    fn bad_amounts() {
+        let requester: User<Requester> = User::<Requester>::sign_in("Naughty");
+        let offset: Duration = Duration::from_secs(20);
+        let value_date: DateTime<Utc> = Utc::now() + offset;
+        let delivery_date: DateTime<Utc> = value_date + offset;
+        let draft = |currency: Currency, amount: Decimal| {
+            TradeDetails::<Draft>::new(
+                &requester,
+                Counterparty("TestCounterParty".to_string()),
+                Direction::BUY,
+                Style::Other("Some Style".to_string()),
+                currency,
+                amount,
+                vec![Currency::GBP, Currency::JPY],
+                value_date,
+                delivery_date
+            )
+        };
+
+        assert!(draft(Currency::GBP, Decimal::ZERO).is_err());
+        assert!(draft(Currency::GBP, Decimal::from(-5)).is_err());
+        assert!(draft(Currency::GBP, Decimal::new(1_000_000_501, 3)).is_err());
+        assert!(draft(Currency::JPY, Decimal::new(15, 1)).is_err());
+        assert!(draft(Currency::GBP, Decimal::new(100_000_050, 2)).is_ok());
+        assert!(draft(Currency::GBP, Decimal::new(1_500, 3)).is_ok());
+        assert!(draft(Currency::GBP, 100u64.into()).is_ok());
+    }
+
+    pub(crate) fn confirmation() -> CounterpartyConfirmation {
+        CounterpartyConfirmation::new("CP-REF-1")
+    }
+
+    pub(crate) fn mock_draft(requester: &User<Requester>) -> TradeDetails<Draft> {
+        let offset: Duration = Duration::from_secs(20);
+        let value_date: DateTime<Utc> = Utc::now() + offset;
+        let delivery_date: DateTime<Utc> = value_date + offset;
+        let wrapped_details: Result<TradeDetails, _> = TradeDetails::<Draft>::new(
+            &requester,
+            Counterparty("TestCounterParty".to_string()),
+            Direction::BUY,
+            Style::Other("Some Style".to_string()),
+            Currency::GBP,
+            100,
+            vec![Currency::GBP, Currency::EUR],
+            value_date,
+            delivery_date
+        );
+
+        assert!(wrapped_details.is_ok());
+        wrapped_details.unwrap()
+    }
+
+    /// The trade with its notional amount changed, bypassing the checks on edits.
+    pub(crate) fn with_notional_amount<S: TradeState>(
+        mut details: TradeDetails<S>,
+        amount: Decimal
+    ) -> TradeDetails<S> {
+        details.mutable_details.notional_amount = amount;
+        details
+    }
+
+    #[test]
+    fn setting_draft_dates() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let offset: Duration = Duration::from_secs(3600);
+
+        // Both dates move past the old delivery date.
+        let details: TradeDetails<Draft> = mock_draft(&requester);
+        let value_date: DateTime<Utc> = *details.delivery_date() + offset;
+        let delivery_date: DateTime<Utc> = value_date + offset;
+        let wrapped_details: Result<TradeDetails<Draft>, _> = details.with_dates(
+            value_date,
+            delivery_date
+        );
+        assert!(wrapped_details.is_ok());
+        let details: TradeDetails<Draft> = wrapped_details.unwrap();
+        assert_eq!(details.value_date(), &value_date);
+        assert_eq!(details.delivery_date(), &delivery_date);
+
+        // Delivery before value.
+        let wrapped_details: Result<TradeDetails<Draft>, _> = details.with_dates(
+            delivery_date,
+            value_date
+        );
+        assert!(wrapped_details.is_err());
+    }
+
+    #[test]
+    fn submitting_and_approving_a_trade() {
+        // Draft
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let details: TradeDetails<Draft> = mock_draft(&requester);
+
+        // Submit
+        let wrapped_details: Result<TradeDetails<PendingApproval>, _> = details.submit(
+            &requester,
+            None
+        );
+        assert!(wrapped_details.is_ok());
+        let details: TradeDetails<PendingApproval> = wrapped_details.unwrap();
+
+        // Approve
+        let approver: User<Approver> = User::<Approver>::sign_in("Admin");
+        let _: TradeDetails<Approved> = details.accept(&approver, None).unwrap();
+    }
+
+    #[test]
+    fn updating_a_trade_detail() {
+        // Draft
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let details: TradeDetails<Draft> = mock_draft(&requester);
+
+        // Submit
+        let wrapped_details: Result<TradeDetails<PendingApproval>, _> = details.submit(
+            &requester,
+            None
+        );
+        assert!(wrapped_details.is_ok());
+        let details: TradeDetails<PendingApproval> = wrapped_details.unwrap();
+
+        // Update
+        let approver: User<Approver> = User::sign_in("Admin");
+        let mut new_details: MutTradeDetails = details.grab_mut_details();
+        new_details.direction = Direction::SELL;
+        let wrapped_details: Result<TradeDetails<NeedsReapproval>, _> = details.update(
+            &approver,
+            new_details,
+            None
+        );
+        assert!(wrapped_details.is_ok());
+        let details: TradeDetails<NeedsReapproval> = wrapped_details.unwrap();
+
+        // Approve
+        let wrapped_details: Result<TradeDetails<Approved>, _> = details.approve(&requester, None);
         assert!(wrapped_details.is_ok());
     }
 
@@ -550,22 +2774,27 @@ pub(crate) mod tests {
         let details: TradeDetails<Draft> = mock_draft(&requester);
 
         // Submit
-        let wrapped_details: Result<TradeDetails<PendingApproval>, _> = details.submit(&requester);
+        let wrapped_details: Result<TradeDetails<PendingApproval>, _> = details.submit(
+            &requester,
+            None
+        );
         assert!(wrapped_details.is_ok());
         let details: TradeDetails<PendingApproval> = wrapped_details.unwrap();
 
         // Approve
         let approver: User<Approver> = User::sign_in("Admin");
-        let details: TradeDetails<Approved> = details.accept(&approver);
+        let details: TradeDetails<Approved> = details.accept(&approver, None).unwrap();
 
         // Send To Execute
-        let details: TradeDetails<SentToCounterparty> = details.send_to_execute(&approver);
+        let details: TradeDetails<SentToCounterparty> = details.send_to_execute(&approver, None);
 
         // Book
-        let wrapped_details: Result<TradeDetails<Executed>, _> = details.book(1000, &requester);
+        let wrapped_details: Result<TradeDetails<Executed>, _> = details
+            .book(1000, Some(&confirmation()), &requester, None)
+            .unwrap();
         assert!(wrapped_details.is_ok());
     }
-    
+
     #[test]
     fn cancelled_last_minute() {
         // Draft
@@ -573,19 +2802,602 @@ pub(crate) mod tests {
         let details: TradeDetails<Draft> = mock_draft(&requester);
 
         // Submit
-        let wrapped_details: Result<TradeDetails<PendingApproval>, _> = details.submit(&requester);
+        let wrapped_details: Result<TradeDetails<PendingApproval>, _> = details.submit(
+            &requester,
+            None
+        );
         assert!(wrapped_details.is_ok());
         let details: TradeDetails<PendingApproval> = wrapped_details.unwrap();
 
         // Approve
         let approver: User<Approver> = User::sign_in("Admin");
-        let details: TradeDetails<Approved> = details.accept(&approver);
+        let details: TradeDetails<Approved> = details.accept(&approver, None).unwrap();
 
         // Send To Execute
-        let details: TradeDetails<SentToCounterparty> = details.send_to_execute(&approver);
+        let details: TradeDetails<SentToCounterparty> = details.send_to_execute(&approver, None);
 
         // Cancel
-        let _: TradeDetails<Cancelled> = details.cancel(&approver);
+        let _: TradeDetails<Cancelled> = details.cancel(&approver, None);
+    }
+
+    #[test]
+    fn diff_display_only_renders_changes() {
+        let diff: TradeDetailsDiff = TradeDetailsDiff {
+            notional_amount: Some((Decimal::from(1), Decimal::from(1000))),
+            ..TradeDetailsDiff::default()
+        };
+        let rendered: String = diff.to_string();
+        assert_eq!(rendered, "amount: 1 -> 1000");
+        assert_eq!(rendered.split("; ").count(), 1);
+
+        let diff: TradeDetailsDiff = TradeDetailsDiff {
+            direction: Some((Direction::BUY, Direction::SELL)),
+            notional_amount: Some((Decimal::from(1), Decimal::from(1000))),
+            ..TradeDetailsDiff::default()
+        };
+        assert_eq!(diff.to_string(), "direction: BUY -> SELL; amount: 1 -> 1000");
+    }
+
+    #[test]
+    fn booking_only_changes_strike() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let approver: User<Approver> = User::sign_in("Admin");
+        let details: TradeDetails<SentToCounterparty> = mock_draft(&requester)
+            .submit(&requester, None)
+            .unwrap()
+            .accept(&approver, None)
+            .unwrap()
+            .send_to_execute(&approver, None);
+        let sent: TradeDetails<SentToCounterparty> = details.clone();
+
+        let executed: TradeDetails<Executed> = details
+            .book(1000, Some(&confirmation()), &approver, None)
+            .unwrap();
+        assert!(sent.equal_ignoring_strike(&executed));
+        assert!(executed.equal_ignoring_strike(&sent));
+
+        let mut altered: TradeDetails<SentToCounterparty> = sent.clone();
+        altered.mutable_details.direction = Direction::SELL;
+        assert!(!altered.equal_ignoring_strike(&executed));
+    }
+
+    #[test]
+    fn snapshot_of_approved() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let approver: User<Approver> = User::sign_in("Admin");
+        let draft: TradeDetails<Draft> = mock_draft(&requester);
+        let original: MutTradeDetails = draft.snapshot();
+
+        let approved: TradeDetails<Approved> = draft
+            .submit(&requester, None)
+            .unwrap()
+            .accept(&approver, None)
+            .unwrap();
+        let mut snapshot: MutTradeDetails = approved.snapshot();
+        assert_eq!(snapshot, original);
+
+        // Editing the snapshot leaves the trade untouched.
+        snapshot.direction = Direction::SELL;
+        assert_eq!(approved.direction(), &Direction::BUY);
+    }
+
+    #[test]
+    fn rejecting_with_reason() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let approver: User<Approver> = User::sign_in("Admin");
+        let details: TradeDetails<PendingApproval> = mock_draft(&requester)
+            .submit(&requester, None)
+            .unwrap();
+        let id: Uuid = *details.id();
+
+        let blank: Note = Note::new("   ").unwrap();
+        assert!(details.clone().reject_with_reason(&approver, blank).is_err());
+
+        let reason: Note = Note::new("Counterparty is over its credit limit").unwrap();
+        let _: TradeDetails<Rejected> = details.reject_with_reason(&approver, reason).unwrap();
+        let record: HistoricalRecord = crate::history::HISTORY
+            .lock()
+            .unwrap()
+            .last_record(id)
+            .cloned()
+            .unwrap();
+        assert_eq!(record.action(), &TradeAction::Reject);
+        assert_eq!(record.state_after(), Rejected::NAME);
+        assert_eq!(record.note(), Some("Counterparty is over its credit limit"));
+        assert!(!record.is_reversible());
+    }
+
+    #[test]
+    fn reopening_a_rejection() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let approver: User<Approver> = User::sign_in("Admin");
+        let draft: TradeDetails<Draft> = mock_draft(&requester);
+        let original: MutTradeDetails = draft.snapshot();
+        let rejected: TradeDetails<Rejected> = draft
+            .submit(&requester, None)
+            .unwrap()
+            .reject_with_reason(&approver, Note::new("Wrong counterparty").unwrap())
+            .unwrap();
+
+        let malicious: User<Requester> = User::sign_in("Malicious");
+        assert!(rejected.clone().reopen(&malicious, None).is_err());
+
+        let reopened: TradeDetails<Draft> = rejected.reopen(&requester, None).unwrap();
+        assert_eq!(reopened.snapshot(), original);
+        let record: HistoricalRecord = crate::history::HISTORY
+            .lock()
+            .unwrap()
+            .last_record(*reopened.id())
+            .cloned()
+            .unwrap();
+        assert_eq!(record.action(), &TradeAction::Reopen);
+        assert_eq!(record.state_before(), Rejected::NAME);
+        assert_eq!(record.state_after(), Draft::NAME);
+
+        // Revised and submitted again.
+        assert!(reopened.submit(&requester, None).is_ok());
+    }
+
+    #[test]
+    fn cancelling_as_requester() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let malicious: User<Requester> = User::sign_in("Malicious");
+        let details: TradeDetails<PendingApproval> = mock_draft(&requester)
+            .submit(&requester, None)
+            .unwrap();
+
+        let wrapped_details: Result<
+            TradeDetails<Cancelled>,
+            UnauthorisedRequester<PendingApproval>
+        > = details.clone().cancel_as_requester(&malicious, None);
+        assert!(wrapped_details.is_err());
+
+        let wrapped_details: Result<TradeDetails<Cancelled>, _> = details
+            .clone()
+            .cancel_as_requester(&requester, None);
+        assert!(wrapped_details.is_ok());
+
+        let approver: User<Approver> = User::sign_in("Admin");
+        let cancelled: TradeDetails<Cancelled> = details.cancel_as_approver(&approver, None);
+        assert_eq!(cancelled.id(), wrapped_details.unwrap().id());
+    }
+
+    #[test]
+    fn comparing_content() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let approver: User<Approver> = User::sign_in("Admin");
+        let draft: TradeDetails<Draft> = mock_draft(&requester);
+        assert_eq!(draft, draft.clone());
+
+        // Only the content matters, not the state.
+        let submitted: TradeDetails<PendingApproval> = draft
+            .clone()
+            .submit(&requester, None)
+            .unwrap();
+        assert!(submitted.content_eq(&draft));
+
+        let mut updated: TradeDetails<PendingApproval> = submitted.clone();
+        updated.mutable_details.notional_amount = Decimal::from(200);
+        assert_ne!(updated, submitted);
+        assert!(!updated.content_eq(&draft));
+
+        let sent: TradeDetails<SentToCounterparty> = submitted
+            .accept(&approver, None)
+            .unwrap()
+            .send_to_execute(&approver, None);
+        let executed: TradeDetails<Executed> = sent
+            .clone()
+            .book(1000, Some(&confirmation()), &approver, None)
+            .unwrap();
+        assert!(!executed.content_eq(&sent));
+        assert!(executed.equal_ignoring_strike(&sent));
+    }
+
+    #[test]
+    fn booking_needs_confirmation() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let approver: User<Approver> = User::sign_in("Admin");
+        let sent: TradeDetails<SentToCounterparty> = mock_draft(&requester)
+            .submit(&requester, None)
+            .unwrap()
+            .accept(&approver, None)
+            .unwrap()
+            .send_to_execute(&approver, None);
+
+        let error: InvalidBooking = sent.clone().book(1000, None, &approver, None).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Trade can't be booked until TestCounterParty confirms it."
+        );
+        let blank: CounterpartyConfirmation = CounterpartyConfirmation::new(" ");
+        assert!(sent.clone().book(1000, Some(&blank), &approver, None).is_err());
+        let last_action: Option<(TradeAction, DateTime<Utc>)> = crate::history::HISTORY
+            .lock()
+            .unwrap()
+            .last_action_for(*sent.id());
+        assert_eq!(last_action.unwrap().0, TradeAction::SendToExecute);
+
+        let executed: TradeDetails<Executed> = sent
+            .book(1000, Some(&confirmation()), &approver, None)
+            .unwrap();
+        assert_eq!(executed.strike(), Some(Decimal::from(1000)));
+        assert_eq!(executed.confirmation_reference(), Some("CP-REF-1"));
+        let record: HistoricalRecord = crate::history::HISTORY
+            .lock()
+            .unwrap()
+            .records_for(*executed.id())
+            .last()
+            .cloned()
+            .unwrap();
+        assert_eq!(record.changes().unwrap().changed_confirmation_reference(), Some("CP-REF-1"));
+    }
+
+    #[test]
+    fn split_trade() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let approver: User<Approver> = User::sign_in("Admin");
+        let approved: TradeDetails<Approved> = mock_draft(&requester)
+            .submit(&requester, None)
+            .unwrap()
+            .accept(&approver, None)
+            .unwrap();
+        let parent: Uuid = *approved.id();
+
+        let (first, second) = approved
+            .split(&approver, (Decimal::from(40), Decimal::from(60)), None)
+            .unwrap();
+        assert_eq!(first.snapshot().notional_amount, Decimal::from(40));
+        assert_eq!(second.snapshot().notional_amount, Decimal::from(60));
+        assert_ne!(first.id(), second.id());
+        for tranche in [&first, &second] {
+            assert_ne!(tranche.id(), &parent);
+            let record: HistoricalRecord = HISTORY
+                .lock()
+                .unwrap()
+                .last_record(*tranche.id())
+                .unwrap()
+                .clone();
+            assert_eq!(record.action(), &TradeAction::Split);
+            assert_eq!(record.linked_trades(), &[parent]);
+            assert_eq!(record.state_before(), Approved::NAME);
+            assert_eq!(record.state_after(), Approved::NAME);
+            assert!(!record.is_reversible());
+        }
+
+        // The parent's lifecycle ends with the split, which names both tranches.
+        let record: HistoricalRecord = HISTORY
+            .lock()
+            .unwrap()
+            .last_record(parent)
+            .unwrap()
+            .clone();
+        assert_eq!(record.action(), &TradeAction::Split);
+        assert_eq!(record.linked_trades(), &[*first.id(), *second.id()]);
+        assert!(record.changes().is_none());
+        assert!(lifecycle_duration(parent).is_some());
+    }
+
+    #[test]
+    fn uneven_split() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let approver: User<Approver> = User::sign_in("Admin");
+        let approved: TradeDetails<Approved> = mock_draft(&requester)
+            .submit(&requester, None)
+            .unwrap()
+            .accept(&approver, None)
+            .unwrap();
+
+        let error: InvalidSplit = approved
+            .clone()
+            .split(&approver, (Decimal::from(40), Decimal::from(50)), None)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Failed to split the trade. \
+            Tranches of 40 and 50 don't sum to the notional amount of 100."
+        );
+        assert!(
+            approved
+                .clone()
+                .split(&approver, (Decimal::ZERO, Decimal::from(100)), None)
+                .is_err()
+        );
+        assert!(
+            approved
+                .split(&approver, (Decimal::from(-10), Decimal::from(110)), None)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn rejected_amendment() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let approver: User<Approver> = User::sign_in("Admin");
+        let pending: TradeDetails<PendingApproval> = mock_draft(&requester)
+            .submit(&requester, None)
+            .unwrap();
+        let original: MutTradeDetails = pending.snapshot();
+        let mut amended: MutTradeDetails = original.clone();
+        amended.notional_amount = Decimal::from(250);
+        amended.direction = Direction::SELL;
+        let amended: TradeDetails<NeedsReapproval> = pending
+            .update(&approver, amended, None)
+            .unwrap();
+
+        let stranger: User<Requester> = User::sign_in("Stranger");
+        assert!(amended.clone().reject_changes(&stranger, None).is_err());
+
+        let restored: TradeDetails<PendingApproval> = amended
+            .reject_changes(&requester, None)
+            .unwrap();
+        assert_eq!(restored.snapshot(), original);
+        let record: HistoricalRecord = HISTORY
+            .lock()
+            .unwrap()
+            .last_record(*restored.id())
+            .unwrap()
+            .clone();
+        assert_eq!(record.action(), &TradeAction::Reject);
+        assert_eq!(record.state_before(), NeedsReapproval::NAME);
+        assert_eq!(record.state_after(), PendingApproval::NAME);
+
+        // Without the amendment recorded, there's nothing to restore.
+        let unrecorded: TradeDetails<NeedsReapproval> = mock_draft(&requester).force_transition();
+        assert!(
+            matches!(
+                unrecorded.reject_changes(&requester, None),
+                Err(InvalidRejection::Missing(_))
+            )
+        );
+    }
+
+    #[test]
+    fn version_hashes() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let draft: TradeDetails<Draft> = mock_draft(&requester);
+        let version: u64 = draft.version_hash();
+        assert_eq!(draft.clone().version_hash(), version);
+        assert!(draft.check_version(version).is_ok());
+
+        let mut edited: MutTradeDetails = draft.snapshot();
+        edited.notional_amount = Decimal::from(250);
+        let edited: TradeDetails<Draft> = draft.clone().edit(&requester, edited, None).unwrap();
+        assert_ne!(edited.version_hash(), version);
+        let error: StaleVersion = edited.check_version(version).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Trade was changed since version {}, it's now {}.",
+                version,
+                edited.version_hash()
+            )
+        );
+
+        // Transitions alone don't change the version.
+        let pending: TradeDetails<PendingApproval> = draft.submit(&requester, None).unwrap();
+        assert_eq!(pending.version_hash(), version);
+    }
+
+    #[test]
+    fn settlement_amounts() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let approver: User<Approver> = User::sign_in("Admin");
+        let execute = |direction: Direction, underlying: Vec<Currency>| -> TradeDetails<Executed> {
+            let mut draft: TradeDetails<Draft> = mock_draft(&requester);
+            draft.mutable_details.direction = direction;
+            draft.mutable_details.underlying = underlying;
+            draft
+                .submit(&requester, None)
+                .unwrap()
+                .accept(&approver, None)
+                .unwrap()
+                .send_to_execute(&approver, None)
+                .book(Decimal::new(11625, 4), Some(&confirmation()), &approver, None)
+                .unwrap()
+        };
+
+        // 100 GBP at 1.1625 is 116.25 EUR, paid on a BUY and received on a SELL.
+        let bought: TradeDetails<Executed> = execute(
+            Direction::BUY,
+            vec![Currency::GBP, Currency::EUR]
+        );
+        let settlement: SettlementDetails = bought.settlement().unwrap();
+        assert_eq!(settlement.settlement_date, *bought.delivery_date());
+        assert_eq!(settlement.settlement_currency, Currency::EUR);
+        assert_eq!(settlement.net_amount, Decimal::new(-11625, 2));
+
+        // The order of the pair doesn't matter.
+        let sold: TradeDetails<Executed> = execute(
+            Direction::SELL,
+            vec![Currency::EUR, Currency::GBP]
+        );
+        let settlement: SettlementDetails = sold.settlement().unwrap();
+        assert_eq!(settlement.settlement_currency, Currency::EUR);
+        assert_eq!(settlement.net_amount, Decimal::new(11625, 2));
+
+        // A basket has no single currency to settle in, nor does a pair without the notional.
+        let basket: TradeDetails<Executed> = execute(
+            Direction::BUY,
+            vec![Currency::GBP, Currency::EUR, Currency::USD]
+        );
+        assert!(basket.settlement().is_none());
+        let unrelated: TradeDetails<Executed> = execute(
+            Direction::BUY,
+            vec![Currency::EUR, Currency::USD]
+        );
+        assert!(unrelated.settlement().is_none());
+    }
+
+    #[test]
+    fn strike_band() {
+        let band: StrikeBand = StrikeBand::new(1000, 2);
+        assert!(band.check(Decimal::from(1000)).is_ok());
+        assert!(band.check(Decimal::from(500)).is_ok());
+        assert!(band.check(Decimal::from(2000)).is_ok());
+        let error: StrikeOutOfBand = band.check(Decimal::from(2001)).unwrap_err();
+        assert_eq!(error.to_string(), "Strike of 2001 is outside the allowed band of 500 to 2000.");
+        assert!(band.check(Decimal::from(499)).is_err());
+        assert!(band.check(Decimal::ZERO).is_err());
+    }
+
+    #[test]
+    fn booking_within_strike_band() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let approver: User<Approver> = User::sign_in("Admin");
+        let sent: TradeDetails<SentToCounterparty> = mock_draft(&requester)
+            .submit(&requester, None)
+            .unwrap()
+            .accept(&approver, None)
+            .unwrap()
+            .send_to_execute(&approver, None);
+        let strict: TradePolicy = TradePolicy {
+            strike_band: Some(StrikeBand::new(1000, 2)),
+            ..TradePolicy::default()
+        };
+
+        let outside = sent
+            .clone()
+            .book_with(2001, Some(&confirmation()), &approver, None, &strict);
+        assert!(matches!(outside, Err(InvalidBooking::Strike(_))));
+        let executed: TradeDetails<Executed> = sent
+            .clone()
+            .book_with(1500, Some(&confirmation()), &approver, None, &strict)
+            .unwrap();
+        assert_eq!(executed.strike(), Some(Decimal::from(1500)));
+
+        // Any strike is booked without a band, as by default.
+        let policy: TradePolicy = TradePolicy::default();
+        assert!(sent.book_with(0, Some(&confirmation()), &approver, None, &policy).is_ok());
+    }
+
+    #[test]
+    fn option_strike() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let mut option: TradeDetails<Draft> = mock_draft(&requester);
+        option.mutable_details.style = Style::Option;
+        let strict: TradePolicy = TradePolicy {
+            options_need_strike: true,
+            ..TradePolicy::default()
+        };
+
+        let error: MissingStrike = option
+            .check_option_strike_with(Decimal::ZERO, &strict)
+            .unwrap_err();
+        assert_eq!(error.to_string(), "An Option can't be booked without a strike.");
+        assert!(option.check_option_strike_with(Decimal::from(1000), &strict).is_ok());
+
+        // Other styles, and options without the policy, may be booked at zero.
+        let forward: TradeDetails<Draft> = mock_draft(&requester);
+        assert!(forward.check_option_strike_with(Decimal::ZERO, &strict).is_ok());
+        assert!(option.check_option_strike_with(Decimal::ZERO, &TradePolicy::default()).is_ok());
+    }
+
+    #[test]
+    fn transitions_move_details() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let mut details: TradeDetails<Draft> = mock_draft(&requester);
+        details.mutable_details.underlying = vec![Currency::GBP; 100_000];
+        let basket: *const Currency = details.underlying().as_ptr();
+
+        // The same allocation is carried through, rather than copied.
+        let details: TradeDetails<PendingApproval> = details.force_transition();
+        assert_eq!(details.underlying().as_ptr(), basket);
+        assert_eq!(details.underlying().len(), 100_000);
+    }
+
+    #[test]
+    fn signed_exposure() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let mut details: TradeDetails<Draft> = mock_draft(&requester);
+        assert_eq!(details.counterparty_exposure_sign(), Decimal::from(100));
+
+        details.mutable_details.direction = Direction::SELL;
+        assert_eq!(details.counterparty_exposure_sign(), Decimal::from(-100));
+    }
+
+    #[test]
+    fn two_of_two_approvals() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let details: TradeDetails<PartiallyApproved> = mock_draft(&requester)
+            .submit_for_approvals(&requester, 2, None)
+            .unwrap();
+        assert_eq!(details.approvals_remaining(), 2);
+
+        let first: User<Approver> = User::sign_in("FirstAdmin");
+        let QuorumProgress::Pending(details) = details.accept(&first, None).unwrap() else {
+            panic!("Trade was approved after a single sign off.");
+        };
+        assert_eq!(details.approvals_remaining(), 1);
+        assert_eq!(details.signed_off(), &["FirstAdmin".to_string()]);
+
+        let second: User<Approver> = User::sign_in("SecondAdmin");
+        let QuorumProgress::Approved(_) = details.accept(&second, None).unwrap() else {
+            panic!("Trade wasn't approved once the quorum was met.");
+        };
+    }
+
+    #[test]
+    fn duplicate_approver() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let approver: User<Approver> = User::sign_in("Admin");
+        let details: TradeDetails<PartiallyApproved> = mock_draft(&requester)
+            .submit_for_approvals(&requester, 2, None)
+            .unwrap();
+
+        let QuorumProgress::Pending(details) = details.accept(&approver, None).unwrap() else {
+            panic!("Trade was approved after a single sign off.");
+        };
+        assert!(details.accept(&approver, None).is_err());
+    }
+
+    #[test]
+    fn empty_quorum() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let submitted = mock_draft(&requester).submit_for_approvals(&requester, 0, None);
+        assert!(matches!(submitted, Err(InvalidSubmission::Quorum(_))));
+    }
+
+    #[test]
+    fn self_approval() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let approver: User<Approver> = User::sign_in("TestUser");
+        let details: TradeDetails<PendingApproval> = mock_draft(&requester)
+            .submit(&requester, None)
+            .unwrap();
+
+        let error: SelfApproval = details.clone().accept(&approver, None).unwrap_err();
+        assert_eq!(error.approver, "TestUser");
+
+        let quorum: TradeDetails<PartiallyApproved> = mock_draft(&requester)
+            .submit_for_approvals(&requester, 2, None)
+            .unwrap();
+        assert!(
+            matches!(quorum.accept(&approver, None), Err(InvalidApproval::SelfApproval(_)))
+        );
+
+        // Allowed when the policy is relaxed.
+        let relaxed: TradePolicy = TradePolicy {
+            allow_self_approval: true,
+            ..TradePolicy::default()
+        };
+        assert!(details.check_approver_with(&approver, &relaxed).is_ok());
+        assert!(details.check_approver_with(&approver, &TradePolicy::default()).is_err());
+    }
+
+    #[test]
+    fn expiring_a_draft() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let details: TradeDetails<Draft> = mock_draft(&requester);
+        assert!(details.expires_at().is_none());
+        assert!(!details.is_expired(&(Utc::now() + TimeDelta::days(3650))));
+
+        let details: TradeDetails<PendingApproval> = details
+            .expire_after(TimeDelta::days(1))
+            .submit(&requester, None)
+            .unwrap();
+        assert_eq!(details.expires_at(), Some(&(*details.trade_date() + TimeDelta::days(1))));
+        assert!(!details.is_expired(details.trade_date()));
+        assert!(details.is_expired(&(*details.trade_date() + TimeDelta::days(1))));
     }
 
     #[test]
@@ -599,7 +3411,7 @@ pub(crate) mod tests {
         let wrapped_details: Result<
             TradeDetails<PendingApproval>,
             UnauthorisedRequester<Draft>
-        > = details.submit(&malicious);
+        > = details.submit(&malicious, None);
         assert!(wrapped_details.is_err());
     }
 }
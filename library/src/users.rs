@@ -1,23 +1,31 @@
 use std::{ fmt::{ Debug, Display }, marker::PhantomData };
 
 use crate::{
-    error::UnauthorisedRequester,
-    history::{ HISTORY, HistoricalRecord },
-    state::{ TradeAction, TradeState },
+    error::{ UnauthorisedCause, UnauthorisedRequester, UnknownApprover },
+    history::{ HISTORY, HistoricalRecord, Note },
+    policy::ApproverRegistry,
+    state::{ AllowedTransition, StateMachine, TradeAction, TradeState },
     trade::TradeDetails,
 };
 
-pub trait Permission: Debug + PartialEq + Eq {}
+pub trait Permission: Debug + PartialEq + Eq {
+    /// Names the role at runtime, where the permission type has been erased.
+    const ROLE: &'static str;
+}
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Requester;
 
-impl Permission for Requester {}
+impl Permission for Requester {
+    const ROLE: &'static str = "requester";
+}
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Approver;
 
-impl Permission for Approver {}
+impl Permission for Approver {
+    const ROLE: &'static str = "approver";
+}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct User<P> where P: Permission {
@@ -38,8 +46,30 @@ impl<P: Permission> User<P> {
             _permission: PhantomData,
         }
     }
+
+    pub fn role(&self) -> &'static str {
+        P::ROLE
+    }
+}
+
+impl User<Approver> {
+    /// Signs in an approver, only if they're registered, as `sign_in` trusts
+    /// any id claiming the role.
+    pub fn sign_in_registered(
+        id: &str,
+        registry: &ApproverRegistry
+    ) -> Result<Self, UnknownApprover> {
+        if !registry.is_known(id) {
+            return Err(UnknownApprover { approver: id.to_string() });
+        }
+        Ok(Self::sign_in(id))
+    }
 }
 
+/// Moves trades between states on behalf of a user, recording each transition
+/// in the history. Every change of state is recorded, as is any action changing
+/// a trade within its state (e.g. a sign off towards a quorum). An action which
+/// leaves the trade exactly as it was isn't recorded.
 pub trait Transitioner {
     type TransitionResult<From: TradeState, To: TradeState>;
 
@@ -47,8 +77,10 @@ pub trait Transitioner {
         &self,
         details: TradeDetails<From>,
         mutation: impl FnOnce(&mut TradeDetails<From>) -> (),
-        action: TradeAction
-    ) -> Self::TransitionResult<From, To>;
+        action: TradeAction,
+        note: Option<Note>
+    ) -> Self::TransitionResult<From, To>
+        where StateMachine: AllowedTransition<From, To>;
 }
 
 impl Transitioner for User<Requester> {
@@ -61,25 +93,30 @@ impl Transitioner for User<Requester> {
         &self,
         mut details: TradeDetails<From>,
         mutation: impl FnOnce(&mut TradeDetails<From>) -> (),
-        action: TradeAction
-    ) -> Self::TransitionResult<From, To> {
+        action: TradeAction,
+        note: Option<Note>
+    ) -> Self::TransitionResult<From, To>
+        where StateMachine: AllowedTransition<From, To>
+    {
         if details.trading_entity != *self {
             return Err(UnauthorisedRequester {
                 requester: self.id.clone(),
                 action: action.to_string(),
+                cause: UnauthorisedCause::NotTradingEntity,
                 _state: PhantomData,
             });
         }
         let old_details: TradeDetails<From> = details.clone();
         mutation(&mut details);
-        let new_details: TradeDetails<To> = details.force_transition::<To>();
-        let record: HistoricalRecord = HistoricalRecord::new(
-            action,
-            self.id.clone(),
-            &old_details,
-            &new_details
-        );
-        {
+        let new_details: TradeDetails<To> = details.map_state::<To>();
+        if !old_details.is_unchanged_by(&new_details) {
+            let record: HistoricalRecord = HistoricalRecord::new(
+                action,
+                self.id.clone(),
+                &old_details,
+                &new_details,
+                note
+            );
             HISTORY.lock().unwrap().add_record(record);
         }
         Ok(new_details)
@@ -93,20 +130,51 @@ impl Transitioner for User<Approver> {
         &self,
         mut details: TradeDetails<From>,
         mutation: impl FnOnce(&mut TradeDetails<From>) -> (),
-        action: TradeAction
-    ) -> Self::TransitionResult<From, To> {
+        action: TradeAction,
+        note: Option<Note>
+    ) -> Self::TransitionResult<From, To>
+        where StateMachine: AllowedTransition<From, To>
+    {
         let old_details: TradeDetails<From> = details.clone();
         mutation(&mut details);
-        let new_details: TradeDetails<To> = details.force_transition::<To>();
-        let record: HistoricalRecord = HistoricalRecord::new(
-            action,
-            self.id.clone(),
-            &old_details,
-            &new_details
-        );
-        {
+        let new_details: TradeDetails<To> = details.map_state::<To>();
+        if !old_details.is_unchanged_by(&new_details) {
+            let record: HistoricalRecord = HistoricalRecord::new(
+                action,
+                self.id.clone(),
+                &old_details,
+                &new_details,
+                note
+            );
             HISTORY.lock().unwrap().add_record(record);
         }
         new_details
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distinct_roles() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let approver: User<Approver> = User::sign_in("TestUser");
+        assert_eq!(requester.role(), "requester");
+        assert_eq!(approver.role(), "approver");
+        assert_ne!(requester.role(), approver.role());
+    }
+
+    #[test]
+    fn registered_approvers() {
+        let mut registry: ApproverRegistry = ApproverRegistry::new(["Admin"]);
+        let approver: User<Approver> = User::sign_in_registered("Admin", &registry).unwrap();
+        assert_eq!(approver, User::sign_in("Admin"));
+
+        let error: UnknownApprover = User::sign_in_registered("Intruder", &registry).unwrap_err();
+        assert_eq!(error.to_string(), "Approver Intruder isn't registered.");
+
+        registry.register("Intruder");
+        assert!(User::sign_in_registered("Intruder", &registry).is_ok());
+    }
+}
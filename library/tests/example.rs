@@ -1,6 +1,7 @@
 use chrono::{Duration, TimeDelta, Utc};
 use iso_currency::Currency;
-use library::{history::{get_historical_record, total_historical_record_count}, state::{Approved, Draft, NeedsReapproval, PendingApproval, TradeAction}, trade::{Counterparty, Direction, MutTradeDetails, Style, TradeDetails}, users::{Approver, Requester, User}};
+use rust_decimal::Decimal;
+use library::{history::{Note, get_historical_record, total_historical_record_count}, state::{Approved, Draft, NeedsReapproval, PendingApproval, TradeAction}, trade::{Counterparty, CounterpartyConfirmation, Direction, MutTradeDetails, Style, TradeDetails}, users::{Approver, Requester, User}};
 
 #[test]
 /// This test works an example for the various interacts with the API.
@@ -14,9 +15,9 @@ fn example_updates_and_history() {
     // Bob will initially create a draft trade.
     let trade: TradeDetails<Draft> = TradeDetails::<Draft>::new(
         &bob, 
-        Counterparty("Maggie".to_string()), 
+        Counterparty::new("Maggie"), 
         Direction::BUY, 
-        Style("Forward Contract Currency Exchange.".to_string()),
+        Style::Other("Forward Contract Currency Exchange.".to_string()),
         iso_currency::Currency::USD, 
         1, 
         vec![Currency::USD, Currency::GBP], 
@@ -25,19 +26,21 @@ fn example_updates_and_history() {
     ).unwrap();
 
     // Bob submits this draft, for Ellie to view.
-    let trade: TradeDetails<PendingApproval> = trade.submit(&bob).unwrap();
+    let trade: TradeDetails<PendingApproval> = trade.submit(&bob, None).unwrap();
     assert_eq!(total_historical_record_count(), 1);
 
     // Ellie now signs in.
     let ellie: User<Approver> = User::<Approver>::sign_in("Ellie");
     
     // Ellie has a look at bobs draft, notices he's trading very little.
-    assert_eq!(trade.amount(), 1);
+    assert_eq!(trade.amount(), Decimal::from(1));
 
-    // Probably a typo - typical Bob. Ellie updates his issue instead of approving.
+    // Probably a typo - typical Bob. Ellie updates his issue instead of approving,
+    // leaving a note to explain why.
     let mut new_trade = trade.grab_mut_details();
-    new_trade.notional_amount = 1000;
-    let trade: TradeDetails<NeedsReapproval> = trade.update(&ellie, new_trade).unwrap();
+    new_trade.notional_amount = Decimal::from(1000);
+    let note: Note = Note::new("Amount looks like a typo, should be 1000.").unwrap();
+    let trade: TradeDetails<NeedsReapproval> = trade.update(&ellie, new_trade, Some(note)).unwrap();
 
     // Bob gets the latest change history to find out whether his trade was approved.
     assert_eq!(total_historical_record_count(), 2);
@@ -45,14 +48,19 @@ fn example_updates_and_history() {
     
     // Bob notices his trade has been updated!
     assert_eq!(record.action(), &TradeAction::Update);
-    assert_eq!(record.changes().unwrap().changed_amount().unwrap(), (1, 1000));
+    assert_eq!(record.note(), Some("Amount looks like a typo, should be 1000."));
+    assert_eq!(record.changes().unwrap().changed_amount().unwrap(), (Decimal::from(1), Decimal::from(1000)));
 
     // Looks like he made a typo, and so he reapproves.
-    let trade: TradeDetails<Approved> = trade.approve(&bob).unwrap();
+    let trade: TradeDetails<Approved> = trade.approve(&bob, None).unwrap();
     assert_eq!(total_historical_record_count(), 3);
 
     // Happy now, Ellie sends and eventually completes the trade.
-    trade.send_to_execute(&ellie)
-        .book(900, &ellie);
+    // The counterparty confirms it under their own reference, which is kept with the trade.
+    let confirmation: CounterpartyConfirmation = CounterpartyConfirmation::new("MAGGIE-0001");
+    let trade = trade.send_to_execute(&ellie, None)
+        .book(900, Some(&confirmation), &ellie, None)
+        .unwrap();
+    assert_eq!(trade.confirmation_reference(), Some("MAGGIE-0001"));
     assert_eq!(total_historical_record_count(), 5);
 }
\ No newline at end of file
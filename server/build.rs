@@ -1,5 +1,11 @@
-use std::error::Error;
+use std::{ env, error::Error, path::PathBuf };
 
 fn main() -> Result<(), Box<dyn Error>> {
-    Ok(tonic_prost_build::compile_protos("proto/trade.proto")?)
+    // The descriptor set is served by gRPC reflection, for tools like grpcurl.
+    let out_dir: PathBuf = PathBuf::from(env::var("OUT_DIR")?);
+    Ok(
+        tonic_prost_build::configure()
+            .file_descriptor_set_path(out_dir.join("trade_descriptor.bin"))
+            .compile_protos(&["proto/trade.proto"], &["proto"])?
+    )
 }
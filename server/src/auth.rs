@@ -0,0 +1,213 @@
+use std::{ collections::HashMap, fmt::Debug, sync::Arc };
+
+use library::{
+    error::{ UnauthorisedCause, UnknownApprover },
+    policy::ApproverRegistry,
+    state::TradeAction,
+    users::{ Approver, Requester, User },
+};
+use tonic::{ Request, Status, service::Interceptor };
+
+use crate::proto;
+
+/// Metadata key holding the `Bearer <token>` credentials.
+pub const AUTHORIZATION_KEY: &str = "authorization";
+
+/// Validates bearer tokens, so that the identity source can be swapped
+/// (e.g. for an identity provider) without touching the handlers.
+pub trait TokenVerifier: Debug + Send + Sync {
+    /// Returns the authenticated user id, or `None` if the token is invalid.
+    fn verify(&self, token: &str) -> Option<String>;
+}
+
+#[derive(Debug, Default)]
+/// Verifies tokens against a fixed table of token to user id.
+pub struct StaticTokenVerifier {
+    tokens: HashMap<String, String>,
+}
+
+impl StaticTokenVerifier {
+    pub fn new(tokens: HashMap<String, String>) -> Self {
+        Self { tokens }
+    }
+
+    /// Reads `token:user_id` pairs, comma separated, from the
+    /// `TRADE_AUTH_TOKENS` environment variable.
+    pub fn from_env() -> Self {
+        let raw: String = std::env::var("TRADE_AUTH_TOKENS").unwrap_or_default();
+        let tokens: HashMap<String, String> = raw
+            .split(',')
+            .filter_map(|pair: &str| pair.split_once(':'))
+            .map(|(token, user_id)| (token.trim().to_string(), user_id.trim().to_string()))
+            .collect();
+        Self::new(tokens)
+    }
+}
+
+impl TokenVerifier for StaticTokenVerifier {
+    fn verify(&self, token: &str) -> Option<String> {
+        self.tokens.get(token).cloned()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// The verified user id, injected into the request extensions.
+pub struct AuthenticatedUser(pub String);
+
+#[derive(Debug, Clone)]
+pub struct AuthInterceptor {
+    verifier: Arc<dyn TokenVerifier>,
+}
+
+impl AuthInterceptor {
+    pub fn new(verifier: Arc<dyn TokenVerifier>) -> Self {
+        Self { verifier }
+    }
+}
+
+impl Interceptor for AuthInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let token: &str = request
+            .metadata()
+            .get(AUTHORIZATION_KEY)
+            .ok_or_else(|| Status::unauthenticated("Authorization token not specified."))?
+            .to_str()
+            .map_err(|_| Status::unauthenticated("Authorization token is malformed."))?
+            .strip_prefix("Bearer ")
+            .ok_or_else(|| Status::unauthenticated("Authorization must be a bearer token."))?;
+
+        let user_id: String = self.verifier
+            .verify(token)
+            .ok_or_else(|| Status::unauthenticated("Authorization token is invalid."))?;
+
+        request.extensions_mut().insert(AuthenticatedUser(user_id));
+        Ok(request)
+    }
+}
+
+/// Resolves the user id the request was authenticated as, rejecting
+/// requests whose body claims to be someone else.
+pub fn authenticated_user_id<T>(request: &Request<T>, claimed: &str) -> Result<String, Status> {
+    let Some(AuthenticatedUser(user_id)) = request.extensions().get::<AuthenticatedUser>() else {
+        return Err(Status::unauthenticated("Request is not authenticated."));
+    };
+    if user_id != claimed {
+        return Err(Status::unauthenticated("Username doesn't match the authenticated user."));
+    }
+    Ok(user_id.clone())
+}
+
+/// A user whose permission is only known once the request is read.
+pub enum SignedInUser {
+    Requester(User<Requester>),
+    Approver(User<Approver>),
+}
+
+impl SignedInUser {
+    /// Signs in the authenticated user, with the permission of their claimed role.
+    /// Approvers must be registered, when there's a registry.
+    pub fn sign_in<T>(
+        request: &Request<T>,
+        user: &proto::Username,
+        approvers: Option<&ApproverRegistry>
+    ) -> Result<Self, Status> {
+        let user_id: String = authenticated_user_id(request, &user.user_id)?;
+        match proto::Role::try_from(user.role) {
+            Ok(proto::Role::Requester) => Ok(Self::Requester(User::sign_in(&user_id))),
+            Ok(proto::Role::Approver) => {
+                let approver: User<Approver> = match approvers {
+                    Some(registry) => User::sign_in_registered(&user_id, registry).map_err(
+                        <UnknownApprover as Into<Status>>::into
+                    )?,
+                    None => User::sign_in(&user_id),
+                };
+                Ok(Self::Approver(approver))
+            }
+            Err(_) => Err(Status::invalid_argument("Role must either be REQUESTER or APPROVER")),
+        }
+    }
+
+    pub fn requester(self, action: TradeAction) -> Result<User<Requester>, Status> {
+        match self {
+            Self::Requester(user) => Ok(user),
+            Self::Approver(user) => {
+                Err(
+                    Status::permission_denied(
+                        format!(
+                            "Approver {} can't {}, {}.",
+                            user,
+                            action.to_string(),
+                            UnauthorisedCause::WrongRole
+                        )
+                    )
+                )
+            }
+        }
+    }
+
+    pub fn approver(self, action: TradeAction) -> Result<User<Approver>, Status> {
+        match self {
+            Self::Approver(user) => Ok(user),
+            Self::Requester(user) => {
+                Err(
+                    Status::permission_denied(
+                        format!(
+                            "Requester {} can't {}, {}.",
+                            user,
+                            action.to_string(),
+                            UnauthorisedCause::WrongRole
+                        )
+                    )
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::{ Code, metadata::MetadataValue };
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct StubVerifier;
+
+    impl TokenVerifier for StubVerifier {
+        fn verify(&self, token: &str) -> Option<String> {
+            (token == "good-token").then(|| "TestUser".to_string())
+        }
+    }
+
+    fn request_with_token(token: &'static str) -> Request<()> {
+        let mut request: Request<()> = Request::new(());
+        request.metadata_mut().insert(AUTHORIZATION_KEY, MetadataValue::from_static(token));
+        request
+    }
+
+    #[test]
+    fn accepted_token() {
+        let mut interceptor: AuthInterceptor = AuthInterceptor::new(Arc::new(StubVerifier));
+        let request: Request<()> = interceptor.call(request_with_token("Bearer good-token")).unwrap();
+        assert_eq!(
+            request.extensions().get::<AuthenticatedUser>(),
+            Some(&AuthenticatedUser("TestUser".to_string()))
+        );
+        assert!(authenticated_user_id(&request, "TestUser").is_ok());
+        assert_eq!(
+            authenticated_user_id(&request, "SomeoneElse").unwrap_err().code(),
+            Code::Unauthenticated
+        );
+    }
+
+    #[test]
+    fn rejected_token() {
+        let mut interceptor: AuthInterceptor = AuthInterceptor::new(Arc::new(StubVerifier));
+        for token in ["Bearer bad-token", "good-token"] {
+            let status: Status = interceptor.call(request_with_token(token)).unwrap_err();
+            assert_eq!(status.code(), Code::Unauthenticated);
+        }
+        let status: Status = interceptor.call(Request::new(())).unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+    }
+}
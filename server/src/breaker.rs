@@ -0,0 +1,160 @@
+use std::{ fmt::{ self, Debug, Display }, sync::Mutex, time::{ Duration, Instant } };
+
+use library::{ error::classify, state::TradeAction };
+use serde::Deserialize;
+use tonic::Status;
+use uuid::Uuid;
+
+#[derive(Debug)]
+pub struct SinkError(pub String);
+
+impl Display for SinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Destination for the server's record of every stored transition.
+/// Currently in memory, but a database backed sink may be temporarily
+/// unavailable, which is why writes are routed through a `CircuitBreaker`.
+pub trait HistorySink: Debug + Send + Sync {
+    fn record(&self, uuid: &Uuid, action: &TradeAction, state: &'static str) -> Result<(), SinkError>;
+}
+
+#[derive(Debug, Default)]
+/// The default sink, which can't fail.
+pub struct InMemorySink {
+    entries: Mutex<Vec<(Uuid, TradeAction, &'static str)>>,
+}
+
+impl HistorySink for InMemorySink {
+    fn record(&self, uuid: &Uuid, action: &TradeAction, state: &'static str) -> Result<(), SinkError> {
+        self.entries.lock().unwrap().push((*uuid, action.clone(), state));
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// What happens to a transition when its history can't be recorded.
+pub enum BreakerPolicy {
+    /// The transition proceeds unrecorded, and the failure is logged.
+    Degrade,
+    /// The transition is rejected with `Status::unavailable`.
+    FailFast,
+}
+
+#[derive(Debug, Clone)]
+pub struct BreakerConfig {
+    /// Consecutive sink failures before the breaker opens.
+    pub failure_threshold: u32,
+
+    /// How long the breaker stays open before the sink is tried again.
+    pub cooldown: Duration,
+
+    pub policy: BreakerPolicy,
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            cooldown: Duration::from_secs(30),
+            policy: BreakerPolicy::FailFast,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    trial_in_flight: bool,
+}
+
+#[derive(Debug, Default)]
+/// Stops calling a repeatedly failing history sink, so that a history
+/// outage doesn't block or fail every transition for the whole service.
+pub struct CircuitBreaker {
+    config: BreakerConfig,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: BreakerConfig) -> Self {
+        Self { config, state: Mutex::new(BreakerState::default()) }
+    }
+
+    /// Whether the sink is currently being skipped. Once the cooldown
+    /// has elapsed the breaker is half-open, letting a single trial through,
+    /// and stays open to everything else until that trial resolves.
+    pub fn is_open(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        match state.opened_at {
+            Some(opened_at) => opened_at.elapsed() < self.config.cooldown || state.trial_in_flight,
+            None => false,
+        }
+    }
+
+    /// Whether a call may reach the sink, claiming the trial if half-open.
+    fn admit(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match state.opened_at {
+            Some(opened_at) if opened_at.elapsed() < self.config.cooldown => false,
+            Some(_) if state.trial_in_flight => false,
+            Some(_) => {
+                state.trial_in_flight = true;
+                true
+            }
+            None => true,
+        }
+    }
+
+    /// Records the transition into the sink, unless the breaker is open.
+    /// Failures are resolved per the configured `BreakerPolicy`.
+    pub fn record(
+        &self,
+        sink: &dyn HistorySink,
+        uuid: &Uuid,
+        action: &TradeAction,
+        state: &'static str
+    ) -> Result<(), Status> {
+        if !self.admit() {
+            return self.reject(uuid, "circuit breaker is open");
+        }
+
+        match sink.record(uuid, action, state) {
+            Ok(()) => {
+                *self.state.lock().unwrap() = BreakerState::default();
+                Ok(())
+            }
+            Err(e) => {
+                {
+                    let mut breaker_state = self.state.lock().unwrap();
+                    breaker_state.trial_in_flight = false;
+                    breaker_state.consecutive_failures += 1;
+                    if breaker_state.consecutive_failures >= self.config.failure_threshold {
+                        breaker_state.opened_at = Some(Instant::now());
+                    }
+                }
+                self.reject(uuid, &e.to_string())
+            }
+        }
+    }
+
+    fn reject(&self, uuid: &Uuid, reason: &str) -> Result<(), Status> {
+        match self.config.policy {
+            BreakerPolicy::Degrade => {
+                tracing::warn!(
+                    %uuid,
+                    reason,
+                    "DEGRADED: transition proceeding without history."
+                );
+                Ok(())
+            }
+            BreakerPolicy::FailFast => {
+                Err(classify(Status::unavailable(format!("History unavailable, {}.", reason))))
+            }
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::{ pin::Pin, task::{ Context, Poll } };
+
+use library::error::{ ErrorClass, RETRYABLE_KEY };
+use tonic::{ Code, codegen::http };
+use tower::{ Layer, Service };
+
+use crate::message_size::GRPC_STATUS_HEADER;
+
+#[derive(Debug, Clone, Default)]
+/// Marks every error the server returns with its `ErrorClass`, as `classify`
+/// does for the library's errors, so clients can rely on `RETRYABLE_KEY`.
+///
+/// Errors made by the server itself, or by tonic (e.g. while decoding), don't
+/// pass through `classify`, so are marked here instead, from the code in the
+/// trailers-only response. Statuses already marked are left as they are.
+pub struct ClassifyLayer;
+
+impl<S> Layer<S> for ClassifyLayer {
+    type Service = ClassifyService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ClassifyService { inner }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClassifyService<S> {
+    inner: S,
+}
+
+impl<S, Request, ResponseBody> Service<Request> for ClassifyService<S>
+    where
+        S: Service<Request, Response = http::Response<ResponseBody>>,
+        S::Future: Send + 'static
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let mut response = response.await?;
+            let code: Option<Code> = response
+                .headers()
+                .get(GRPC_STATUS_HEADER)
+                .map(|status: &http::HeaderValue| Code::from_bytes(status.as_bytes()));
+            let marked: bool = response.headers().contains_key(RETRYABLE_KEY);
+            if let Some(code) = code.filter(|code: &Code| *code != Code::Ok && !marked) {
+                let retryable: &'static str = match ErrorClass::of(code) {
+                    ErrorClass::Retryable => "true",
+                    ErrorClass::NonRetryable => "false",
+                };
+                response
+                    .headers_mut()
+                    .insert(RETRYABLE_KEY, http::HeaderValue::from_static(retryable));
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use tonic::{ Status, metadata::MetadataValue };
+    use tower::{ ServiceExt, service_fn };
+
+    use super::*;
+
+    async fn respond(status: Status) -> http::Response<()> {
+        let service = ClassifyLayer.layer(
+            service_fn(|status: Status| async move { Ok::<_, Infallible>(status.into_http()) })
+        );
+        service.oneshot(status).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn unmarked_errors_classified() {
+        let response: http::Response<()> = respond(Status::not_found("Trade not found.")).await;
+        assert_eq!(response.headers().get(RETRYABLE_KEY).unwrap(), "false");
+
+        let response: http::Response<()> = respond(Status::unavailable("Offline.")).await;
+        assert_eq!(response.headers().get(RETRYABLE_KEY).unwrap(), "true");
+
+        // Statuses already marked, as by `classify`, keep their mark.
+        let mut status: Status = Status::unavailable("Offline.");
+        status.metadata_mut().insert(RETRYABLE_KEY, MetadataValue::from_static("false"));
+        let response: http::Response<()> = respond(status).await;
+        assert_eq!(response.headers().get(RETRYABLE_KEY).unwrap(), "false");
+    }
+}
@@ -0,0 +1,512 @@
+use std::{
+    error::Error,
+    fmt::{ self, Display },
+    io,
+    net::SocketAddr,
+    path::{ Path, PathBuf },
+    time::Duration,
+};
+
+use chrono::TimeDelta;
+use library::{ history::DEFAULT_NOTE_LIMIT, policy::{ ApproverRegistry, TradePolicy } };
+use serde::Deserialize;
+use tonic::Status;
+
+use crate::{
+    breaker::{ BreakerConfig, BreakerPolicy },
+    events::DEFAULT_EVENT_CAPACITY,
+    metrics::METRICS_ADDRESS,
+};
+
+/// Environment variable overriding the address the gRPC server listens on.
+pub const LISTEN_ADDR_VAR: &str = "LISTEN_ADDR";
+
+/// The largest request message decoded by default, as for tonic itself.
+pub const DEFAULT_MAX_MESSAGE_BYTES: usize = 4 * 1024 * 1024;
+
+/// Environment variable giving the path of the TOML config file, when it
+/// isn't passed as the first argument.
+pub const CONFIG_PATH_VAR: &str = "SERVER_CONFIG";
+
+/// Where the gRPC server listens when `LISTEN_ADDR` isn't set.
+pub const DEFAULT_LISTEN_ADDR: &str = "[::1]:25565";
+
+/// Resolves the address to listen on from the raw `LISTEN_ADDR` value,
+/// falling back to `DEFAULT_LISTEN_ADDR` when it's unset.
+pub fn resolve_listen_address(raw: Option<&str>) -> Result<SocketAddr, ConfigError> {
+    parse_address(LISTEN_ADDR_VAR, raw.unwrap_or(DEFAULT_LISTEN_ADDR))
+}
+
+fn parse_address(name: &str, raw: &str) -> Result<SocketAddr, ConfigError> {
+    raw.trim()
+        .parse()
+        .map_err(|_| ConfigError {
+            problems: vec![
+                format!(
+                    "{} of {:?} isn't a socket address, e.g. {}",
+                    name,
+                    raw,
+                    DEFAULT_LISTEN_ADDR
+                )
+            ],
+        })
+}
+
+#[derive(Debug, Clone)]
+/// Every tunable of the server, which is validated as a whole on startup.
+pub struct ServerConfig {
+    /// Where the gRPC server listens, unless overridden by `LISTEN_ADDR`.
+    pub listen_address: SocketAddr,
+
+    /// Where `/metrics` is served.
+    pub metrics_address: SocketAddr,
+
+    /// Shortest allowed tenor, from trade date to delivery, in days.
+    pub min_tenor_days: Option<i64>,
+
+    /// Longest allowed tenor, from trade date to delivery, in days.
+    pub max_tenor_days: Option<i64>,
+
+    /// How long after its trade date an unprogressed trade is cancelled.
+    pub trade_ttl: TimeDelta,
+
+    /// How often expired trades are swept.
+    pub sweep_interval: Duration,
+
+    /// How many status events are retained for lagging subscribers.
+    pub event_capacity: usize,
+
+    /// Longest note, in characters, accepted on a transition.
+    pub max_note_length: usize,
+
+    /// Most history records retained, beyond which the oldest are evicted.
+    /// Unbounded when unset.
+    pub history_capacity: Option<usize>,
+
+    /// Largest encoded request message, in bytes. Larger requests are rejected
+    /// with `OutOfRange` as they're decoded, before reaching any handler.
+    pub max_message_bytes: usize,
+
+    pub limits: FieldLimits,
+
+    pub breaker: BreakerConfig,
+
+    /// The trade policy, applied with `set_policy` on startup. Only its flags
+    /// are configurable, the registries and limits being left empty.
+    pub policy: TradePolicy,
+
+    /// The only users who may sign in as approvers, when set. Otherwise any
+    /// authenticated user claiming the role is trusted.
+    pub approvers: Option<ApproverRegistry>,
+
+    /// A JSON array of trades, as the library serializes them in any state,
+    /// seeded into the server before it serves, e.g. for demos.
+    pub seed_trades: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+/// Bounds on the free-form fields of inbound trade details, so a client
+/// can't exhaust the server's memory with a single request.
+pub struct FieldLimits {
+    /// Longest counterparty, in characters.
+    pub max_counterparty_length: usize,
+
+    /// Longest style, in characters.
+    pub max_style_length: usize,
+
+    /// Most currencies in the underlying basket.
+    pub max_underlying: usize,
+}
+
+impl Default for FieldLimits {
+    fn default() -> Self {
+        Self {
+            max_counterparty_length: 256,
+            max_style_length: 64,
+            max_underlying: 32,
+        }
+    }
+}
+
+impl FieldLimits {
+    /// Rejects the first field over its limit, before the details are parsed.
+    pub fn check(
+        &self,
+        counterparty: &str,
+        style: &str,
+        underlying: usize
+    ) -> Result<(), Status> {
+        let counterparty_length: usize = counterparty.chars().count();
+        if counterparty_length > self.max_counterparty_length {
+            return Err(
+                Status::invalid_argument(
+                    format!(
+                        "Counterparty of {} characters exceeds the limit of {}.",
+                        counterparty_length,
+                        self.max_counterparty_length
+                    )
+                )
+            );
+        }
+        let style_length: usize = style.chars().count();
+        if style_length > self.max_style_length {
+            return Err(
+                Status::invalid_argument(
+                    format!(
+                        "Style of {} characters exceeds the limit of {}.",
+                        style_length,
+                        self.max_style_length
+                    )
+                )
+            );
+        }
+        if underlying > self.max_underlying {
+            return Err(
+                Status::invalid_argument(
+                    format!(
+                        "Underlying of {} currencies exceeds the limit of {}.",
+                        underlying,
+                        self.max_underlying
+                    )
+                )
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            listen_address: DEFAULT_LISTEN_ADDR.parse().unwrap(),
+            metrics_address: METRICS_ADDRESS.parse().unwrap(),
+            min_tenor_days: None,
+            max_tenor_days: None,
+            trade_ttl: TimeDelta::days(30),
+            sweep_interval: Duration::from_secs(60),
+            event_capacity: DEFAULT_EVENT_CAPACITY,
+            max_note_length: DEFAULT_NOTE_LIMIT,
+            history_capacity: None,
+            max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+            limits: FieldLimits::default(),
+            breaker: BreakerConfig::default(),
+            policy: TradePolicy::default(),
+            approvers: None,
+            seed_trades: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+/// The TOML file of a `ServerConfig`, in which any field left out keeps its
+/// default. Durations are in whole days or seconds, as named.
+struct ConfigFile {
+    listen_address: Option<String>,
+    metrics_address: Option<String>,
+    min_tenor_days: Option<i64>,
+    max_tenor_days: Option<i64>,
+    trade_ttl_days: Option<i64>,
+    sweep_interval_secs: Option<u64>,
+    event_capacity: Option<usize>,
+    max_note_length: Option<usize>,
+    history_capacity: Option<usize>,
+    max_message_bytes: Option<usize>,
+    approvers: Option<Vec<String>>,
+    seed_trades: Option<PathBuf>,
+    limits: LimitsFile,
+    breaker: BreakerFile,
+    policy: PolicyFile,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LimitsFile {
+    max_counterparty_length: Option<usize>,
+    max_style_length: Option<usize>,
+    max_underlying: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct BreakerFile {
+    failure_threshold: Option<u32>,
+    cooldown_secs: Option<u64>,
+    policy: Option<BreakerPolicy>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PolicyFile {
+    strict_exponents: Option<bool>,
+    notional_first: Option<bool>,
+    allow_self_approval: Option<bool>,
+    fold_case: Option<bool>,
+    options_need_strike: Option<bool>,
+}
+
+#[derive(Debug)]
+pub struct ConfigError {
+    pub problems: Vec<String>,
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid server configuration. {}.", self.problems.join("; "))
+    }
+}
+impl Error for ConfigError {}
+
+impl ServerConfig {
+    /// Loads the config from a TOML file, or the defaults when there's no path.
+    /// A path given but missing is an error, rather than silently defaulting
+    /// (e.g. without the approver registry). The result still needs to be validated.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+        let raw: String = std::fs::read_to_string(path).map_err(|error: io::Error| ConfigError {
+            problems: vec![format!("Couldn't read {}: {}", path.display(), error)],
+        })?;
+        Self::from_toml(&raw)
+    }
+
+    /// Parses the config from TOML, defaulting any field left out.
+    pub fn from_toml(raw: &str) -> Result<Self, ConfigError> {
+        let file: ConfigFile = toml::from_str(raw).map_err(|error: toml::de::Error| ConfigError {
+            problems: vec![error.message().to_string()],
+        })?;
+
+        let mut config: Self = Self::default();
+        if let Some(raw) = file.listen_address {
+            config.listen_address = parse_address("listen_address", &raw)?;
+        }
+        if let Some(raw) = file.metrics_address {
+            config.metrics_address = parse_address("metrics_address", &raw)?;
+        }
+        config.min_tenor_days = file.min_tenor_days.or(config.min_tenor_days);
+        config.max_tenor_days = file.max_tenor_days.or(config.max_tenor_days);
+        if let Some(days) = file.trade_ttl_days {
+            config.trade_ttl = TimeDelta::try_days(days).ok_or_else(|| ConfigError {
+                problems: vec![format!("trade_ttl_days of {} is out of range", days)],
+            })?;
+        }
+        if let Some(secs) = file.sweep_interval_secs {
+            config.sweep_interval = Duration::from_secs(secs);
+        }
+        config.event_capacity = file.event_capacity.unwrap_or(config.event_capacity);
+        config.max_note_length = file.max_note_length.unwrap_or(config.max_note_length);
+        config.max_message_bytes = file.max_message_bytes.unwrap_or(config.max_message_bytes);
+        config.history_capacity = file.history_capacity.or(config.history_capacity);
+        config.approvers = file.approvers.map(ApproverRegistry::new);
+        config.seed_trades = file.seed_trades;
+
+        let limits: &mut FieldLimits = &mut config.limits;
+        limits.max_counterparty_length = file.limits.max_counterparty_length
+            .unwrap_or(limits.max_counterparty_length);
+        limits.max_style_length = file.limits.max_style_length.unwrap_or(limits.max_style_length);
+        limits.max_underlying = file.limits.max_underlying.unwrap_or(limits.max_underlying);
+
+        let breaker: &mut BreakerConfig = &mut config.breaker;
+        breaker.failure_threshold = file.breaker.failure_threshold
+            .unwrap_or(breaker.failure_threshold);
+        if let Some(secs) = file.breaker.cooldown_secs {
+            breaker.cooldown = Duration::from_secs(secs);
+        }
+        breaker.policy = file.breaker.policy.unwrap_or(breaker.policy);
+
+        let policy: &mut TradePolicy = &mut config.policy;
+        policy.strict_exponents = file.policy.strict_exponents.unwrap_or(policy.strict_exponents);
+        policy.notional_first = file.policy.notional_first.unwrap_or(policy.notional_first);
+        policy.allow_self_approval = file.policy.allow_self_approval
+            .unwrap_or(policy.allow_self_approval);
+        policy.fold_case = file.policy.fold_case.unwrap_or(policy.fold_case);
+        policy.options_need_strike = file.policy.options_need_strike
+            .unwrap_or(policy.options_need_strike);
+
+        Ok(config)
+    }
+
+    /// Checks the fields are consistent with one another, reporting every problem at once.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems: Vec<String> = Vec::new();
+
+        if let Some(min) = self.min_tenor_days && min < 0 {
+            problems.push(format!("Minimum tenor of {} days is negative", min));
+        }
+        if let Some(max) = self.max_tenor_days && max < 0 {
+            problems.push(format!("Maximum tenor of {} days is negative", max));
+        }
+        if let (Some(min), Some(max)) = (self.min_tenor_days, self.max_tenor_days) && min > max {
+            problems.push(
+                format!("Minimum tenor of {} days exceeds the maximum of {} days", min, max)
+            );
+        }
+        if self.trade_ttl <= TimeDelta::zero() {
+            problems.push("Trade time to live must be positive".to_string());
+        }
+        if self.sweep_interval.is_zero() {
+            problems.push("Expiry sweep interval must be positive".to_string());
+        }
+        if self.event_capacity == 0 {
+            problems.push("Event capacity must be at least 1".to_string());
+        }
+        if self.history_capacity == Some(0) {
+            problems.push("History capacity must be at least 1 record".to_string());
+        }
+        if self.max_message_bytes == 0 {
+            problems.push("Message size limit must be at least 1 byte".to_string());
+        }
+        if self.limits.max_counterparty_length == 0 {
+            problems.push("Counterparty length limit must be at least 1".to_string());
+        }
+        if self.limits.max_style_length == 0 {
+            problems.push("Style length limit must be at least 1".to_string());
+        }
+        if self.limits.max_underlying == 0 {
+            problems.push("Underlying basket limit must be at least 1".to_string());
+        }
+        if self.breaker.failure_threshold == 0 {
+            problems.push("Circuit breaker failure threshold must be at least 1".to_string());
+        }
+        if self.approvers.as_ref().is_some_and(ApproverRegistry::is_empty) {
+            problems.push("Approver registry must list at least 1 approver".to_string());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError { problems })
+        }
+    }
+
+    /// Whether a tenor in days is within the configured bounds.
+    pub fn tenor_allowed(&self, tenor_days: i64) -> bool {
+        self.min_tenor_days.is_none_or(|min: i64| tenor_days >= min) &&
+            self.max_tenor_days.is_none_or(|max: i64| tenor_days <= max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn consistent_config() {
+        let config: ServerConfig = ServerConfig {
+            min_tenor_days: Some(1),
+            max_tenor_days: Some(365),
+            ..ServerConfig::default()
+        };
+        assert!(config.validate().is_ok());
+        assert!(ServerConfig::default().validate().is_ok());
+        assert!(config.tenor_allowed(30));
+        assert!(!config.tenor_allowed(400));
+    }
+
+    #[test]
+    fn listen_address() {
+        let default: SocketAddr = resolve_listen_address(None).unwrap();
+        assert_eq!(default, DEFAULT_LISTEN_ADDR.parse().unwrap());
+
+        let address: SocketAddr = resolve_listen_address(Some("0.0.0.0:8080")).unwrap();
+        assert_eq!(address.port(), 8080);
+
+        let error: ConfigError = resolve_listen_address(Some("localhost:port")).unwrap_err();
+        assert_eq!(
+            error.problems,
+            vec!["LISTEN_ADDR of \"localhost:port\" isn't a socket address, e.g. [::1]:25565"]
+        );
+    }
+
+    #[test]
+    fn inconsistent_config() {
+        let config: ServerConfig = ServerConfig {
+            min_tenor_days: Some(30),
+            max_tenor_days: Some(7),
+            event_capacity: 0,
+            ..ServerConfig::default()
+        };
+        let error: ConfigError = config.validate().unwrap_err();
+        assert_eq!(error.problems.len(), 2);
+        assert_eq!(error.problems[0], "Minimum tenor of 30 days exceeds the maximum of 7 days");
+        assert!(error.to_string().contains("Event capacity must be at least 1"));
+    }
+
+    #[test]
+    fn empty_approver_registry() {
+        // No approver could sign in, rather than every one being trusted.
+        let config: ServerConfig = ServerConfig::from_toml("approvers = []").unwrap();
+        let error: ConfigError = config.validate().unwrap_err();
+        assert_eq!(error.problems, vec!["Approver registry must list at least 1 approver"]);
+
+        let config: ServerConfig = ServerConfig::from_toml("approvers = [\"Admin\"]").unwrap();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn config_from_toml() {
+        let config: ServerConfig = ServerConfig::from_toml(
+            r#"
+            listen_address = "0.0.0.0:50051"
+            max_tenor_days = 365
+            trade_ttl_days = 7
+            sweep_interval_secs = 10
+            max_message_bytes = 65536
+            history_capacity = 100000
+            approvers = ["Admin"]
+
+            [limits]
+            max_underlying = 4
+
+            [breaker]
+            cooldown_secs = 5
+            policy = "degrade"
+
+            [policy]
+            notional_first = true
+            "#
+        ).unwrap();
+        assert_eq!(config.listen_address, "0.0.0.0:50051".parse().unwrap());
+        assert_eq!(config.metrics_address, METRICS_ADDRESS.parse().unwrap());
+        assert_eq!(config.min_tenor_days, None);
+        assert_eq!(config.max_tenor_days, Some(365));
+        assert_eq!(config.trade_ttl, TimeDelta::days(7));
+        assert_eq!(config.sweep_interval, Duration::from_secs(10));
+        assert_eq!(config.event_capacity, DEFAULT_EVENT_CAPACITY);
+        assert_eq!(config.max_message_bytes, 65536);
+        assert_eq!(config.history_capacity, Some(100000));
+        let approvers: &ApproverRegistry = config.approvers.as_ref().unwrap();
+        assert!(approvers.is_known("Admin"));
+        assert!(!approvers.is_known("TestUser"));
+        assert_eq!(config.limits.max_underlying, 4);
+        assert_eq!(config.limits.max_style_length, FieldLimits::default().max_style_length);
+        assert_eq!(config.breaker.cooldown, Duration::from_secs(5));
+        assert_eq!(config.breaker.policy, BreakerPolicy::Degrade);
+        assert!(config.policy.notional_first);
+        assert!(!config.policy.strict_exponents);
+        assert!(config.validate().is_ok());
+
+        assert!(ServerConfig::from_toml("trade_ttl = 7").is_err());
+        let error: ConfigError = ServerConfig::from_toml(
+            "trade_ttl_days = 9223372036854775807"
+        ).unwrap_err();
+        assert!(error.problems[0].starts_with("trade_ttl_days of 9223372036854775807"));
+        let error: ConfigError = ServerConfig::from_toml(
+            "metrics_address = \"nowhere\""
+        ).unwrap_err();
+        assert!(error.problems[0].starts_with("metrics_address of \"nowhere\""));
+    }
+
+    #[test]
+    fn absent_config_file() {
+        // Only the defaults when no path is given, as a mistyped one may drop settings.
+        let config: ServerConfig = ServerConfig::load(None).unwrap();
+        assert_eq!(config.listen_address, DEFAULT_LISTEN_ADDR.parse().unwrap());
+        assert_eq!(config.trade_ttl, ServerConfig::default().trade_ttl);
+
+        let missing: &Path = Path::new("/nonexistent/server.toml");
+        let error: ConfigError = ServerConfig::load(Some(missing)).unwrap_err();
+        assert!(error.problems[0].starts_with("Couldn't read /nonexistent/server.toml"));
+    }
+}
@@ -0,0 +1,407 @@
+use std::str::FromStr;
+
+use chrono::{ DateTime, Utc };
+use iso_currency::Currency;
+use library::{
+    history::HistoricalRecord,
+    state::TradeState,
+    trade::{ Counterparty, Direction, MutTradeDetails, SettlementDetails, Style, TradeDetails },
+};
+use rust_decimal::Decimal;
+use tonic::Status;
+use uuid::Uuid;
+
+use crate::{ config::FieldLimits, proto };
+
+/// Sanitises inbound details in full, the field limits and then the parsing.
+/// Pure, so every malformed input can be shown to be an `InvalidArgument`.
+pub(crate) fn parse_details(
+    raw_details: &proto::MutableTradeDetails,
+    limits: &FieldLimits
+) -> Result<MutTradeDetails, Status> {
+    limits.check(
+        &raw_details.counterparty,
+        &raw_details.style,
+        raw_details.underlying_currency_codes.len()
+    )?;
+    MutTradeDetails::try_from(raw_details.clone())
+}
+
+/// Applies only the fields named in the mask onto the existing details,
+/// leaving the others untouched, or every field when the mask is empty.
+/// The merged details are sanitised as in full, bar the unnamed fields
+/// which needn't be set at all.
+pub(crate) fn merge_details(
+    existing: MutTradeDetails,
+    raw_details: &proto::MutableTradeDetails,
+    field_mask: &[String],
+    limits: &FieldLimits
+) -> Result<MutTradeDetails, Status> {
+    if field_mask.is_empty() {
+        return parse_details(raw_details, limits);
+    }
+    let mut details: MutTradeDetails = existing;
+    for field in field_mask {
+        match field.as_str() {
+            "counterparty" => {
+                details.counterparty = Counterparty::new(raw_details.counterparty.as_str());
+            }
+            "direction" => {
+                details.direction = raw_details.direction.try_into()?;
+            }
+            "style" => {
+                details.style = Style::new(raw_details.style.as_str());
+            }
+            "currency_code" => {
+                details.notional_currency = currency_from_code(
+                    raw_details.currency_code,
+                    "Currency"
+                )?;
+            }
+            "currency_amount" => {
+                details.notional_amount = parse_amount(&raw_details.currency_amount)?;
+            }
+            "underlying_currency_codes" => {
+                details.underlying = parse_underlying(&raw_details.underlying_currency_codes)?;
+            }
+            "value_date" => {
+                details.value_date = parse_date(
+                    &raw_details.value_date,
+                    "Value Date doesn't follow the UTC standard."
+                )?;
+            }
+            "delivery_date" => {
+                details.delivery_date = parse_date(
+                    &raw_details.delivery_date,
+                    "Delivery Date doesn't follow the UTC standard."
+                )?;
+            }
+            unknown => {
+                return Err(
+                    Status::invalid_argument(format!("Unknown field {:?} in the mask.", unknown))
+                );
+            }
+        }
+    }
+    limits.check(
+        details.counterparty.name(),
+        &details.style.to_string(),
+        details.underlying.len()
+    )?;
+    Ok(details.canonicalised())
+}
+
+/// The ISO 4217 currency with the numeric code, if there is one. Codes too
+/// large for any currency are rejected as any other unknown code, so the
+/// error reads the same either way, naming the field and the code.
+fn currency_from_code(code: u32, field: &str) -> Result<Currency, Status> {
+    u16::try_from(code)
+        .ok()
+        .and_then(Currency::from_numeric)
+        .ok_or_else(|| {
+            Status::invalid_argument(
+                format!("{} code {} isn't an ISO 4217 numeric code.", field, code)
+            )
+        })
+}
+
+fn parse_underlying(codes: &[u32]) -> Result<Vec<Currency>, Status> {
+    codes
+        .iter()
+        .map(|code: &u32| {
+            currency_from_code(*code, "Underlying currency")
+        })
+        .collect()
+}
+
+fn parse_amount(raw: &str) -> Result<Decimal, Status> {
+    Decimal::from_str(raw).map_err(|_| {
+        Status::invalid_argument("Currency amount isn't a decimal number.")
+    })
+}
+
+fn parse_date(raw: &str, error: &'static str) -> Result<DateTime<Utc>, Status> {
+    raw.parse().map_err(|_| Status::invalid_argument(error))
+}
+
+/// Sanitises inbound details in full as `parse_details` does, but reports the
+/// problem with every field rather than only the first.
+pub(crate) fn parse_details_fully(
+    raw_details: &proto::MutableTradeDetails,
+    limits: &FieldLimits
+) -> Result<MutTradeDetails, Vec<Status>> {
+    let checked: Result<(), Status> = limits.check(
+        &raw_details.counterparty,
+        &raw_details.style,
+        raw_details.underlying_currency_codes.len()
+    );
+    match (checked, parse_fields(raw_details)) {
+        (Ok(()), Ok(details)) => Ok(details),
+        (Ok(()), Err(errors)) => Err(errors),
+        (Err(error), Ok(_)) => Err(vec![error]),
+        (Err(error), Err(errors)) => Err([vec![error], errors].concat()),
+    }
+}
+
+/// Parses each field in turn, collecting the error of every malformed one.
+/// This doesn't check the details are consistent, that's left to the library
+/// on creation or update.
+fn parse_fields(
+    raw_details: &proto::MutableTradeDetails
+) -> Result<MutTradeDetails, Vec<Status>> {
+    let mut errors: Vec<Status> = Vec::new();
+    let direction: Option<Direction> = collect(raw_details.direction.try_into(), &mut errors);
+    let notional_currency: Option<Currency> = collect(
+        currency_from_code(raw_details.currency_code, "Currency"),
+        &mut errors
+    );
+    let underlying: Option<Vec<Currency>> = collect(
+        parse_underlying(&raw_details.underlying_currency_codes),
+        &mut errors
+    );
+    let notional_amount: Option<Decimal> = collect(
+        parse_amount(&raw_details.currency_amount),
+        &mut errors
+    );
+    let value_date: Option<DateTime<Utc>> = collect(
+        parse_date(&raw_details.value_date, "Value Date doesn't follow the UTC standard."),
+        &mut errors
+    );
+    let delivery_date: Option<DateTime<Utc>> = collect(
+        parse_date(&raw_details.delivery_date, "Delivery Date doesn't follow the UTC standard."),
+        &mut errors
+    );
+
+    match (direction, notional_currency, underlying, notional_amount, value_date, delivery_date) {
+        (
+            Some(direction),
+            Some(notional_currency),
+            Some(underlying),
+            Some(notional_amount),
+            Some(value_date),
+            Some(delivery_date),
+        ) =>
+            Ok(
+                MutTradeDetails {
+                    counterparty: Counterparty::new(raw_details.counterparty.as_str()),
+                    direction,
+                    style: Style::new(raw_details.style.as_str()),
+                    notional_currency,
+                    notional_amount,
+                    underlying,
+                    value_date,
+                    delivery_date,
+                }.canonicalised()
+            ),
+        _ => Err(errors),
+    }
+}
+
+/// The parsed value, or none with its error collected.
+fn collect<T>(result: Result<T, Status>, errors: &mut Vec<Status>) -> Option<T> {
+    result.map_err(|error: Status| errors.push(error)).ok()
+}
+
+impl TryFrom<proto::MutableTradeDetails> for MutTradeDetails {
+    type Error = Status;
+
+    /// Sanitises the inbound details, failing on the first malformed field.
+    fn try_from(raw_details: proto::MutableTradeDetails) -> Result<Self, Self::Error> {
+        // Never empty when parsing fails.
+        parse_fields(&raw_details).map_err(|mut errors: Vec<Status>| errors.remove(0))
+    }
+}
+
+impl From<&MutTradeDetails> for proto::MutableTradeDetails {
+    fn from(details: &MutTradeDetails) -> Self {
+        Self {
+            counterparty: details.counterparty.to_string(),
+            direction: <&Direction as Into<i32>>::into(&details.direction),
+            style: details.style.to_string(),
+            currency_code: details.notional_currency.numeric() as u32,
+            currency_amount: details.notional_amount.to_string(),
+            underlying_currency_codes: details.underlying
+                .iter()
+                .map(|c: &Currency| { c.numeric() as u32 })
+                .collect(),
+            value_date: details.value_date.to_rfc3339(),
+            delivery_date: details.delivery_date.to_rfc3339(),
+        }
+    }
+}
+
+impl<S: TradeState> From<&TradeDetails<S>> for proto::TradeDetails {
+    fn from(details: &TradeDetails<S>) -> Self {
+        Self {
+            trading_entity: Some(proto::Username {
+                user_id: details.trading_entity().to_string(),
+                role: proto::Role::Requester as i32,
+            }),
+            // The same conversion as inbound details are checked against, so a
+            // submitted trade reads back as it was sent.
+            subdetails: Some((&details.snapshot()).into()),
+            trade_date: details.trade_date().to_rfc3339(),
+            strike: details
+                .strike()
+                .map(|strike: Decimal| strike.to_string())
+                .unwrap_or_default(),
+            tenor_days: details.tenor_days(),
+            value_tenor_days: details.value_tenor_days(),
+            version: details.version_hash(),
+        }
+    }
+}
+
+impl From<&SettlementDetails> for proto::SettlementDetails {
+    fn from(settlement: &SettlementDetails) -> Self {
+        Self {
+            settlement_date: settlement.settlement_date.to_rfc3339(),
+            settlement_currency_code: settlement.settlement_currency.numeric() as u32,
+            net_amount: settlement.net_amount.to_string(),
+        }
+    }
+}
+
+impl From<&HistoricalRecord> for proto::HistoricalRecord {
+    fn from(record: &HistoricalRecord) -> Self {
+        Self {
+            action: record.action().to_string(),
+            action_id: record.action().id() as i32,
+            user_id: record.user_id().to_string(),
+            state_before: record.state_before().to_string(),
+            state_after: record.state_after().to_string(),
+            timestamp: record.timestamp().to_rfc3339(),
+            note: record.note().unwrap_or_default().to_string(),
+            linked_trades: record.linked_trades()
+                .iter()
+                .map(|uuid: &Uuid| proto::TradeUuid { uuid: uuid.to_string() })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use prost::Message;
+    use tonic::Code;
+
+    use super::*;
+    use crate::tests::mock_submit_request;
+
+    fn mock_details() -> proto::MutableTradeDetails {
+        mock_submit_request("TestUser").details.unwrap()
+    }
+
+    #[test]
+    fn valid_details_round_trip() {
+        let raw: proto::MutableTradeDetails = mock_details();
+        let details: MutTradeDetails = MutTradeDetails::try_from(raw.clone()).unwrap();
+        assert_eq!(details.notional_currency, Currency::GBP);
+        assert_eq!(details.underlying, vec![Currency::GBP, Currency::EUR]);
+        assert_eq!(details.direction, Direction::BUY);
+        assert_eq!(proto::MutableTradeDetails::from(&details), raw);
+    }
+
+    #[test]
+    fn malformed_currencies() {
+        let mut raw: proto::MutableTradeDetails = mock_details();
+        raw.currency_code = 1;
+        assert_eq!(MutTradeDetails::try_from(raw).unwrap_err().code(), Code::InvalidArgument);
+
+        let mut raw: proto::MutableTradeDetails = mock_details();
+        raw.currency_code = u32::MAX;
+        assert_eq!(MutTradeDetails::try_from(raw).unwrap_err().code(), Code::InvalidArgument);
+
+        let mut raw: proto::MutableTradeDetails = mock_details();
+        raw.underlying_currency_codes.push(1);
+        assert_eq!(MutTradeDetails::try_from(raw).unwrap_err().code(), Code::InvalidArgument);
+    }
+
+    #[test]
+    fn currency_code_errors() {
+        // Out of range of any numeric code.
+        let out_of_range: u32 = u32::from(u16::MAX) + 1;
+        let error: Status = currency_from_code(out_of_range, "Currency").unwrap_err();
+        assert_eq!(error.code(), Code::InvalidArgument);
+        assert_eq!(error.message(), "Currency code 65536 isn't an ISO 4217 numeric code.");
+
+        // In range, but no currency has it.
+        let error: Status = currency_from_code(1, "Currency").unwrap_err();
+        assert_eq!(error.code(), Code::InvalidArgument);
+        assert_eq!(error.message(), "Currency code 1 isn't an ISO 4217 numeric code.");
+
+        let mut raw: proto::MutableTradeDetails = mock_details();
+        raw.underlying_currency_codes.push(out_of_range);
+        assert_eq!(
+            MutTradeDetails::try_from(raw).unwrap_err().message(),
+            "Underlying currency code 65536 isn't an ISO 4217 numeric code."
+        );
+        let mut raw: proto::MutableTradeDetails = mock_details();
+        raw.currency_code = 1;
+        assert_eq!(
+            MutTradeDetails::try_from(raw).unwrap_err().message(),
+            "Currency code 1 isn't an ISO 4217 numeric code."
+        );
+    }
+
+    #[test]
+    fn malformed_dates() {
+        let mut raw: proto::MutableTradeDetails = mock_details();
+        raw.value_date = "tomorrow".to_string();
+        assert_eq!(MutTradeDetails::try_from(raw).unwrap_err().code(), Code::InvalidArgument);
+
+        let mut raw: proto::MutableTradeDetails = mock_details();
+        raw.delivery_date = "2025-13-45T00:00:00Z".to_string();
+        assert_eq!(MutTradeDetails::try_from(raw).unwrap_err().code(), Code::InvalidArgument);
+    }
+
+    #[test]
+    fn malformed_direction() {
+        let mut raw: proto::MutableTradeDetails = mock_details();
+        raw.direction = 7;
+        assert_eq!(MutTradeDetails::try_from(raw).unwrap_err().code(), Code::InvalidArgument);
+    }
+
+    /// Parses without panicking, any failure being an `InvalidArgument`.
+    fn assert_sanitised(raw: &proto::MutableTradeDetails) -> Result<(), TestCaseError> {
+        if let Err(status) = parse_details(raw, &FieldLimits::default()) {
+            prop_assert_eq!(status.code(), Code::InvalidArgument);
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn arbitrary_fields_sanitised(
+            counterparty in ".{0,300}",
+            style in ".{0,80}",
+            direction in any::<i32>(),
+            currency_code in any::<u32>(),
+            currency_amount in prop_oneof![".*", "-?[0-9]{0,40}(\\.[0-9]{0,40})?"],
+            underlying_currency_codes in prop::collection::vec(any::<u32>(), 0..40),
+            value_date in prop_oneof![".*", "[+-]?[0-9]{1,6}-[0-9]{2}-[0-9]{2}T[0-9:.]{8,12}Z"],
+            delivery_date in ".*"
+        ) {
+            let raw: proto::MutableTradeDetails = proto::MutableTradeDetails {
+                counterparty,
+                direction,
+                style,
+                currency_code,
+                currency_amount,
+                underlying_currency_codes,
+                value_date,
+                delivery_date,
+            };
+            assert_sanitised(&raw)?;
+        }
+
+        #[test]
+        fn arbitrary_bytes_sanitised(bytes in prop::collection::vec(any::<u8>(), 0..512)) {
+            // Whatever the wire decodes to, as a client needn't use a generated client.
+            if let Ok(raw) = proto::MutableTradeDetails::decode(bytes.as_slice()) {
+                assert_sanitised(&raw)?;
+            }
+        }
+    }
+}
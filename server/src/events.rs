@@ -0,0 +1,111 @@
+use std::sync::{ Arc, atomic::{ AtomicU64, Ordering } };
+
+use tokio::sync::broadcast::{ self, error::RecvError };
+use uuid::Uuid;
+
+use crate::proto::TradeStatusResponse;
+
+/// Default number of events retained for subscribers.
+pub const DEFAULT_EVENT_CAPACITY: usize = 1024;
+
+pub type TradeEvent = (Uuid, TradeStatusResponse);
+
+#[derive(Debug, Clone)]
+/// Fan-out of every status written into the trade store.
+///
+/// The bus is bounded, and publishing never blocks. A subscriber that
+/// falls more than `capacity` events behind loses the oldest events it
+/// hasn't read yet; each lost event is counted in `dropped_events`, which
+/// is scraped as `trade_events_dropped_total`.
+pub struct EventBus {
+    sender: broadcast::Sender<TradeEvent>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            sender: broadcast::channel(capacity).0,
+            dropped: Arc::default(),
+        }
+    }
+
+    pub fn publish(&self, uuid: &Uuid, response: &TradeStatusResponse) {
+        // Only fails when nobody is subscribed, which is fine.
+        let _ = self.sender.send((*uuid, response.clone()));
+    }
+
+    pub fn subscribe(&self) -> EventReceiver {
+        EventReceiver {
+            receiver: self.sender.subscribe(),
+            dropped: self.dropped.clone(),
+        }
+    }
+
+    /// Total events lost by lagging subscribers.
+    pub fn dropped_events(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(DEFAULT_EVENT_CAPACITY)
+    }
+}
+
+#[derive(Debug)]
+pub struct EventReceiver {
+    receiver: broadcast::Receiver<TradeEvent>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl EventReceiver {
+    /// The next event still retained, or `None` once the bus is gone.
+    pub async fn recv(&mut self) -> Option<TradeEvent> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) => {
+                    return Some(event);
+                }
+                Err(RecvError::Lagged(missed)) => {
+                    self.dropped.fetch_add(missed, Ordering::Relaxed);
+                    tracing::warn!(missed, "Event subscriber lagged, dropping events.");
+                }
+                Err(RecvError::Closed) => {
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn overflowing_drops_events() {
+        let bus: EventBus = EventBus::new(2);
+        let mut receiver: EventReceiver = bus.subscribe();
+
+        // Publishing beyond capacity returns immediately.
+        for status in 0..5 {
+            let response: TradeStatusResponse = TradeStatusResponse {
+                details: None,
+                status,
+                uuid: None,
+                settlement: None,
+            };
+            bus.publish(&Uuid::new_v4(), &response);
+        }
+        assert_eq!(bus.dropped_events(), 0);
+
+        let (_, oldest_retained) = receiver.recv().await.unwrap();
+        assert_eq!(oldest_retained.status, 3);
+        assert_eq!(bus.dropped_events(), 3);
+
+        let (_, newest) = receiver.recv().await.unwrap();
+        assert_eq!(newest.status, 4);
+    }
+}
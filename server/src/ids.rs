@@ -0,0 +1,41 @@
+use std::fmt::Debug;
+#[cfg(test)]
+use std::sync::atomic::{ AtomicU64, Ordering };
+
+use uuid::Uuid;
+
+/// Where the UUIDs of newly submitted trades come from.
+pub trait UuidSource: Debug + Send + Sync {
+    fn next_uuid(&self) -> Uuid;
+}
+
+#[derive(Debug, Default)]
+/// The default source, generating random v4 UUIDs.
+pub struct RandomUuids;
+
+impl UuidSource for RandomUuids {
+    fn next_uuid(&self) -> Uuid {
+        Uuid::new_v4()
+    }
+}
+
+#[cfg(test)]
+#[derive(Debug)]
+/// Counts up from a seed, so tests can predict the UUIDs of their trades.
+pub struct SequentialUuids {
+    next: AtomicU64,
+}
+
+#[cfg(test)]
+impl SequentialUuids {
+    pub fn starting_at(seed: u64) -> Self {
+        Self { next: AtomicU64::new(seed) }
+    }
+}
+
+#[cfg(test)]
+impl UuidSource for SequentialUuids {
+    fn next_uuid(&self) -> Uuid {
+        Uuid::from_u128(self.next.fetch_add(1, Ordering::SeqCst) as u128)
+    }
+}
@@ -1,39 +1,98 @@
-use std::{ collections::HashMap, net::SocketAddr, str::FromStr, sync::Arc };
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    path::PathBuf,
+    pin::Pin,
+    str::FromStr,
+    sync::Arc,
+};
 
-use chrono::{ DateTime, Utc };
-use iso_currency::Currency;
+use auth::{ AuthInterceptor, SignedInUser, StaticTokenVerifier };
+use breaker::{ CircuitBreaker, HistorySink, InMemorySink };
+use classification::ClassifyLayer;
+use config::{ FieldLimits, ServerConfig };
+use chrono::{ DateTime, TimeDelta, Utc };
+use events::EventBus;
+use ids::{ RandomUuids, UuidSource };
+use message_size::OversizedMessageLayer;
+use metrics::{ Metrics, MetricsLayer };
+use shutdown::{ InFlight, InFlightLayer };
 use library::{
-    error::{ InvalidDetails, UnauthorisedRequester },
+    clock::{ self, Clock },
+    error::{
+        InvalidApproval,
+        InvalidDetails,
+        InvalidRevert,
+        InvalidSubmission,
+        OversizedNote,
+        SelfApproval,
+        StaleVersion,
+        TerminalStateError,
+        UnauthorisedRequester,
+        ValidationIssue,
+    },
+    history::{ HISTORY, HistoricalRecord, Note },
     state::{
         Approved,
         Cancelled,
         Draft,
         Executed,
         NeedsReapproval,
+        PartiallyApproved,
         PendingApproval,
+        Rejected,
+        STATES,
         SentToCounterparty,
+        Authority,
+        CancellableState,
+        TradeAction,
         TradeState,
+        Transition,
+        is_terminal_state,
+        state_id_from_name,
+        transitions_from,
     },
-    trade::{ Counterparty, Direction, Style, TradeDetails },
-    users::{ Requester, User },
+    trade::{ Counterparty, MutTradeDetails, QuorumProgress, SettlementDetails, TradeDetails },
+    users::{ Approver, Requester, User },
 };
 use proto::{ trade_handler_server::{ TradeHandlerServer, TradeHandler }, TradeUuid };
-use tokio::sync::RwLock;
-use tonic::{ Response, Status, transport::Server };
+use store::TradeStore;
+use tokio::sync::{ OwnedMutexGuard, mpsc };
+use tokio_stream::{ Stream, wrappers::{ ReceiverStream, TcpListenerStream } };
+use tonic::{ Response, Status, service::interceptor::InterceptedService, transport::Server };
 use uuid::Uuid;
 
+mod auth;
+mod breaker;
+mod classification;
+mod config;
+mod conversions;
+mod events;
+mod ids;
+mod message_size;
+mod metrics;
+mod shutdown;
+mod store;
+mod telemetry;
+
 mod proto {
     tonic::include_proto!("trade");
+
+    /// Every message and service of `trade.proto`, for gRPC reflection.
+    pub(crate) const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!(
+        "trade_descriptor"
+    );
 }
 
 #[derive(Debug, Default)]
 /// This is an unfortunate issue with following the
 /// generic type state pattern. We've relied on the
-/// states being monomorphised via generics, but in
-/// these scenarios it'd be best for each type to be
-/// dyn-compatible and instead implement some trait.
+/// states being monomorphised via generics, so the
+/// trade is held in the slot for its state, and read
+/// through `StoredTrade` whichever slot that is.
 struct ComposedTradeDetails {
     pending_approval: Option<TradeDetails<PendingApproval>>,
+    partially_approved: Option<TradeDetails<PartiallyApproved>>,
     needs_reapproval: Option<TradeDetails<NeedsReapproval>>,
     approved: Option<TradeDetails<Approved>>,
     sent_to_counterparty: Option<TradeDetails<SentToCounterparty>>,
@@ -41,43 +100,578 @@ struct ComposedTradeDetails {
     cancelled: Option<TradeDetails<Cancelled>>,
 }
 
+/// What's read of a stored trade regardless of its state, which keeps
+/// `TradeDetails` dyn-compatible where the server doesn't need its state.
+trait StoredTrade {
+    fn state_name(&self) -> &'static str;
+
+    fn trade_date(&self) -> &DateTime<Utc>;
+
+    fn counterparty(&self) -> &Counterparty;
+
+    fn to_response(&self) -> Result<proto::TradeStatusResponse, Status>;
+}
+
+impl<S: TradeState> StoredTrade for TradeDetails<S> {
+    fn state_name(&self) -> &'static str {
+        S::NAME
+    }
+
+    fn trade_date(&self) -> &DateTime<Utc> {
+        TradeDetails::<S>::trade_date(self)
+    }
+
+    fn counterparty(&self) -> &Counterparty {
+        TradeDetails::<S>::counterparty(self)
+    }
+
+    fn to_response(&self) -> Result<proto::TradeStatusResponse, Status> {
+        convert_trade_details_to_response(self)
+    }
+}
+
+impl ComposedTradeDetails {
+    /// The trade in whichever slot it's stored, the only place reading a trade
+    /// of any state names every slot.
+    fn stored(&self) -> Option<&dyn StoredTrade> {
+        match self {
+            Self { pending_approval: Some(details), .. } => Some(details),
+            Self { partially_approved: Some(details), .. } => Some(details),
+            Self { needs_reapproval: Some(details), .. } => Some(details),
+            Self { approved: Some(details), .. } => Some(details),
+            Self { sent_to_counterparty: Some(details), .. } => Some(details),
+            Self { executed: Some(details), .. } => Some(details),
+            Self { cancelled: Some(details), .. } => Some(details),
+            _ => None,
+        }
+    }
+
+    /// Name of the state the trade is stored in.
+    fn state_name(&self) -> Option<&'static str> {
+        self.stored().map(|details: &dyn StoredTrade| details.state_name())
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.state_name().and_then(state_id_from_name).is_some_and(is_terminal_state)
+    }
+
+    /// How long before `now` the trade was made, as `TradeDetails::age` by the
+    /// service's clock.
+    fn age(&self, now: &DateTime<Utc>) -> Option<TimeDelta> {
+        self.stored().map(|details: &dyn StoredTrade| *now - details.trade_date())
+    }
+
+    fn counterparty(&self) -> Option<&Counterparty> {
+        self.stored().map(|details: &dyn StoredTrade| details.counterparty())
+    }
+
+    /// Reads a trade serialized in whichever state it's stored in.
+    fn from_json(value: serde_json::Value) -> Result<Self, serde_json::Error> {
+        let state: String = value.get("state")
+            .and_then(serde_json::Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let mut composed: Self = Self::default();
+        match state.as_str() {
+            PendingApproval::NAME => {
+                composed.pending_approval = Some(serde_json::from_value(value)?)
+            },
+            PartiallyApproved::NAME => {
+                composed.partially_approved = Some(serde_json::from_value(value)?)
+            },
+            NeedsReapproval::NAME => {
+                composed.needs_reapproval = Some(serde_json::from_value(value)?)
+            },
+            Approved::NAME => composed.approved = Some(serde_json::from_value(value)?),
+            SentToCounterparty::NAME => {
+                composed.sent_to_counterparty = Some(serde_json::from_value(value)?)
+            },
+            Executed::NAME => composed.executed = Some(serde_json::from_value(value)?),
+            Cancelled::NAME => composed.cancelled = Some(serde_json::from_value(value)?),
+            _ => return Err(
+                serde::de::Error::custom(format!("{:?} trades can't be stored", state))
+            ),
+        }
+        Ok(composed)
+    }
+
+    /// Executed trades also report how they settle, where it's a single currency.
+    fn to_response(&self) -> Result<proto::TradeStatusResponse, Status> {
+        let Some(details) = self.stored() else {
+            return Err(Status::data_loss("Server Error."));
+        };
+        let mut response = details.to_response()?;
+        if let Some(executed) = &self.executed {
+            response.settlement = executed.settlement()
+                .as_ref()
+                .map(|settlement: &SettlementDetails| settlement.into());
+        }
+        Ok(response)
+    }
+}
+
 fn convert_trade_details_to_response<S: TradeState>(
     details: &TradeDetails<S>
 ) -> Result<proto::TradeStatusResponse, Status> {
     Ok(proto::TradeStatusResponse {
-        details: Some(proto::TradeDetails {
-            trading_entity: Some(proto::Username { user_id: details.trading_entity().to_string() }),
-            subdetails: Some(proto::MutableTradeDetails {
-                counterparty: details.counterparty().to_string(),
-                direction: <&Direction as Into<i32>>::into(details.direction()),
-                style: details.style().to_string(),
-                currency_code: details.currency().numeric() as u32,
-                currency_amount: details.amount(),
-                underlying_currency_codes: details
-                    .underlying()
-                    .clone()
-                    .iter()
-                    .map(|c: &Currency| { c.numeric() as u32 })
-                    .collect(),
-                value_date: details.value_date().to_rfc3339(),
-                delivery_date: details.delivery_date().to_rfc3339(),
-            }),
-            trade_date: details.trade_date().to_rfc3339(),
-            strike: details.strike().unwrap_or(0),
-        }),
+        details: Some(proto::TradeDetails::from(details)),
         status: S::ID as i32,
+        uuid: Some(TradeUuid { uuid: details.id().to_string() }),
+        settlement: None,
+    })
+}
+
+/// The state machine as data, derived from the library's transition table.
+fn describe_workflow() -> proto::DescribeWorkflowResponse {
+    let states: Vec<proto::WorkflowState> = STATES.iter()
+        .filter(|(id, _, _)| is_served_state(*id))
+        .map(|(id, name, _)| proto::WorkflowState {
+            status: *id as i32,
+            name: name.to_string(),
+            transitions: transitions_from(*id)
+                .filter(|transition: &&Transition| is_served_state(transition.to))
+                .map(|transition: &Transition| proto::WorkflowTransition {
+                    action: transition.action.to_string(),
+                    to: transition.to as i32,
+                    authority: (match transition.authority {
+                        Authority::Any => proto::Authority::Any,
+                        Authority::Requester => proto::Authority::Requester,
+                        Authority::Approver => proto::Authority::Approver,
+                    }) as i32,
+                })
+                .collect(),
+        })
+        .collect();
+    proto::DescribeWorkflowResponse { states }
+}
+
+/// Whether the server has an RPC reaching the state. No RPC rejects a trade,
+/// so `Rejected` is left out of the workflow and summary the server reports.
+fn is_served_state(state_id: u8) -> bool {
+    state_id != Rejected::ID
+}
+
+/// Whether a stored status is one the trade can never leave.
+fn is_terminal_status(status: i32) -> bool {
+    u8::try_from(status).is_ok_and(is_terminal_state)
+}
+
+/// Cancels the trade as whichever kind of user signed in. A requester may
+/// only cancel their own trades, whereas an approver may cancel any.
+fn cancel_as<S: CancellableState>(
+    user: &SignedInUser,
+    details: TradeDetails<S>,
+    reason: Note
+) -> Result<TradeDetails<Cancelled>, Status> {
+    match user {
+        SignedInUser::Requester(requester) => {
+            details
+                .cancel_as_requester(requester, Some(reason))
+                .map_err(<UnauthorisedRequester<S> as Into<Status>>::into)
+        }
+        SignedInUser::Approver(approver) => Ok(details.cancel_as_approver(approver, Some(reason))),
+    }
+}
+
+/// Rejects an update made against another version than the stored trade's,
+/// when the client sent the version it expects.
+fn check_expected_version<S: TradeState>(
+    details: &TradeDetails<S>,
+    expected: Option<u64>
+) -> Result<(), Status> {
+    match expected {
+        Some(expected) => details
+            .check_version(expected)
+            .map_err(<StaleVersion as Into<Status>>::into),
+        None => Ok(()),
+    }
+}
+
+/// Parses the UUID of the trade a request refers to, telling an empty UUID
+/// apart from a malformed one.
+fn parse_trade_uuid(raw_uuid: &str) -> Result<Uuid, Status> {
+    if raw_uuid.trim().is_empty() {
+        return Err(Status::invalid_argument("UUID is empty."));
+    }
+    Uuid::from_str(raw_uuid).map_err(|e: uuid::Error| {
+        Status::invalid_argument(format!("Invalid UUID {:?}, {}.", raw_uuid, e))
     })
 }
 
-#[derive(Default, Debug)]
+/// Locks a trade to transition, uniformly rejecting trades in a terminal state.
+async fn active_trade(
+    trades: &TradeStore,
+    uuid: &Uuid
+) -> Result<OwnedMutexGuard<ComposedTradeDetails>, Status> {
+    let Some(composed) = trades.lock(uuid).await else {
+        return Err(Status::not_found("Trade not found."));
+    };
+    if composed.is_terminal() {
+        let state: &'static str = composed.state_name().unwrap_or("?");
+        return Err(TerminalStateError { state }.into());
+    }
+    Ok(composed)
+}
+
+#[derive(Debug, Clone)]
 struct TradeHandlerService {
     /// Would be interested to know if there's a better
     /// whilst still following the generic state pattern.
-    mapping: Arc<RwLock<HashMap<Uuid, ComposedTradeDetails>>>,
+    trades: TradeStore,
+
+    config: Arc<ServerConfig>,
+
+    /// Where stored transitions are recorded, guarded by the breaker.
+    history_sink: Arc<dyn HistorySink>,
+
+    breaker: Arc<CircuitBreaker>,
+
+    /// Every status written into the store, for `subscribe` streams.
+    updates: EventBus,
+
+    metrics: Metrics,
+
+    /// Gives each submitted trade its UUID, random outside of tests.
+    uuids: Arc<dyn UuidSource>,
+
+    /// Tells the time trades expire and age by, the library's clock unless
+    /// injected.
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for TradeHandlerService {
+    fn default() -> Self {
+        Self::new(ServerConfig::default())
+    }
+}
+
+impl TradeHandlerService {
+    fn new(config: ServerConfig) -> Self {
+        Self {
+            trades: TradeStore::default(),
+            history_sink: Arc::new(InMemorySink::default()),
+            breaker: Arc::new(CircuitBreaker::new(config.breaker.clone())),
+            updates: EventBus::new(config.event_capacity),
+            metrics: Metrics::default(),
+            uuids: Arc::new(RandomUuids),
+            clock: clock::clock(),
+            config: Arc::new(config),
+        }
+    }
+
+    /// Seeds the service with trades already in any state, before it serves,
+    /// e.g. for demos. A trade whose UUID was already seeded is skipped.
+    fn with_initial_trades(self, trades: Vec<(Uuid, ComposedTradeDetails)>) -> Self {
+        for (uuid, composed) in trades {
+            if !self.trades.insert(uuid, composed) {
+                tracing::warn!(%uuid, "Skipped seeding a trade, as its UUID is taken.");
+            }
+        }
+        self
+    }
+
+    /// Records a transition into the history sink, through the breaker, before
+    /// it's stored. Handlers transition a clone of the stored trade, which the
+    /// library has already recorded in `HISTORY`, and only replace the stored
+    /// trade once this succeeds. So on failure, the library's record is
+    /// retracted too, leaving no trace of a transition that never happened.
+    fn record_transition(
+        &self,
+        uuid: &Uuid,
+        action: TradeAction,
+        state: &'static str
+    ) -> Result<(), Status> {
+        if let Err(status) = self.breaker.record(self.history_sink.as_ref(), uuid, &action, state) {
+            HISTORY.lock().unwrap().retract_last(*uuid, &action);
+            return Err(status);
+        }
+        self.metrics.count_transition(&action);
+        Ok(())
+    }
+
+    /// Sets the per state gauges from the trades currently stored.
+    async fn refresh_trade_gauges(&self) {
+        let mut states: Vec<Option<&'static str>> = Vec::new();
+        for (_, slot) in self.trades.slots() {
+            states.push(slot.lock().await.state_name());
+        }
+        self.metrics.set_trades_per_state(|name: &'static str| {
+            states
+                .iter()
+                .filter(|state: &&Option<&'static str>| **state == Some(name))
+                .count() as i64
+        });
+    }
+
+    /// Announces a status newly written into the store to any subscribers.
+    /// This never blocks, see `EventBus` for what happens to slow subscribers.
+    fn publish(&self, uuid: &Uuid, response: &proto::TradeStatusResponse) {
+        telemetry::record_state(response.status);
+        self.updates.publish(uuid, response);
+    }
+
+    /// Creates the draft trade a request describes, which lapses if it doesn't
+    /// progress. Nothing is stored, so this is also used to validate trades.
+    fn draft(
+        &self,
+        requester: &User<Requester>,
+        raw_details: &proto::MutableTradeDetails
+    ) -> Result<TradeDetails<Draft>, Status> {
+        let raw_details: MutTradeDetails = conversions::parse_details(
+            raw_details,
+            &self.config.limits
+        )?;
+        let details = TradeDetails::<Draft>
+            ::new(
+                requester,
+                raw_details.counterparty,
+                raw_details.direction,
+                raw_details.style,
+                raw_details.notional_currency,
+                raw_details.notional_amount,
+                raw_details.underlying,
+                raw_details.value_date,
+                raw_details.delivery_date
+            )
+            .map_err(<InvalidDetails as Into<Status>>::into)?
+            .expire_after(self.config.trade_ttl);
+        self.check_tenor(&details)?;
+        Ok(details)
+    }
+
+    fn check_tenor(&self, details: &TradeDetails<Draft>) -> Result<(), Status> {
+        let tenor_days: i64 = details.tenor_days();
+        if !self.config.tenor_allowed(tenor_days) {
+            return Err(
+                Status::invalid_argument(
+                    format!("Tenor of {} days is outside the permitted range.", tenor_days)
+                )
+            );
+        }
+        Ok(())
+    }
+
+    /// Every problem `draft` would refuse the details for, rather than only
+    /// the first.
+    fn problems(
+        &self,
+        requester: &User<Requester>,
+        raw_details: &proto::MutableTradeDetails
+    ) -> Vec<String> {
+        let raw_details: MutTradeDetails = match
+            conversions::parse_details_fully(raw_details, &self.config.limits)
+        {
+            Ok(raw_details) => raw_details,
+            Err(errors) => {
+                return errors
+                    .iter()
+                    .map(|status: &Status| status.message().to_string())
+                    .collect();
+            }
+        };
+        let created: Result<TradeDetails<Draft>, InvalidDetails> = TradeDetails::<Draft>::new(
+            requester,
+            raw_details.counterparty,
+            raw_details.direction,
+            raw_details.style,
+            raw_details.notional_currency,
+            raw_details.notional_amount,
+            raw_details.underlying,
+            raw_details.value_date,
+            raw_details.delivery_date
+        );
+        match created {
+            Ok(details) =>
+                self
+                    .check_tenor(&details)
+                    .err()
+                    .map(|status: Status| status.message().to_string())
+                    .into_iter()
+                    .collect(),
+            Err(invalid) =>
+                invalid
+                    .issues()
+                    .iter()
+                    .map(|issue: &ValidationIssue| issue.to_string())
+                    .collect(),
+        }
+    }
+
+    /// Sanitises a submit request, then submits the trade it describes,
+    /// which is left to the caller to store.
+    fn submission<T>(
+        &self,
+        request: &tonic::Request<T>,
+        input: &proto::TradeSubmitRequest
+    ) -> Result<(Uuid, ComposedTradeDetails, proto::TradeStatusResponse), Status> {
+        let Some(user) = &input.info else {
+            return Err(Status::invalid_argument("Username not specified"));
+        };
+        let requester = self.sign_in(request, user)?.requester(TradeAction::Submit)?;
+
+        let Some(raw_details) = &input.details else {
+            return Err(Status::invalid_argument("Details not specified"));
+        };
+        let details: TradeDetails<Draft> = self
+            .draft(&requester, raw_details)?
+            .with_id(self.uuids.next_uuid());
+
+        // Preparing the draft trade for submission, to as many approvers as required
+        let uuid: Uuid = *details.id();
+        if self.trades.contains(&uuid) {
+            return Err(Status::already_exists("Trade has already been submitted."));
+        }
+        let composed: ComposedTradeDetails = match input.approvals_required {
+            Some(approvals_required) => ComposedTradeDetails {
+                partially_approved: Some(
+                    details
+                        .submit_for_approvals(&requester, approvals_required as usize, None)
+                        .map_err(<InvalidSubmission as Into<Status>>::into)?
+                ),
+                ..ComposedTradeDetails::default()
+            },
+            None => ComposedTradeDetails {
+                pending_approval: Some(
+                    details
+                        .submit(&requester, None)
+                        .map_err(<UnauthorisedRequester<Draft> as Into<Status>>::into)?
+                ),
+                ..ComposedTradeDetails::default()
+            },
+        };
+
+        let response = composed.to_response()?;
+        self.record_transition(&uuid, TradeAction::Submit, composed.state_name().unwrap_or("?"))?;
+        Ok((uuid, composed, response))
+    }
+
+    /// Stores a submission, retracting its record if the UUID was taken since
+    /// it was checked.
+    fn store_submission(&self, uuid: Uuid, composed: ComposedTradeDetails) -> Result<(), Status> {
+        if !self.trades.insert(uuid, composed) {
+            HISTORY.lock().unwrap().retract_last(uuid, &TradeAction::Submit);
+            return Err(Status::already_exists("Trade has already been submitted."));
+        }
+        Ok(())
+    }
+
+    /// Cancels every trade which has expired before progressing past
+    /// approval, returning their UUIDs in ascending order.
+    async fn sweep_expired(&self, now: DateTime<Utc>) -> Vec<Uuid> {
+        let system: User<Approver> = User::sign_in("system");
+        let reason: Option<Note> = Note::new("Expired before progressing past approval.").ok();
+        let mut cancelled: Vec<Uuid> = Vec::new();
+        for (uuid, slot) in self.trades.slots() {
+            let mut composed = slot.lock().await;
+            let details: TradeDetails<Cancelled> = if
+                let Some(details) = composed.pending_approval
+                    .as_ref()
+                    .filter(|details| details.is_expired(&now))
+            {
+                details.clone().cancel(&system, reason.clone())
+            } else if
+                let Some(details) = composed.partially_approved
+                    .as_ref()
+                    .filter(|details| details.is_expired(&now))
+            {
+                details.clone().cancel(&system, reason.clone())
+            } else if
+                let Some(details) = composed.needs_reapproval
+                    .as_ref()
+                    .filter(|details| details.is_expired(&now))
+            {
+                details.clone().cancel(&system, reason.clone())
+            } else if
+                let Some(details) = composed.approved
+                    .as_ref()
+                    .filter(|details| details.is_expired(&now))
+            {
+                details.clone().cancel(&system, reason.clone())
+            } else {
+                continue;
+            };
+
+            // An unrecorded trade is left for the next sweep.
+            if self.record_transition(&uuid, TradeAction::Cancel, Cancelled::NAME).is_err() {
+                continue;
+            }
+            tracing::info!(%uuid, "Cancelled trade, as it expired unprogressed.");
+            if let Ok(response) = convert_trade_details_to_response(&details) {
+                self.publish(&uuid, &response);
+            }
+            *composed = ComposedTradeDetails {
+                cancelled: Some(details),
+                ..ComposedTradeDetails::default()
+            };
+            cancelled.push(uuid);
+        }
+        cancelled
+    }
+
+    /// Periodically sweeps expired trades, per the configured interval.
+    async fn run_expiry_sweeper(self) {
+        let mut interval = tokio::time::interval(self.config.sweep_interval);
+        loop {
+            interval.tick().await;
+            self.sweep_expired(self.clock.now()).await;
+        }
+    }
+
+    /// Signs in the request's user against the configured approvers.
+    fn sign_in<T>(
+        &self,
+        request: &tonic::Request<T>,
+        user: &proto::Username
+    ) -> Result<SignedInUser, Status> {
+        SignedInUser::sign_in(request, user, self.config.approvers.as_ref())
+    }
+
+    /// Common sanitisation of requests acting upon an existing trade.
+    fn parse_action_request(
+        &self,
+        request: &tonic::Request<proto::TradeActionRequest>
+    ) -> Result<(SignedInUser, Uuid, Option<Note>), Status> {
+        let input = request.get_ref();
+        let Some(user) = &input.info else {
+            return Err(Status::invalid_argument("Username not specified"));
+        };
+        let Some(raw_uuid) = &input.uuid else {
+            return Err(Status::invalid_argument("UUID not specified"));
+        };
+        let uuid: Uuid = parse_trade_uuid(&raw_uuid.uuid)?;
+        telemetry::record_uuid(&uuid);
+        let note: Option<Note> = self.parse_note(&input.note)?;
+        Ok((self.sign_in(request, user)?, uuid, note))
+    }
+
+    /// An optional note, which is empty for none.
+    fn parse_note(&self, text: &str) -> Result<Option<Note>, Status> {
+        match text {
+            "" => Ok(None),
+            text => {
+                Ok(
+                    Some(
+                        Note::with_limit(text, self.config.max_note_length).map_err(
+                            <OversizedNote as Into<Status>>::into
+                        )?
+                    )
+                )
+            }
+        }
+    }
 }
 
 #[tonic::async_trait]
 impl TradeHandler for TradeHandlerService {
+    type SubscribeStream = Pin<
+        Box<dyn Stream<Item = Result<proto::TradeStatusResponse, Status>> + Send>
+    >;
+
+    #[tracing::instrument(
+        skip_all,
+        err(level = "warn"),
+        fields(uuid = tracing::field::Empty, state = tracing::field::Empty)
+    )]
     async fn status(
         &self,
         request: tonic::Request<proto::TradeStatusRequest>
@@ -87,140 +681,2133 @@ impl TradeHandler for TradeHandlerService {
         let Some(raw_uuid) = &input.uuid else {
             return Err(Status::invalid_argument("UUID not specified"));
         };
-        let uuid: Uuid = Uuid::from_str(&raw_uuid.uuid).map_err(|e: uuid::Error| {
-            Status::invalid_argument(format!("Invalid UUID, {}.", e))
-        })?;
+        let uuid: Uuid = parse_trade_uuid(&raw_uuid.uuid)?;
+        telemetry::record_uuid(&uuid);
 
-        // Retrieving the details
-        let map = self.mapping.read().await;
-        let Some(composed) = map.get(&uuid) else {
+        // Retrieving the details, locking only this trade
+        let Some(composed) = self.trades.lock(&uuid).await else {
             return Err(Status::not_found("Trade not found."));
         };
-        // Preparing the response
-        let response = if let Some(pending_approval) = &composed.pending_approval {
-            convert_trade_details_to_response(pending_approval)?
-        } else if let Some(needs_reapproval) = &composed.needs_reapproval {
-            convert_trade_details_to_response(needs_reapproval)?
-        } else if let Some(approved) = &composed.approved {
-            convert_trade_details_to_response(approved)?
-        } else if let Some(sent_to_counterparty) = &composed.sent_to_counterparty {
-            convert_trade_details_to_response(sent_to_counterparty)?
-        } else if let Some(executed) = &composed.executed {
-            convert_trade_details_to_response(&executed)?
-        } else if let Some(cancelled) = &composed.cancelled {
-            convert_trade_details_to_response(cancelled)?
-        } else {
-            return Err(Status::data_loss("Server Error."));
-        };
+        let response = composed.to_response()?;
+        telemetry::record_state(response.status);
         Ok(Response::<proto::TradeStatusResponse>::new(response))
     }
 
-    async fn submit(
+    #[tracing::instrument(skip_all, err(level = "warn"))]
+    async fn batch_status(
         &self,
-        request: tonic::Request<proto::TradeSubmitRequest>
-    ) -> Result<tonic::Response<proto::TradeSubmitResponse>, Status> {
+        request: tonic::Request<proto::BatchStatusRequest>
+    ) -> Result<tonic::Response<proto::BatchStatusResponse>, Status> {
+        // Each trade is locked in turn, so the batch never holds up other trades.
+        let mut results: Vec<proto::BatchStatusResult> = Vec::new();
+        for raw_uuid in request.into_inner().uuids {
+            let slot = parse_trade_uuid(&raw_uuid.uuid).and_then(|uuid: Uuid| {
+                self.trades.get(&uuid).ok_or(Status::not_found("Trade not found."))
+            });
+            let response: Result<proto::TradeStatusResponse, Status> = match slot {
+                Ok(slot) => slot.lock().await.to_response(),
+                Err(status) => Err(status),
+            };
+            let result: proto::batch_status_result::Result = match response {
+                Ok(response) => proto::batch_status_result::Result::Status(response),
+                Err(status) => {
+                    proto::batch_status_result::Result::Error(proto::BatchStatusError {
+                        code: status.code() as i32,
+                        message: status.message().to_string(),
+                    })
+                }
+            };
+            results.push(proto::BatchStatusResult { uuid: Some(raw_uuid), result: Some(result) });
+        }
+        Ok(Response::new(proto::BatchStatusResponse { results }))
+    }
+
+    #[tracing::instrument(
+        skip_all,
+        err(level = "warn"),
+        fields(uuid = tracing::field::Empty, state = tracing::field::Empty)
+    )]
+    async fn cancel(
+        &self,
+        request: tonic::Request<proto::TradeCancelRequest>
+    ) -> Result<tonic::Response<proto::TradeStatusResponse>, Status> {
         // Sanitisation of the inbound request
         let input = request.get_ref();
         let Some(user) = &input.info else {
             return Err(Status::invalid_argument("Username not specified"));
         };
-        let requester = User::<Requester>::sign_in(&user.user_id);
+        let Some(raw_uuid) = &input.uuid else {
+            return Err(Status::invalid_argument("UUID not specified"));
+        };
+        let uuid: Uuid = parse_trade_uuid(&raw_uuid.uuid)?;
+        telemetry::record_uuid(&uuid);
+        if input.reason.trim().is_empty() {
+            return Err(Status::invalid_argument("A reason must be given to cancel a trade."));
+        }
+        let reason: Note = Note::with_limit(
+            input.reason.clone(),
+            self.config.max_note_length
+        ).map_err(<OversizedNote as Into<Status>>::into)?;
+        let user: SignedInUser = self.sign_in(&request, user)?;
 
-        let Some(raw_details) = &input.details else {
-            return Err(Status::invalid_argument("Details not specified"));
+        let mut composed = active_trade(&self.trades, &uuid).await?;
+
+        let details: TradeDetails<Cancelled> = if let Some(details) = &composed.pending_approval {
+            cancel_as(&user, details.clone(), reason)?
+        } else if let Some(details) = &composed.partially_approved {
+            cancel_as(&user, details.clone(), reason)?
+        } else if let Some(details) = &composed.needs_reapproval {
+            cancel_as(&user, details.clone(), reason)?
+        } else if let Some(details) = &composed.approved {
+            cancel_as(&user, details.clone(), reason)?
+        } else if let Some(details) = &composed.sent_to_counterparty {
+            cancel_as(&user, details.clone(), reason)?
+        } else {
+            return Err(Status::failed_precondition("Trade can't be cancelled from its state."));
         };
+        self.record_transition(&uuid, TradeAction::Cancel, Cancelled::NAME)?;
+        let response = convert_trade_details_to_response(&details)?;
+        *composed = ComposedTradeDetails {
+            cancelled: Some(details),
+            ..ComposedTradeDetails::default()
+        };
+        self.publish(&uuid, &response);
+        Ok(Response::new(response))
+    }
 
-        let direction: Direction = raw_details.direction.try_into()?;
+    #[tracing::instrument(
+        skip_all,
+        err(level = "warn"),
+        fields(uuid = tracing::field::Empty)
+    )]
+    async fn history(
+        &self,
+        request: tonic::Request<proto::TradeStatusRequest>
+    ) -> Result<tonic::Response<proto::TradeHistoryResponse>, Status> {
+        let input = request.get_ref();
+        let Some(raw_uuid) = &input.uuid else {
+            return Err(Status::invalid_argument("UUID not specified"));
+        };
+        let uuid: Uuid = parse_trade_uuid(&raw_uuid.uuid)?;
+        telemetry::record_uuid(&uuid);
 
-        let currency: Currency = Currency::from_numeric(
-            raw_details.currency_code
-                .try_into()
-                .map_err(|_| { Status::invalid_argument("Currency doesn't follow ISO standard.") })?
-        ).ok_or(Status::invalid_argument("Currency doesn't follow ISO standard."))?;
+        if !self.trades.contains(&uuid) {
+            return Err(Status::not_found("Trade not found."));
+        }
+        let records: Vec<proto::HistoricalRecord> = HISTORY.lock()
+            .unwrap()
+            .records_for(uuid)
+            .iter()
+            .map(proto::HistoricalRecord::from)
+            .collect();
+        Ok(Response::new(proto::TradeHistoryResponse { records }))
+    }
 
-        let underlying: Vec<Currency> = raw_details.underlying_currency_codes
-            .clone()
-            .into_iter()
-            .map(|code: u32| {
-                Currency::from_numeric(
-                    code
-                        .try_into()
-                        .map_err(|_| {
-                            Status::invalid_argument(
-                                "Underlying currency codes don't follow ISO standard."
-                            )
-                        })?
-                ).ok_or(
-                    Status::invalid_argument("Underlying currency codes don't follow ISO standard.")
-                )
+    #[tracing::instrument(skip_all, err(level = "warn"))]
+    async fn batch_submit(
+        &self,
+        request: tonic::Request<proto::BatchSubmitRequest>
+    ) -> Result<tonic::Response<proto::BatchSubmitResponse>, Status> {
+        let error = |status: Status| {
+            proto::batch_submit_result::Result::Error(proto::BatchStatusError {
+                code: status.code() as i32,
+                message: status.message().to_string(),
             })
-            .collect::<Result<Vec<Currency>, Status>>()?;
+        };
 
-        let value_date: DateTime<Utc> = raw_details.value_date
-            .parse()
-            .map_err(|_| {
-                Status::invalid_argument("Value Date doesn't follow the UTC standard.")
-            })?;
+        let mut results: Vec<proto::batch_submit_result::Result> = Vec::new();
+        let mut submitted: Vec<(usize, Uuid, ComposedTradeDetails, proto::TradeStatusResponse)> =
+            Vec::new();
+        for (index, entry) in request.get_ref().entries.iter().enumerate() {
+            match self.submission(&request, entry) {
+                Ok((uuid, composed, response)) => {
+                    results.push(
+                        proto::batch_submit_result::Result::Uuid(TradeUuid {
+                            uuid: uuid.to_string(),
+                        })
+                    );
+                    submitted.push((index, uuid, composed, response));
+                }
+                Err(status) => results.push(error(status)),
+            }
+        }
+
+        let mut stored: Vec<(Uuid, proto::TradeStatusResponse)> = Vec::new();
+        for (index, uuid, composed, response) in submitted {
+            if let Err(status) = self.store_submission(uuid, composed) {
+                results[index] = error(status);
+                continue;
+            }
+            tracing::info!(%uuid, "Submitted trade in a batch.");
+            stored.push((uuid, response));
+        }
+        for (uuid, response) in &stored {
+            self.publish(uuid, response);
+        }
 
-        let delivery_date: DateTime<Utc> = raw_details.delivery_date
-            .parse()
-            .map_err(|_| {
-                Status::invalid_argument("Delivery Date doesn't follow the UTC standard.")
-            })?;
+        let results: Vec<proto::BatchSubmitResult> = results
+            .into_iter()
+            .map(|result: proto::batch_submit_result::Result| proto::BatchSubmitResult {
+                result: Some(result),
+            })
+            .collect();
+        Ok(Response::new(proto::BatchSubmitResponse { results }))
+    }
 
-        // Creating the draft trade
-        let details = TradeDetails::<Draft>
-            ::new(
-                &requester,
-                Counterparty(raw_details.counterparty.clone()),
-                direction,
-                Style(raw_details.style.clone()),
-                currency,
-                raw_details.currency_amount,
-                underlying,
-                value_date,
-                delivery_date
-            )
-            .map_err(<InvalidDetails as Into<Status>>::into)?;
+    #[tracing::instrument(skip_all)]
+    async fn summary(
+        &self,
+        _: tonic::Request<proto::SummaryRequest>
+    ) -> Result<tonic::Response<proto::SummaryResponse>, Status> {
+        let mut counts: HashMap<u32, u64> = STATES.iter()
+            .filter(|(id, _, _)| is_served_state(*id))
+            .map(|(id, _, _)| (*id as u32, 0))
+            .collect();
+        for (_, slot) in self.trades.slots() {
+            if let Some(id) = slot.lock().await.state_name().and_then(state_id_from_name) {
+                *counts.entry(id as u32).or_default() += 1;
+            }
+        }
+        Ok(Response::new(proto::SummaryResponse { counts }))
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn stale_trades(
+        &self,
+        request: tonic::Request<proto::StaleTradesRequest>
+    ) -> Result<tonic::Response<proto::StaleTradesResponse>, Status> {
+        let threshold: TimeDelta = TimeDelta::days(request.get_ref().older_than_days as i64);
+        let now: DateTime<Utc> = self.clock.now();
+        let mut uuids: Vec<TradeUuid> = Vec::new();
+        for (uuid, slot) in self.trades.slots() {
+            let composed = slot.lock().await;
+            let stale: bool = composed.age(&now).is_some_and(|age: TimeDelta| age > threshold);
+            if stale && !composed.is_terminal() {
+                uuids.push(TradeUuid { uuid: uuid.to_string() });
+            }
+        }
+        Ok(Response::new(proto::StaleTradesResponse { uuids }))
+    }
 
-        // Preparing the draft trade for submission
-        let details = details
-            .submit(&requester)
-            .map_err(<UnauthorisedRequester<Draft> as Into<Status>>::into)?;
+    #[tracing::instrument(
+        skip_all,
+        err(level = "warn"),
+        fields(uuid = tracing::field::Empty, state = tracing::field::Empty)
+    )]
+    async fn update(
+        &self,
+        request: tonic::Request<proto::TradeUpdateRequest>
+    ) -> Result<tonic::Response<proto::TradeStatusResponse>, Status> {
+        // Sanitisation of the inbound request
+        let input = request.get_ref();
+        let Some(user) = &input.info else {
+            return Err(Status::invalid_argument("Username not specified"));
+        };
+        let Some(raw_uuid) = &input.uuid else {
+            return Err(Status::invalid_argument("UUID not specified"));
+        };
+        let Some(raw_details) = &input.details else {
+            return Err(Status::invalid_argument("Details not specified"));
+        };
+        let uuid: Uuid = parse_trade_uuid(&raw_uuid.uuid)?;
+        telemetry::record_uuid(&uuid);
+        let note: Option<Note> = self.parse_note(&input.note)?;
+        let approver: User<Approver> = self
+            .sign_in(&request, user)?
+            .approver(TradeAction::Update)?;
 
-        let uuid = Uuid::new_v4();
+        let mut composed = active_trade(&self.trades, &uuid).await?;
+        let limits: &FieldLimits = &self.config.limits;
+        let merge = |existing: MutTradeDetails| -> Result<MutTradeDetails, Status> {
+            conversions::merge_details(existing, raw_details, &input.field_mask, limits)
+        };
 
+        let response: proto::TradeStatusResponse = if
+            let Some(details) = &composed.pending_approval
         {
-            // Storing the details, scoped to reduce limit write lock.
-            let mut map = self.mapping.write().await;
-            if map.contains_key(&uuid) {
-                return Err(Status::already_exists("Trade has already been submitted."));
-            }
+            check_expected_version(details, input.expected_version)?;
+            let new_details: MutTradeDetails = merge(details.snapshot())?;
+            let details: TradeDetails<NeedsReapproval> = details
+                .clone()
+                .update(&approver, new_details, note)
+                .map_err(<InvalidDetails as Into<Status>>::into)?;
+            self.record_transition(&uuid, TradeAction::Update, NeedsReapproval::NAME)?;
+            let response = convert_trade_details_to_response(&details)?;
+            composed.pending_approval = None;
+            composed.needs_reapproval = Some(details);
+            response
+        } else if let Some(details) = &composed.approved {
+            check_expected_version(details, input.expected_version)?;
+            let new_details: MutTradeDetails = merge(details.snapshot())?;
+            let details: TradeDetails<NeedsReapproval> = details
+                .clone()
+                .amend(&approver, new_details, note)
+                .map_err(<InvalidDetails as Into<Status>>::into)?;
+            self.record_transition(&uuid, TradeAction::Update, NeedsReapproval::NAME)?;
+            let response = convert_trade_details_to_response(&details)?;
+            composed.approved = None;
+            composed.needs_reapproval = Some(details);
+            response
+        } else if let Some(details) = &composed.sent_to_counterparty {
+            check_expected_version(details, input.expected_version)?;
+            let new_details: MutTradeDetails = merge(details.snapshot())?;
+            let details: TradeDetails<Approved> = details
+                .clone()
+                .amend_before_book(&approver, new_details, note)
+                .map_err(<InvalidDetails as Into<Status>>::into)?;
+            self.record_transition(&uuid, TradeAction::Update, Approved::NAME)?;
+            let response = convert_trade_details_to_response(&details)?;
+            composed.sent_to_counterparty = None;
+            composed.approved = Some(details);
+            response
+        } else {
+            return Err(Status::failed_precondition("Trade can't be updated from its state."));
+        };
+        self.publish(&uuid, &response);
+        Ok(Response::new(response))
+    }
 
-            let composed = ComposedTradeDetails {
-                pending_approval: Some(details),
+    #[tracing::instrument(skip_all, err(level = "warn"))]
+    async fn cancel_all_for_counterparty(
+        &self,
+        request: tonic::Request<proto::CounterpartyCancelRequest>
+    ) -> Result<tonic::Response<proto::CounterpartyCancelResponse>, Status> {
+        // Sanitisation of the inbound request
+        let input = request.get_ref();
+        let Some(user) = &input.info else {
+            return Err(Status::invalid_argument("Username not specified"));
+        };
+        if input.counterparty.trim().is_empty() {
+            return Err(Status::invalid_argument("Counterparty not specified"));
+        }
+        if input.reason.trim().is_empty() {
+            return Err(Status::invalid_argument("A reason must be given to cancel a trade."));
+        }
+        let reason: Note = Note::with_limit(
+            input.reason.clone(),
+            self.config.max_note_length
+        ).map_err(<OversizedNote as Into<Status>>::into)?;
+        let approver: User<Approver> = self
+            .sign_in(&request, user)?
+            .approver(TradeAction::Cancel)?;
+        let counterparty: Counterparty = Counterparty::new(input.counterparty.as_str());
+
+        // Each trade is cancelled under its own lock, so a failure to record
+        // one leaves those before it cancelled, to be skipped on a retry.
+        let mut uuids: Vec<TradeUuid> = Vec::new();
+        for (uuid, slot) in self.trades.slots() {
+            let mut composed = slot.lock().await;
+            if composed.is_terminal() || composed.counterparty() != Some(&counterparty) {
+                continue;
+            }
+            let reason: Option<Note> = Some(reason.clone());
+            let details: TradeDetails<Cancelled> = if
+                let Some(details) = &composed.pending_approval
+            {
+                details.clone().cancel_as_approver(&approver, reason)
+            } else if let Some(details) = &composed.partially_approved {
+                details.clone().cancel_as_approver(&approver, reason)
+            } else if let Some(details) = &composed.needs_reapproval {
+                details.clone().cancel_as_approver(&approver, reason)
+            } else if let Some(details) = &composed.approved {
+                details.clone().cancel_as_approver(&approver, reason)
+            } else if let Some(details) = &composed.sent_to_counterparty {
+                details.clone().cancel_as_approver(&approver, reason)
+            } else {
+                continue;
+            };
+            self.record_transition(&uuid, TradeAction::Cancel, Cancelled::NAME)?;
+            tracing::info!(%uuid, %counterparty, "Cancelled trade with the counterparty.");
+            let response = convert_trade_details_to_response(&details)?;
+            *composed = ComposedTradeDetails {
+                cancelled: Some(details),
                 ..ComposedTradeDetails::default()
             };
-            (*map).insert(uuid, composed);
+            self.publish(&uuid, &response);
+            uuids.push(TradeUuid { uuid: uuid.to_string() });
         }
-
-        // Sending the response
-        Ok(
-            Response::<proto::TradeSubmitResponse>::new(proto::TradeSubmitResponse {
-                uuid: Some(TradeUuid { uuid: uuid.to_string() }),
-            })
-        )
+        Ok(Response::new(proto::CounterpartyCancelResponse { uuids }))
     }
-}
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let address: SocketAddr = "[::1]:25565".parse()?;
-    println!("TradeHandlerServer listening on {}", address);
+    #[tracing::instrument(skip_all, err(level = "warn"))]
+    async fn validate(
+        &self,
+        request: tonic::Request<proto::TradeSubmitRequest>
+    ) -> Result<tonic::Response<proto::TradeValidateResponse>, Status> {
+        // Sanitisation of the inbound request, as for a submission
+        let input = request.get_ref();
+        let Some(user) = &input.info else {
+            return Err(Status::invalid_argument("Username not specified"));
+        };
+        let requester = self.sign_in(&request, user)?.requester(TradeAction::Submit)?;
+        let Some(raw_details) = &input.details else {
+            return Err(Status::invalid_argument("Details not specified"));
+        };
+
+        let problems: Vec<String> = self.problems(&requester, raw_details);
+        Ok(
+            Response::new(proto::TradeValidateResponse {
+                valid: problems.is_empty(),
+                problems,
+            })
+        )
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn describe_workflow(
+        &self,
+        _: tonic::Request<proto::DescribeWorkflowRequest>
+    ) -> Result<tonic::Response<proto::DescribeWorkflowResponse>, Status> {
+        Ok(Response::new(describe_workflow()))
+    }
+
+    #[tracing::instrument(
+        skip_all,
+        err(level = "warn"),
+        fields(uuid = tracing::field::Empty, state = tracing::field::Empty)
+    )]
+    async fn submit(
+        &self,
+        request: tonic::Request<proto::TradeSubmitRequest>
+    ) -> Result<tonic::Response<proto::TradeSubmitResponse>, Status> {
+        let (uuid, composed, response) = self.submission(&request, request.get_ref())?;
+        telemetry::record_uuid(&uuid);
+
+        // Storing the details
+        self.store_submission(uuid, composed)?;
+        self.publish(&uuid, &response);
+
+        // Sending the response
+        Ok(
+            Response::<proto::TradeSubmitResponse>::new(proto::TradeSubmitResponse {
+                uuid: Some(TradeUuid { uuid: uuid.to_string() }),
+            })
+        )
+    }
+
+    #[tracing::instrument(
+        skip_all,
+        err(level = "warn"),
+        fields(uuid = tracing::field::Empty, state = tracing::field::Empty)
+    )]
+    async fn accept(
+        &self,
+        request: tonic::Request<proto::TradeActionRequest>
+    ) -> Result<tonic::Response<proto::TradeStatusResponse>, Status> {
+        let (user, uuid, note) = self.parse_action_request(&request)?;
+        let approver = user.approver(TradeAction::Accept)?;
+
+        let mut composed = active_trade(&self.trades, &uuid).await?;
+
+        let accepted: ComposedTradeDetails = if let Some(details) = &composed.pending_approval {
+            let details: TradeDetails<Approved> = details
+                .clone()
+                .accept(&approver, note)
+                .map_err(<SelfApproval as Into<Status>>::into)?;
+            ComposedTradeDetails { approved: Some(details), ..ComposedTradeDetails::default() }
+        } else if let Some(details) = &composed.partially_approved {
+            // Signing off, which only approves the trade once the quorum is met.
+            match details.clone().accept(&approver, note) {
+                Ok(QuorumProgress::Pending(details)) => ComposedTradeDetails {
+                    partially_approved: Some(details),
+                    ..ComposedTradeDetails::default()
+                },
+                Ok(QuorumProgress::Approved(details)) => ComposedTradeDetails {
+                    approved: Some(details),
+                    ..ComposedTradeDetails::default()
+                },
+                Err(error) => return Err(<InvalidApproval as Into<Status>>::into(error)),
+            }
+        } else {
+            return Err(Status::failed_precondition("Trade isn't pending approval."));
+        };
+        let state: &'static str = accepted.state_name().unwrap_or("?");
+        self.record_transition(&uuid, TradeAction::Accept, state)?;
+        let response = accepted.to_response()?;
+        *composed = accepted;
+        self.publish(&uuid, &response);
+        Ok(Response::new(response))
+    }
+
+    #[tracing::instrument(
+        skip_all,
+        err(level = "warn"),
+        fields(uuid = tracing::field::Empty, state = tracing::field::Empty)
+    )]
+    async fn approve(
+        &self,
+        request: tonic::Request<proto::TradeActionRequest>
+    ) -> Result<tonic::Response<proto::TradeStatusResponse>, Status> {
+        let (user, uuid, note) = self.parse_action_request(&request)?;
+        let requester = user.requester(TradeAction::Approve)?;
+
+        let mut composed = active_trade(&self.trades, &uuid).await?;
+        let Some(details) = &composed.needs_reapproval else {
+            return Err(Status::failed_precondition("Trade doesn't need reapproval."));
+        };
+
+        let details: TradeDetails<Approved> = details
+            .clone()
+            .approve(&requester, note)
+            .map_err(<UnauthorisedRequester<NeedsReapproval> as Into<Status>>::into)?;
+        self.record_transition(&uuid, TradeAction::Approve, Approved::NAME)?;
+        let response = convert_trade_details_to_response(&details)?;
+        composed.needs_reapproval = None;
+        composed.approved = Some(details);
+        self.publish(&uuid, &response);
+        Ok(Response::new(response))
+    }
+
+    #[tracing::instrument(
+        skip_all,
+        err(level = "warn"),
+        fields(uuid = tracing::field::Empty, state = tracing::field::Empty)
+    )]
+    async fn revert(
+        &self,
+        request: tonic::Request<proto::TradeActionRequest>
+    ) -> Result<tonic::Response<proto::TradeStatusResponse>, Status> {
+        let (user, uuid, note) = self.parse_action_request(&request)?;
+        let approver = user.approver(TradeAction::Revert)?;
+
+        let mut composed = active_trade(&self.trades, &uuid).await?;
+        let record: HistoricalRecord = HISTORY.lock()
+            .unwrap()
+            .last_reversible(uuid)
+            .ok_or(Status::failed_precondition("Trade has no transition which can be reverted."))?;
+        let into_status = <InvalidRevert as Into<Status>>::into;
+
+        // Approved trades revert to whichever state approved them.
+        let response = if let Some(details) = &composed.approved {
+            match record.state_before() {
+                PendingApproval::NAME => {
+                    let details = details
+                        .clone()
+                        .revert::<PendingApproval>(&approver, &record, note);
+                    let details = details.map_err(into_status)?;
+                    self.record_transition(&uuid, TradeAction::Revert, PendingApproval::NAME)?;
+                    let response = convert_trade_details_to_response(&details)?;
+                    composed.approved = None;
+                    composed.pending_approval = Some(details);
+                    response
+                }
+                PartiallyApproved::NAME => {
+                    let details = details
+                        .clone()
+                        .revert::<PartiallyApproved>(&approver, &record, note);
+                    let details = details.map_err(into_status)?;
+                    self.record_transition(&uuid, TradeAction::Revert, PartiallyApproved::NAME)?;
+                    let response = convert_trade_details_to_response(&details)?;
+                    composed.approved = None;
+                    composed.partially_approved = Some(details);
+                    response
+                }
+                NeedsReapproval::NAME => {
+                    let details = details
+                        .clone()
+                        .revert::<NeedsReapproval>(&approver, &record, note);
+                    let details = details.map_err(into_status)?;
+                    self.record_transition(&uuid, TradeAction::Revert, NeedsReapproval::NAME)?;
+                    let response = convert_trade_details_to_response(&details)?;
+                    composed.approved = None;
+                    composed.needs_reapproval = Some(details);
+                    response
+                }
+                _ => {
+                    return Err(
+                        Status::failed_precondition("Trade's latest transition can't be reverted.")
+                    );
+                }
+            }
+        } else if let Some(details) = &composed.partially_approved {
+            // Withdraws the latest sign off, staying partially approved.
+            let details = details.clone().revert::<PartiallyApproved>(&approver, &record, note);
+            let details = details.map_err(into_status)?;
+            self.record_transition(&uuid, TradeAction::Revert, PartiallyApproved::NAME)?;
+            let response = convert_trade_details_to_response(&details)?;
+            composed.partially_approved = Some(details);
+            response
+        } else if let Some(details) = &composed.needs_reapproval {
+            let details = details.clone().revert::<PendingApproval>(&approver, &record, note);
+            let details = details.map_err(into_status)?;
+            self.record_transition(&uuid, TradeAction::Revert, PendingApproval::NAME)?;
+            let response = convert_trade_details_to_response(&details)?;
+            composed.needs_reapproval = None;
+            composed.pending_approval = Some(details);
+            response
+        } else if let Some(details) = &composed.sent_to_counterparty {
+            let details = details.clone().revert::<Approved>(&approver, &record, note);
+            let details = details.map_err(into_status)?;
+            self.record_transition(&uuid, TradeAction::Revert, Approved::NAME)?;
+            let response = convert_trade_details_to_response(&details)?;
+            composed.sent_to_counterparty = None;
+            composed.approved = Some(details);
+            response
+        } else {
+            return Err(Status::failed_precondition("Trade can't be reverted from its state."));
+        };
+        self.publish(&uuid, &response);
+        Ok(Response::new(response))
+    }
+
+    #[tracing::instrument(
+        skip_all,
+        err(level = "warn"),
+        fields(uuid = tracing::field::Empty, state = tracing::field::Empty)
+    )]
+    async fn subscribe(
+        &self,
+        request: tonic::Request<proto::TradeStatusRequest>
+    ) -> Result<tonic::Response<Self::SubscribeStream>, Status> {
+        let input = request.get_ref();
+        let Some(raw_uuid) = &input.uuid else {
+            return Err(Status::invalid_argument("UUID not specified"));
+        };
+        let uuid: Uuid = parse_trade_uuid(&raw_uuid.uuid)?;
+        telemetry::record_uuid(&uuid);
+
+        // Subscribing before the lookup, so no transition in between is missed.
+        let mut updates = self.updates.subscribe();
+        let Some(composed) = self.trades.lock(&uuid).await else {
+            return Err(Status::not_found("Trade not found."));
+        };
+        let already_terminal: bool = composed.is_terminal();
+        drop(composed);
+
+        let (sender, receiver) = mpsc::channel::<Result<proto::TradeStatusResponse, Status>>(16);
+        if !already_terminal {
+            // Stops once the subscriber disconnects, even while no events arrive.
+            tokio::spawn(async move {
+                loop {
+                    let (updated, response) = tokio::select! {
+                        event = updates.recv() => match event {
+                            Some(event) => event,
+                            None => break,
+                        },
+                        _ = sender.closed() => break,
+                    };
+                    if updated != uuid {
+                        continue;
+                    }
+                    let terminal: bool = is_terminal_status(response.status);
+                    if sender.send(Ok(response)).await.is_err() || terminal {
+                        break;
+                    }
+                }
+            });
+        }
+        Ok(Response::new(Box::pin(ReceiverStream::new(receiver)) as Self::SubscribeStream))
+    }
+}
+
+/// Serves the handler until `signal` resolves, after which new connections
+/// are refused while the requests counted by `in_flight` are completed.
+async fn serve(
+    service: TradeHandlerService,
+    interceptor: AuthInterceptor,
+    listener: tokio::net::TcpListener,
+    in_flight: InFlight,
+    signal: impl Future<Output = ()>
+) -> Result<(), tonic::transport::Error> {
+    let draining: InFlight = in_flight.clone();
+    let max_message_bytes: usize = service.config.max_message_bytes;
+    let trades = TradeHandlerServer::new(service).max_decoding_message_size(max_message_bytes);
+
+    // The trades are held in memory, so are ready as soon as the service is.
+    let (health, health_service) = tonic_health::server::health_reporter();
+    health.set_serving::<TradeHandlerServer<TradeHandlerService>>().await;
+
+    // Registration only fails on a malformed descriptor set, which is generated by the build.
+    let reflection = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(proto::FILE_DESCRIPTOR_SET)
+        .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
+        .build_v1()
+        .unwrap();
 
     Server::builder()
-        .add_service(TradeHandlerServer::new(TradeHandlerService::default()))
-        .serve(address).await?;
+        .layer(InFlightLayer::new(in_flight))
+        .layer(OversizedMessageLayer::new(max_message_bytes))
+        .layer(ClassifyLayer)
+        .add_service(health_service)
+        .add_service(reflection)
+        .add_service(InterceptedService::new(trades, interceptor))
+        .serve_with_incoming_shutdown(TcpListenerStream::new(listener), async move {
+            signal.await;
+            health.set_not_serving::<TradeHandlerServer<TradeHandlerService>>().await;
+            tracing::info!(requests = draining.count(), "Shutting down, draining requests.");
+        }).await?;
+    tracing::info!("Shut down, every in-flight request was drained.");
+    Ok(())
+}
+
+/// Reads the trades to seed the service with, keyed by their own UUIDs.
+fn load_seed_trades(
+    path: &std::path::Path
+) -> Result<Vec<(Uuid, ComposedTradeDetails)>, Box<dyn std::error::Error>> {
+    let raw: String = std::fs::read_to_string(path)?;
+    let values: Vec<serde_json::Value> = serde_json::from_str(&raw)?;
+    let mut trades: Vec<(Uuid, ComposedTradeDetails)> = Vec::with_capacity(values.len());
+    for value in values {
+        let uuid: Uuid = Uuid::parse_str(
+            value.get("id").and_then(serde_json::Value::as_str).unwrap_or_default()
+        )?;
+        trades.push((uuid, ComposedTradeDetails::from_json(value)?));
+    }
+    Ok(trades)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let config_path: Option<PathBuf> = std::env::args_os()
+        .nth(1)
+        .or_else(|| std::env::var_os(config::CONFIG_PATH_VAR))
+        .map(PathBuf::from);
+    let mut config: ServerConfig = ServerConfig::load(config_path.as_deref())?;
+    if let Ok(raw) = std::env::var(config::LISTEN_ADDR_VAR) {
+        config.listen_address = config::resolve_listen_address(Some(&raw))?;
+    }
+    config.validate()?;
+    library::policy::set_policy(config.policy.clone());
+    HISTORY.lock().unwrap().set_capacity(config.history_capacity);
+    let address: SocketAddr = config.listen_address;
+    let metrics_address: SocketAddr = config.metrics_address;
+
+    let interceptor = AuthInterceptor::new(Arc::new(StaticTokenVerifier::from_env()));
+    let seed_trades: Vec<(Uuid, ComposedTradeDetails)> = match &config.seed_trades {
+        Some(path) => load_seed_trades(path)?,
+        None => Vec::new(),
+    };
+    let service: TradeHandlerService = TradeHandlerService::new(config)
+        .with_initial_trades(seed_trades);
+    telemetry::init(MetricsLayer::new(service.metrics.clone()));
+    tokio::spawn(service.clone().run_expiry_sweeper());
+
+    let listener = tokio::net::TcpListener::bind(metrics_address).await?;
+    tracing::info!(%metrics_address, "Metrics listening.");
+    tokio::spawn(axum::serve(listener, metrics::router(service.clone())).into_future());
+
+    let listener = tokio::net::TcpListener::bind(address).await?;
+    tracing::info!(%address, "TradeHandlerServer listening.");
+
+    // There's no persistent history backend yet, so nothing to flush once drained.
+    serve(service, interceptor, listener, InFlight::default(), shutdown::signal()).await?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use auth::AuthenticatedUser;
+    use breaker::{ BreakerConfig, BreakerPolicy, SinkError };
+    use config::FieldLimits;
+    use ids::SequentialUuids;
+    use iso_currency::Currency;
+    use library::{
+        clock::FixedClock,
+        error::{ ErrorClass, RETRYABLE_KEY, UnauthorisedCause },
+        policy::ApproverRegistry,
+        trade::{ Counterparty, CounterpartyConfirmation, Direction, Style },
+        users::Requester,
+    };
+    use tokio_stream::StreamExt;
+    use tonic::Code;
+
+    use super::*;
+
+    /// Wraps the message as if it had passed the `AuthInterceptor`.
+    pub(crate) fn authenticated<T>(message: T, user_id: &str) -> tonic::Request<T> {
+        let mut request: tonic::Request<T> = tonic::Request::new(message);
+        request.extensions_mut().insert(AuthenticatedUser(user_id.to_string()));
+        request
+    }
+
+    pub(crate) fn mock_submit_request(user_id: &str) -> proto::TradeSubmitRequest {
+        let value_date: DateTime<Utc> = Utc::now() + Duration::from_secs(60);
+        let delivery_date: DateTime<Utc> = value_date + Duration::from_secs(60);
+        proto::TradeSubmitRequest {
+            info: Some(proto::Username {
+                user_id: user_id.to_string(),
+                role: proto::Role::Requester as i32,
+            }),
+            details: Some(proto::MutableTradeDetails {
+                counterparty: "TestCounterParty".to_string(),
+                direction: 0,
+                style: "Some Style".to_string(),
+                currency_code: Currency::GBP.numeric() as u32,
+                currency_amount: "100".to_string(),
+                underlying_currency_codes: vec![
+                    Currency::GBP.numeric() as u32,
+                    Currency::EUR.numeric() as u32
+                ],
+                value_date: value_date.to_rfc3339(),
+                delivery_date: delivery_date.to_rfc3339(),
+            }),
+            approvals_required: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn fractional_amount_round_trip() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let mut request: proto::TradeSubmitRequest = mock_submit_request("TestUser");
+        request.details.as_mut().unwrap().currency_amount = "1000000.50".to_string();
+
+        let uuid: TradeUuid = service
+            .submit(authenticated(request, "TestUser")).await
+            .unwrap()
+            .into_inner()
+            .uuid.unwrap();
+        let response: proto::TradeStatusResponse = service
+            .status(tonic::Request::new(proto::TradeStatusRequest { uuid: Some(uuid) })).await
+            .unwrap()
+            .into_inner();
+        let details: proto::TradeDetails = response.details.unwrap();
+        assert_eq!(details.subdetails.unwrap().currency_amount, "1000000.50");
+        assert_eq!(details.strike, "");
+    }
+
+    /// Submits the details and reads them straight back with `status`, asserting
+    /// every field is as sent, other than the dates being normalised to UTC.
+    async fn assert_round_trip(service: &TradeHandlerService, sent: proto::MutableTradeDetails) {
+        let mut request: proto::TradeSubmitRequest = mock_submit_request("TestUser");
+        request.details = Some(sent.clone());
+        let uuid: TradeUuid = service
+            .submit(authenticated(request, "TestUser")).await
+            .unwrap()
+            .into_inner()
+            .uuid.unwrap();
+        let details: proto::TradeDetails = service
+            .status(tonic::Request::new(proto::TradeStatusRequest { uuid: Some(uuid) })).await
+            .unwrap()
+            .into_inner()
+            .details.unwrap();
+
+        let normalise = |date: &str| -> String {
+            date.parse::<DateTime<Utc>>().unwrap().to_rfc3339()
+        };
+        let expected: proto::MutableTradeDetails = proto::MutableTradeDetails {
+            value_date: normalise(&sent.value_date),
+            delivery_date: normalise(&sent.delivery_date),
+            ..sent
+        };
+        assert_eq!(details.subdetails.unwrap(), expected);
+        assert_eq!(details.trading_entity.unwrap().user_id, "TestUser");
+        assert_eq!(details.strike, "");
+    }
+
+    #[tokio::test]
+    async fn submitted_details_read_back() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let sent: proto::MutableTradeDetails = mock_submit_request("TestUser").details.unwrap();
+        assert_round_trip(&service, sent.clone()).await;
+
+        // Amounts keep their scale, and the underlying its order.
+        assert_round_trip(&service, proto::MutableTradeDetails {
+            direction: 1,
+            style: "Forward".to_string(),
+            currency_amount: "0.10".to_string(),
+            underlying_currency_codes: vec![
+                Currency::EUR.numeric() as u32,
+                Currency::GBP.numeric() as u32
+            ],
+            ..sent.clone()
+        }).await;
+
+        // Dates given in another offset are read back as the same instant in UTC.
+        let value_date: DateTime<Utc> = Utc::now() + Duration::from_secs(3600);
+        let offset: chrono::FixedOffset = chrono::FixedOffset::east_opt(3600).unwrap();
+        assert_round_trip(&service, proto::MutableTradeDetails {
+            value_date: value_date.with_timezone(&offset).to_rfc3339(),
+            delivery_date: (value_date + Duration::from_secs(60)).to_rfc3339(),
+            ..sent
+        }).await;
+    }
+
+    #[tokio::test]
+    async fn field_limits() {
+        let service: TradeHandlerService = TradeHandlerService::new(ServerConfig {
+            limits: FieldLimits {
+                max_counterparty_length: 16,
+                max_style_length: 10,
+                max_underlying: 2,
+            },
+            ..ServerConfig::default()
+        });
+        let oversized = |edit: fn(&mut proto::MutableTradeDetails)| {
+            let mut request: proto::TradeSubmitRequest = mock_submit_request("TestUser");
+            edit(request.details.as_mut().unwrap());
+            authenticated(request, "TestUser")
+        };
+
+        let response = service.submit(oversized(|details| {
+            details.counterparty = "C".repeat(17);
+        })).await;
+        assert_eq!(response.unwrap_err().code(), Code::InvalidArgument);
+
+        let response = service.submit(oversized(|details| {
+            details.style = "Forward Contract".to_string();
+        })).await;
+        assert_eq!(response.unwrap_err().code(), Code::InvalidArgument);
+
+        let response = service.submit(oversized(|details| {
+            details.underlying_currency_codes.push(Currency::USD.numeric() as u32);
+        })).await;
+        let status: Status = response.unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(status.message(), "Underlying of 3 currencies exceeds the limit of 2.");
+
+        // Exactly at every limit is fine.
+        let response = service.submit(oversized(|details| {
+            details.counterparty = "C".repeat(16);
+            details.style = "Some Style".to_string();
+        })).await;
+        assert!(response.is_ok());
+        assert_eq!(service.trades.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn tenor_in_status() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let mut request: proto::TradeSubmitRequest = mock_submit_request("TestUser");
+        let now: DateTime<Utc> = Utc::now();
+        let raw_details: &mut proto::MutableTradeDetails = request.details.as_mut().unwrap();
+        raw_details.value_date = (now + chrono::TimeDelta::days(2)).to_rfc3339();
+        raw_details.delivery_date = (now + chrono::TimeDelta::days(366)).to_rfc3339();
+
+        let uuid: TradeUuid = service
+            .submit(authenticated(request, "TestUser")).await
+            .unwrap()
+            .into_inner()
+            .uuid.unwrap();
+        let response: proto::TradeStatusResponse = service
+            .status(tonic::Request::new(proto::TradeStatusRequest { uuid: Some(uuid) })).await
+            .unwrap()
+            .into_inner();
+        let details: proto::TradeDetails = response.details.unwrap();
+        // Submitted a moment after `now`, so just short of the whole days.
+        assert_eq!(details.tenor_days, 365);
+        assert_eq!(details.value_tenor_days, 1);
+    }
+
+    #[tokio::test]
+    async fn malformed_amounts_rejected() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        for amount in ["", "abc", "1.5e3", "0", "-10", "100.123"] {
+            let mut request: proto::TradeSubmitRequest = mock_submit_request("TestUser");
+            request.details.as_mut().unwrap().currency_amount = amount.to_string();
+            let response = service.submit(authenticated(request, "TestUser")).await;
+            assert_eq!(response.unwrap_err().code(), Code::InvalidArgument);
+        }
+    }
+
+    #[tokio::test]
+    async fn impersonation_rejected() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let response = service.submit(
+            authenticated(mock_submit_request("Victim"), "Attacker")
+        ).await;
+        assert_eq!(response.unwrap_err().code(), Code::Unauthenticated);
+
+        let response = service.submit(tonic::Request::new(mock_submit_request("Victim"))).await;
+        assert_eq!(response.unwrap_err().code(), Code::Unauthenticated);
+        assert!(service.trades.is_empty());
+    }
+
+    #[test]
+    fn described_workflow() {
+        let workflow: proto::DescribeWorkflowResponse = describe_workflow();
+        assert_eq!(workflow.states.len(), STATES.len() - 1);
+        assert!(
+            workflow.states
+                .iter()
+                .flat_map(|state: &proto::WorkflowState| &state.transitions)
+                .all(|transition: &proto::WorkflowTransition| {
+                    transition.to != Rejected::ID as i32
+                })
+        );
+
+        let pending: &proto::WorkflowState = workflow.states
+            .iter()
+            .find(|state: &&proto::WorkflowState| state.status == PendingApproval::ID as i32)
+            .unwrap();
+        assert_eq!(pending.name, PendingApproval::NAME);
+
+        let authority_of = |action: TradeAction| {
+            pending.transitions
+                .iter()
+                .find(|transition: &&proto::WorkflowTransition| {
+                    transition.action == action.to_string()
+                })
+                .map(|transition: &proto::WorkflowTransition| transition.authority)
+        };
+        assert_eq!(authority_of(TradeAction::Accept), Some(proto::Authority::Approver as i32));
+        assert_eq!(authority_of(TradeAction::Update), Some(proto::Authority::Approver as i32));
+        assert_eq!(authority_of(TradeAction::Cancel), Some(proto::Authority::Any as i32));
+        assert_eq!(authority_of(TradeAction::Book), None);
+    }
+
+    pub(crate) fn action_request(
+        uuid: &Uuid,
+        user_id: &str,
+        role: proto::Role
+    ) -> tonic::Request<proto::TradeActionRequest> {
+        authenticated(
+            proto::TradeActionRequest {
+                info: Some(proto::Username { user_id: user_id.to_string(), role: role as i32 }),
+                uuid: Some(TradeUuid { uuid: uuid.to_string() }),
+                note: String::new(),
+            },
+            user_id
+        )
+    }
+
+    /// Submits a trade for the user over the handler, returning its UUID.
+    pub(crate) async fn submit_trade(service: &TradeHandlerService, user_id: &str) -> Uuid {
+        let response: proto::TradeSubmitResponse = service
+            .submit(authenticated(mock_submit_request(user_id), user_id)).await
+            .unwrap()
+            .into_inner();
+        Uuid::from_str(&response.uuid.unwrap().uuid).unwrap()
+    }
+
+    /// A draft of the trade `mock_submit_request` describes, for placing a trade
+    /// directly into the store in whichever state a test needs.
+    pub(crate) fn mock_draft(requester: &User<Requester>) -> TradeDetails<Draft> {
+        TradeDetails::<Draft>
+            ::new(
+                requester,
+                Counterparty::new("TestCounterParty"),
+                Direction::BUY,
+                Style::Other("Some Style".to_string()),
+                Currency::GBP,
+                100,
+                vec![Currency::GBP, Currency::EUR],
+                Utc::now() + Duration::from_secs(60),
+                Utc::now() + Duration::from_secs(120)
+            )
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn batch_status_per_item() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let uuid: Uuid = submit_trade(&service, "TestUser").await;
+        let uuids: Vec<TradeUuid> = [uuid.to_string(), Uuid::new_v4().to_string(), "bad".into()]
+            .into_iter()
+            .map(|uuid: String| TradeUuid { uuid })
+            .collect();
+
+        let response: proto::BatchStatusResponse = service
+            .batch_status(tonic::Request::new(proto::BatchStatusRequest { uuids })).await
+            .unwrap()
+            .into_inner();
+        let results: Vec<proto::batch_status_result::Result> = response.results
+            .into_iter()
+            .map(|result: proto::BatchStatusResult| result.result.unwrap())
+            .collect();
+        let [known, unknown, malformed] = results.as_slice() else {
+            panic!("Expected a result per UUID.");
+        };
+        assert!(
+            matches!(known, proto::batch_status_result::Result::Status(status)
+                if status.status == PendingApproval::ID as i32)
+        );
+        assert!(
+            matches!(unknown, proto::batch_status_result::Result::Error(error)
+                if error.code == Code::NotFound as i32)
+        );
+        assert!(
+            matches!(malformed, proto::batch_status_result::Result::Error(error)
+                if error.code == Code::InvalidArgument as i32)
+        );
+    }
+
+    fn cancel_request(
+        uuid: &Uuid,
+        user_id: &str,
+        role: proto::Role,
+        reason: &str
+    ) -> tonic::Request<proto::TradeCancelRequest> {
+        authenticated(
+            proto::TradeCancelRequest {
+                info: Some(proto::Username { user_id: user_id.to_string(), role: role as i32 }),
+                uuid: Some(TradeUuid { uuid: uuid.to_string() }),
+                reason: reason.to_string(),
+            },
+            user_id
+        )
+    }
+
+    #[tokio::test]
+    async fn cancel_then_history() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let uuid: Uuid = submit_trade(&service, "TestUser").await;
+
+        let response = service.cancel(
+            cancel_request(&uuid, "Admin", proto::Role::Approver, " ")
+        ).await;
+        assert_eq!(response.unwrap_err().code(), Code::InvalidArgument);
+
+        let response = service.cancel(
+            cancel_request(&uuid, "Other", proto::Role::Requester, "Not mine")
+        ).await;
+        assert_eq!(response.unwrap_err().code(), Code::Unauthenticated);
+
+        let response = service.cancel(
+            cancel_request(&uuid, "TestUser", proto::Role::Requester, "Entered twice")
+        ).await;
+        assert_eq!(response.unwrap().into_inner().status, Cancelled::ID as i32);
+
+        let history: proto::TradeHistoryResponse = service
+            .history(
+                tonic::Request::new(proto::TradeStatusRequest {
+                    uuid: Some(TradeUuid { uuid: uuid.to_string() }),
+                })
+            ).await
+            .unwrap()
+            .into_inner();
+        let actions: Vec<&str> = history.records
+            .iter()
+            .map(|record: &proto::HistoricalRecord| record.action.as_str())
+            .collect();
+        assert_eq!(actions, vec!["submit", "cancel"]);
+        let terminal: &proto::HistoricalRecord = history.records.last().unwrap();
+        assert_eq!(terminal.state_after, Cancelled::NAME);
+        assert_eq!(terminal.action_id, proto::Action::Cancel as i32);
+        assert_eq!(terminal.user_id, "TestUser");
+        assert_eq!(terminal.note, "Entered twice");
+    }
+
+    #[tokio::test]
+    async fn batch_submit_partially() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let mut bad_currency: proto::TradeSubmitRequest = mock_submit_request("TestUser");
+        bad_currency.details.as_mut().unwrap().currency_code = 1;
+        let entries: Vec<proto::TradeSubmitRequest> = vec![
+            mock_submit_request("TestUser"),
+            bad_currency,
+            mock_submit_request("TestUser")
+        ];
+
+        let response: proto::BatchSubmitResponse = service
+            .batch_submit(authenticated(proto::BatchSubmitRequest { entries }, "TestUser")).await
+            .unwrap()
+            .into_inner();
+        let results: Vec<proto::batch_submit_result::Result> = response.results
+            .into_iter()
+            .map(|result: proto::BatchSubmitResult| result.result.unwrap())
+            .collect();
+        let [
+            proto::batch_submit_result::Result::Uuid(first),
+            proto::batch_submit_result::Result::Error(error),
+            proto::batch_submit_result::Result::Uuid(second),
+        ] = results.as_slice() else {
+            panic!("Expected only the bad currency to fail.");
+        };
+        assert_eq!(error.code, Code::InvalidArgument as i32);
+
+        assert_eq!(service.trades.len(), 2);
+        assert!(service.trades.contains(&Uuid::from_str(&first.uuid).unwrap()));
+        assert!(service.trades.contains(&Uuid::from_str(&second.uuid).unwrap()));
+    }
+
+    #[tokio::test]
+    async fn seeded_uuids() {
+        let service: TradeHandlerService = TradeHandlerService {
+            uuids: Arc::new(SequentialUuids::starting_at(7)),
+            ..TradeHandlerService::default()
+        };
+        let first: Uuid = submit_trade(&service, "TestUser").await;
+        let second: Uuid = submit_trade(&service, "TestUser").await;
+        assert_eq!(first, Uuid::from_u128(7));
+        assert_eq!(second, Uuid::from_u128(8));
+        assert_eq!(first.to_string(), "00000000-0000-0000-0000-000000000007");
+    }
+
+    #[tokio::test]
+    async fn colliding_uuids_unrecorded() {
+        let seed: u64 = Uuid::new_v4().as_u64_pair().0 >> 1;
+        let service: TradeHandlerService = TradeHandlerService {
+            uuids: Arc::new(SequentialUuids::starting_at(seed)),
+            ..TradeHandlerService::default()
+        };
+        let taken: Uuid = submit_trade(&service, "TestUser").await;
+        let colliding = || -> TradeHandlerService {
+            TradeHandlerService {
+                uuids: Arc::new(SequentialUuids::starting_at(seed)),
+                ..service.clone()
+            }
+        };
+        let records = || -> usize { HISTORY.lock().unwrap().records_for(taken).len() };
+        assert_eq!(records(), 1);
+
+        // Neither submission records a transition for the trade already stored.
+        let status: Status = colliding()
+            .submit(authenticated(mock_submit_request("TestUser"), "TestUser")).await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::AlreadyExists);
+        let entries: Vec<proto::TradeSubmitRequest> = vec![mock_submit_request("TestUser")];
+        let response: proto::BatchSubmitResponse = colliding()
+            .batch_submit(authenticated(proto::BatchSubmitRequest { entries }, "TestUser")).await
+            .unwrap()
+            .into_inner();
+        let Some(proto::batch_submit_result::Result::Error(error)) = &response.results[0].result
+        else {
+            panic!("Expected the colliding submission to fail.");
+        };
+        assert_eq!(error.code, Code::AlreadyExists as i32);
+        assert_eq!(records(), 1);
+        assert_eq!(service.trades.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn summarising_by_state() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let pending: Uuid = submit_trade(&service, "TestUser").await;
+        let approved: Uuid = submit_trade(&service, "TestUser").await;
+        let cancelled: Uuid = submit_trade(&service, "TestUser").await;
+        service.accept(action_request(&approved, "Admin", proto::Role::Approver)).await.unwrap();
+        service
+            .cancel(cancel_request(&cancelled, "Admin", proto::Role::Approver, "Duplicate")).await
+            .unwrap();
+
+        let counts: HashMap<u32, u64> = service
+            .summary(tonic::Request::new(proto::SummaryRequest {})).await
+            .unwrap()
+            .into_inner().counts;
+        assert_eq!(counts.len(), STATES.len() - 1);
+        assert!(!counts.contains_key(&(Rejected::ID as u32)));
+        assert_eq!(counts[&(PendingApproval::ID as u32)], 1);
+        assert_eq!(counts[&(Approved::ID as u32)], 1);
+        assert_eq!(counts[&(Cancelled::ID as u32)], 1);
+        assert_eq!(counts[&(Executed::ID as u32)], 0);
+        assert_eq!(counts.values().sum::<u64>(), 3);
+        assert!(service.trades.contains(&pending));
+    }
+
+    #[tokio::test]
+    async fn validating_without_storing() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let response: proto::TradeValidateResponse = service
+            .validate(authenticated(mock_submit_request("TestUser"), "TestUser")).await
+            .unwrap()
+            .into_inner();
+        assert!(response.valid);
+        assert!(response.problems.is_empty());
+
+        let mut request: proto::TradeSubmitRequest = mock_submit_request("TestUser");
+        let details: &mut proto::MutableTradeDetails = request.details.as_mut().unwrap();
+        std::mem::swap(&mut details.value_date, &mut details.delivery_date);
+        let response: proto::TradeValidateResponse = service
+            .validate(authenticated(request, "TestUser")).await
+            .unwrap()
+            .into_inner();
+        assert!(!response.valid);
+        assert_eq!(response.problems.len(), 1);
+        assert!(response.problems[0].contains("Dates must be chronologically ordered"));
+
+        // Every problem is listed, not only the first.
+        let mut request: proto::TradeSubmitRequest = mock_submit_request("TestUser");
+        let details: &mut proto::MutableTradeDetails = request.details.as_mut().unwrap();
+        std::mem::swap(&mut details.value_date, &mut details.delivery_date);
+        details.currency_amount = "-100".to_string();
+        let response: proto::TradeValidateResponse = service
+            .validate(authenticated(request, "TestUser")).await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.problems, vec![
+            "Dates must be chronologically ordered",
+            "Notional amount must be positive"
+        ]);
+
+        let mut request: proto::TradeSubmitRequest = mock_submit_request("TestUser");
+        let details: &mut proto::MutableTradeDetails = request.details.as_mut().unwrap();
+        details.currency_amount = "lots".to_string();
+        details.value_date = "tomorrow".to_string();
+        let response: proto::TradeValidateResponse = service
+            .validate(authenticated(request, "TestUser")).await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.problems, vec![
+            "Currency amount isn't a decimal number.",
+            "Value Date doesn't follow the UTC standard."
+        ]);
+
+        assert!(service.trades.is_empty());
+    }
+
+    #[test]
+    fn parsing_trade_uuids() {
+        let empty: Status = parse_trade_uuid("  ").unwrap_err();
+        assert_eq!(empty.code(), Code::InvalidArgument);
+        assert_eq!(empty.message(), "UUID is empty.");
+
+        let malformed: Status = parse_trade_uuid("not-a-uuid").unwrap_err();
+        assert_eq!(malformed.code(), Code::InvalidArgument);
+        assert!(malformed.message().starts_with("Invalid UUID \"not-a-uuid\""));
+
+        let uuid: Uuid = Uuid::new_v4();
+        assert_eq!(parse_trade_uuid(&uuid.to_string()).unwrap(), uuid);
+    }
+
+    #[tokio::test]
+    async fn terminal_trades_rejected() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let uuid: Uuid = submit_trade(&service, "TestUser").await;
+        service.sweep_expired(Utc::now() + chrono::TimeDelta::days(365)).await;
+
+        let response = service.accept(action_request(&uuid, "Admin", proto::Role::Approver)).await;
+        let status: Status = response.unwrap_err();
+        assert_eq!(status.code(), Code::FailedPrecondition);
+        assert_eq!(status.message(), "Trade is Cancelled, so can't transition.");
+
+        let response = service.revert(action_request(&uuid, "Admin", proto::Role::Approver)).await;
+        assert_eq!(response.unwrap_err().message(), status.message());
+    }
+
+    #[tokio::test]
+    async fn cancelling_an_executed_trade() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let uuid: Uuid = submit_trade(&service, "TestUser").await;
+        service.accept(action_request(&uuid, "Admin", proto::Role::Approver)).await.unwrap();
+
+        // There's no RPC to execute, so booking the trade directly in the store.
+        {
+            let approver: User<Approver> = User::sign_in("Admin");
+            let mut composed = service.trades.lock(&uuid).await.unwrap();
+            let executed: TradeDetails<Executed> = composed.approved
+                .take()
+                .unwrap()
+                .send_to_execute(&approver, None)
+                .book(1000, Some(&CounterpartyConfirmation::new("CP-REF-1")), &approver, None)
+                .unwrap();
+            composed.executed = Some(executed);
+        }
+
+        let response = service.cancel(
+            cancel_request(&uuid, "Admin", proto::Role::Approver, "Too late")
+        ).await;
+        let status: Status = response.unwrap_err();
+        assert_eq!(status.code(), Code::FailedPrecondition);
+        assert_eq!(status.message(), "Trade is Executed, so can't transition.");
+    }
+
+    #[tokio::test]
+    async fn settlement_in_status() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let uuid: Uuid = submit_trade(&service, "TestUser").await;
+        let pending: Uuid = submit_trade(&service, "TestUser").await;
+        service.accept(action_request(&uuid, "Admin", proto::Role::Approver)).await.unwrap();
+
+        // There's no RPC to execute, so booking the trade directly in the store.
+        {
+            let approver: User<Approver> = User::sign_in("Admin");
+            let mut composed = service.trades.lock(&uuid).await.unwrap();
+            let executed: TradeDetails<Executed> = composed.approved
+                .take()
+                .unwrap()
+                .send_to_execute(&approver, None)
+                .book(1000, Some(&CounterpartyConfirmation::new("CP-REF-1")), &approver, None)
+                .unwrap();
+            composed.executed = Some(executed);
+        }
+
+        let status = |uuid: Uuid| -> tonic::Request<proto::TradeStatusRequest> {
+            tonic::Request::new(proto::TradeStatusRequest {
+                uuid: Some(TradeUuid { uuid: uuid.to_string() }),
+            })
+        };
+
+        // Only executed trades have their settlement, here 100 GBP bought at 1000.
+        let response: proto::TradeStatusResponse = service
+            .status(status(uuid)).await
+            .unwrap()
+            .into_inner();
+        let settlement: proto::SettlementDetails = response.settlement.unwrap();
+        assert_eq!(settlement.settlement_currency_code, Currency::EUR.numeric() as u32);
+        assert_eq!(settlement.net_amount, "-100000");
+
+        let response: proto::TradeStatusResponse = service
+            .status(status(pending)).await
+            .unwrap()
+            .into_inner();
+        assert!(response.settlement.is_none());
+    }
+
+    #[tokio::test]
+    async fn approving_a_cancelled_trade() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let uuid: Uuid = submit_trade(&service, "TestUser").await;
+        service
+            .cancel(cancel_request(&uuid, "TestUser", proto::Role::Requester, "Withdrawn")).await
+            .unwrap();
+
+        let response = service.approve(action_request(&uuid, "TestUser", proto::Role::Requester)).await;
+        let status: Status = response.unwrap_err();
+        assert_eq!(status.code(), Code::FailedPrecondition);
+        assert_eq!(status.message(), "Trade is Cancelled, so can't transition.");
+    }
+
+    #[tokio::test]
+    async fn accept_requires_approver_role() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let uuid: Uuid = submit_trade(&service, "TestUser").await;
+
+        // Each refusal says why, so the client can tell the user what to change.
+        let response = service.accept(action_request(&uuid, "TestUser", proto::Role::Requester)).await;
+        let wrong_role: Status = response.unwrap_err();
+        assert_eq!(wrong_role.code(), Code::PermissionDenied);
+        assert!(wrong_role.message().ends_with(&format!("{}.", UnauthorisedCause::WrongRole)));
+
+        // Nor may the requester sign off their own trade as an approver.
+        let response = service.accept(action_request(&uuid, "TestUser", proto::Role::Approver)).await;
+        let self_approval: Status = response.unwrap_err();
+        assert_eq!(self_approval.code(), Code::PermissionDenied);
+        let self_approval_cause: String = format!("{}.", UnauthorisedCause::SelfApproval);
+        assert!(self_approval.message().ends_with(&self_approval_cause));
+        assert_ne!(wrong_role.message(), self_approval.message());
+
+        let response = service.accept(action_request(&uuid, "Admin", proto::Role::Approver)).await;
+        assert_eq!(response.unwrap().into_inner().status, Approved::ID as i32);
+    }
+
+    #[tokio::test]
+    async fn accepting_with_several_approvals() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let mut request: proto::TradeSubmitRequest = mock_submit_request("TestUser");
+        request.approvals_required = Some(2);
+        let uuid: TradeUuid = service
+            .submit(authenticated(request, "TestUser")).await
+            .unwrap()
+            .into_inner()
+            .uuid.unwrap();
+        let uuid: Uuid = Uuid::from_str(&uuid.uuid).unwrap();
+
+        let response = service.accept(action_request(&uuid, "Admin", proto::Role::Approver)).await;
+        assert_eq!(response.unwrap().into_inner().status, PartiallyApproved::ID as i32);
+        let response = service.accept(action_request(&uuid, "Admin", proto::Role::Approver)).await;
+        assert_eq!(response.unwrap_err().code(), Code::AlreadyExists);
+
+        let response = service.accept(
+            action_request(&uuid, "SecondAdmin", proto::Role::Approver)
+        ).await;
+        assert_eq!(response.unwrap().into_inner().status, Approved::ID as i32);
+
+        // Requiring no approvals at all is rejected outright.
+        let mut request: proto::TradeSubmitRequest = mock_submit_request("TestUser");
+        request.approvals_required = Some(0);
+        let status: Status = service.submit(authenticated(request, "TestUser")).await.unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(service.trades.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn only_registered_approvers() {
+        let service: TradeHandlerService = TradeHandlerService::new(ServerConfig {
+            approvers: Some(ApproverRegistry::new(["Admin"])),
+            ..ServerConfig::default()
+        });
+        let uuid: Uuid = submit_trade(&service, "TestUser").await;
+
+        let response = service.accept(action_request(&uuid, "Intruder", proto::Role::Approver)).await;
+        let status: Status = response.unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+        assert_eq!(status.message(), "Approver Intruder isn't registered.");
+
+        // Requesters aren't checked against the registry.
+        assert!(service.submit(authenticated(mock_submit_request("Other"), "Other")).await.is_ok());
+
+        let response = service.accept(action_request(&uuid, "Admin", proto::Role::Approver)).await;
+        assert_eq!(response.unwrap().into_inner().status, Approved::ID as i32);
+    }
+
+    #[tokio::test]
+    async fn oversized_note_rejected() {
+        let service: TradeHandlerService = TradeHandlerService::new(ServerConfig {
+            max_note_length: 10,
+            ..ServerConfig::default()
+        });
+        let uuid: Uuid = submit_trade(&service, "TestUser").await;
+
+        let mut request = action_request(&uuid, "Admin", proto::Role::Approver);
+        request.get_mut().note = "Far too long to be accepted".to_string();
+        let response = service.accept(request).await;
+        assert_eq!(response.unwrap_err().code(), Code::InvalidArgument);
+        assert!(service.trades.lock(&uuid).await.unwrap().pending_approval.is_some());
+
+        let mut request = action_request(&uuid, "Admin", proto::Role::Approver);
+        request.get_mut().note = "Fine".to_string();
+        let response = service.accept(request).await;
+        assert_eq!(response.unwrap().into_inner().status, Approved::ID as i32);
+        let record: HistoricalRecord = HISTORY.lock().unwrap().last_reversible(uuid).unwrap();
+        assert_eq!(record.note(), Some("Fine"));
+    }
+
+    #[tokio::test]
+    async fn approve_requires_requester_role() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+
+        // Placing a trade which needs reapproval directly into the map.
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let approver: User<Approver> = User::sign_in("Admin");
+        let details: TradeDetails<PendingApproval> = mock_draft(&requester)
+            .submit(&requester, None)
+            .unwrap();
+        let mut new_details: MutTradeDetails = details.grab_mut_details();
+        new_details.direction = Direction::SELL;
+        let details: TradeDetails<NeedsReapproval> = details
+            .update(&approver, new_details, None)
+            .unwrap();
+        let uuid: Uuid = *details.id();
+        service.trades.insert(uuid, ComposedTradeDetails {
+            needs_reapproval: Some(details),
+            ..ComposedTradeDetails::default()
+        });
+
+        let response = service.approve(action_request(&uuid, "Admin", proto::Role::Approver)).await;
+        assert_eq!(response.unwrap_err().code(), Code::PermissionDenied);
+
+        let response = service.approve(action_request(&uuid, "Other", proto::Role::Requester)).await;
+        assert_eq!(response.unwrap_err().code(), Code::Unauthenticated);
+        assert!(service.trades.lock(&uuid).await.unwrap().needs_reapproval.is_some());
+
+        let response = service.approve(action_request(&uuid, "TestUser", proto::Role::Requester)).await;
+        assert_eq!(response.unwrap().into_inner().status, Approved::ID as i32);
+    }
+
+    #[tokio::test]
+    async fn subscribed_to_transitions() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let uuid: Uuid = submit_trade(&service, "TestUser").await;
+
+        let mut stream = service
+            .subscribe(
+                tonic::Request::new(proto::TradeStatusRequest {
+                    uuid: Some(TradeUuid { uuid: uuid.to_string() }),
+                })
+            ).await
+            .unwrap()
+            .into_inner();
+
+        let accepting: TradeHandlerService = service.clone();
+        tokio::spawn(async move {
+            accepting
+                .accept(action_request(&uuid, "Admin", proto::Role::Approver)).await
+                .unwrap();
+        });
+
+        let update: proto::TradeStatusResponse = stream.next().await.unwrap().unwrap();
+        assert_eq!(update.status, Approved::ID as i32);
+
+        let response = service.subscribe(
+            tonic::Request::new(proto::TradeStatusRequest {
+                uuid: Some(TradeUuid { uuid: Uuid::new_v4().to_string() }),
+            })
+        ).await;
+        assert_eq!(response.err().unwrap().code(), Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn responses_echo_uuid() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let uuid: Uuid = submit_trade(&service, "TestUser").await;
+        let requested: TradeUuid = TradeUuid { uuid: uuid.to_string() };
+        let status_request = || -> tonic::Request<proto::TradeStatusRequest> {
+            tonic::Request::new(proto::TradeStatusRequest { uuid: Some(requested.clone()) })
+        };
+
+        let response: proto::TradeStatusResponse = service
+            .status(status_request()).await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.uuid, Some(requested.clone()));
+
+        let response: proto::BatchStatusResponse = service
+            .batch_status(
+                tonic::Request::new(proto::BatchStatusRequest { uuids: vec![requested.clone()] })
+            ).await
+            .unwrap()
+            .into_inner();
+        let Some(proto::batch_status_result::Result::Status(status)) = &response.results[0].result
+        else {
+            panic!("Expected the trade's status.");
+        };
+        assert_eq!(status.uuid, Some(requested.clone()));
+
+        let mut stream = service
+            .subscribe(status_request()).await
+            .unwrap()
+            .into_inner();
+        let accepted: proto::TradeStatusResponse = service
+            .accept(action_request(&uuid, "Admin", proto::Role::Approver)).await
+            .unwrap()
+            .into_inner();
+        assert_eq!(accepted.uuid, Some(requested.clone()));
+        let update: proto::TradeStatusResponse = stream.next().await.unwrap().unwrap();
+        assert_eq!(update.uuid, Some(requested));
+    }
+
+    #[tokio::test]
+    async fn reverting_an_accept() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let uuid: Uuid = submit_trade(&service, "TestUser").await;
+
+        // Submissions have no prior state.
+        let response = service.revert(action_request(&uuid, "Admin", proto::Role::Approver)).await;
+        assert_eq!(response.unwrap_err().code(), Code::FailedPrecondition);
+
+        service.accept(action_request(&uuid, "Admin", proto::Role::Approver)).await.unwrap();
+        let response = service.revert(action_request(&uuid, "Admin", proto::Role::Approver)).await;
+        assert_eq!(response.unwrap().into_inner().status, PendingApproval::ID as i32);
+        assert!(service.trades.lock(&uuid).await.unwrap().pending_approval.is_some());
+
+        // Only the latest transition may be reverted, once.
+        let response = service.revert(action_request(&uuid, "Admin", proto::Role::Approver)).await;
+        assert_eq!(response.unwrap_err().code(), Code::FailedPrecondition);
+    }
+
+    #[tokio::test]
+    async fn reverting_sign_offs() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let mut request: proto::TradeSubmitRequest = mock_submit_request("TestUser");
+        request.approvals_required = Some(2);
+        let uuid: TradeUuid = service
+            .submit(authenticated(request, "TestUser")).await
+            .unwrap()
+            .into_inner()
+            .uuid.unwrap();
+        let uuid: Uuid = Uuid::from_str(&uuid.uuid).unwrap();
+        let accept = |approver: &str| action_request(&uuid, approver, proto::Role::Approver);
+        let revert = || action_request(&uuid, "Admin", proto::Role::Approver);
+
+        // A sign off short of the quorum is withdrawn, leaving it partially approved.
+        let response = service.accept(accept("Admin")).await;
+        assert_eq!(response.unwrap().into_inner().status, PartiallyApproved::ID as i32);
+        let response = service.revert(revert()).await;
+        assert_eq!(response.unwrap().into_inner().status, PartiallyApproved::ID as i32);
+        {
+            let composed = service.trades.lock(&uuid).await.unwrap();
+            assert!(composed.partially_approved.as_ref().unwrap().signed_off().is_empty());
+        }
+
+        // As is the sign off which met the quorum, unapproving the trade.
+        service.accept(accept("Admin")).await.unwrap();
+        let response = service.accept(accept("SecondAdmin")).await;
+        assert_eq!(response.unwrap().into_inner().status, Approved::ID as i32);
+        let response = service.revert(revert()).await;
+        assert_eq!(response.unwrap().into_inner().status, PartiallyApproved::ID as i32);
+        {
+            let composed = service.trades.lock(&uuid).await.unwrap();
+            assert!(composed.approved.is_none());
+            assert_eq!(composed.partially_approved.as_ref().unwrap().signed_off(), ["Admin"]);
+        }
+        let response = service.accept(accept("SecondAdmin")).await;
+        assert_eq!(response.unwrap().into_inner().status, Approved::ID as i32);
+    }
+
+    #[tokio::test]
+    async fn expired_trades_swept() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let live: Uuid = submit_trade(&service, "TestUser").await;
+
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let details: TradeDetails<PendingApproval> = mock_draft(&requester)
+            .expire_after(chrono::TimeDelta::zero())
+            .submit(&requester, None)
+            .unwrap();
+        let expired: Uuid = *details.id();
+        service.trades.insert(expired, ComposedTradeDetails {
+            pending_approval: Some(details),
+            ..ComposedTradeDetails::default()
+        });
+
+        assert_eq!(service.sweep_expired(Utc::now()).await, vec![expired]);
+        let composed = service.trades.lock(&expired).await.unwrap();
+        assert!(composed.cancelled.is_some());
+        assert!(composed.pending_approval.is_none());
+        assert!(service.trades.lock(&live).await.unwrap().pending_approval.is_some());
+    }
+
+    #[tokio::test]
+    async fn expired_reapprovals_swept() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let approver: User<Approver> = User::sign_in("Admin");
+        let pending: TradeDetails<PendingApproval> = mock_draft(&requester)
+            .expire_after(chrono::TimeDelta::zero())
+            .submit(&requester, None)
+            .unwrap();
+        let mut new_details: MutTradeDetails = pending.grab_mut_details();
+        new_details.direction = Direction::SELL;
+        let details: TradeDetails<NeedsReapproval> = pending
+            .update(&approver, new_details, None)
+            .unwrap();
+        let expired: Uuid = *details.id();
+        service.trades.insert(expired, ComposedTradeDetails {
+            needs_reapproval: Some(details),
+            ..ComposedTradeDetails::default()
+        });
+
+        // Awaiting reapproval hasn't progressed past approval either.
+        assert_eq!(service.sweep_expired(Utc::now()).await, vec![expired]);
+        let composed = service.trades.lock(&expired).await.unwrap();
+        assert!(composed.cancelled.is_some());
+        assert!(composed.needs_reapproval.is_none());
+    }
+
+    #[test]
+    fn loading_seed_trades() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let approver: User<Approver> = User::sign_in("Admin");
+        let pending: TradeDetails<PendingApproval> = mock_draft(&requester)
+            .submit(&requester, None)
+            .unwrap();
+        let approved: TradeDetails<Approved> = mock_draft(&requester)
+            .submit(&requester, None)
+            .unwrap()
+            .accept(&approver, None)
+            .unwrap();
+
+        let path: PathBuf = std::env::temp_dir().join(format!("seed-{}.json", Uuid::new_v4()));
+        let load = |rows: serde_json::Value| {
+            std::fs::write(&path, rows.to_string()).unwrap();
+            load_seed_trades(&path)
+        };
+        let trades: Vec<(Uuid, ComposedTradeDetails)> = load(
+            serde_json::json!([pending, approved])
+        ).unwrap();
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[0].0, *pending.id());
+        assert_eq!(trades[0].1.state_name(), Some(PendingApproval::NAME));
+        assert_eq!(trades[1].0, *approved.id());
+        assert_eq!(trades[1].1.approved.as_ref(), Some(&approved));
+
+        // A row in a state the server doesn't store fails the whole load.
+        let mut rejected: serde_json::Value = serde_json::to_value(&pending).unwrap();
+        rejected["state"] = serde_json::json!("Rejected");
+        let error = load(serde_json::json!([approved, rejected])).unwrap_err();
+        assert!(error.to_string().contains("\"Rejected\" trades can't be stored"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn seeded_trades() {
+        let requester: User<Requester> = User::sign_in("TestUser");
+        let approver: User<Approver> = User::sign_in("Admin");
+        let submitted = || -> TradeDetails<PendingApproval> {
+            mock_draft(&requester).submit(&requester, None).unwrap()
+        };
+        let pending: TradeDetails<PendingApproval> = submitted();
+        let approved: TradeDetails<Approved> = submitted().accept(&approver, None).unwrap();
+        let (pending_uuid, approved_uuid) = (*pending.id(), *approved.id());
+
+        let service: TradeHandlerService = TradeHandlerService::default().with_initial_trades(
+            vec![
+                (pending_uuid, ComposedTradeDetails {
+                    pending_approval: Some(pending),
+                    ..ComposedTradeDetails::default()
+                }),
+                (approved_uuid, ComposedTradeDetails {
+                    approved: Some(approved),
+                    ..ComposedTradeDetails::default()
+                })
+            ]
+        );
+        assert_eq!(service.trades.len(), 2);
+
+        let status = |uuid: Uuid| {
+            let uuid: TradeUuid = TradeUuid { uuid: uuid.to_string() };
+            service.status(tonic::Request::new(proto::TradeStatusRequest { uuid: Some(uuid) }))
+        };
+        let response: proto::TradeStatusResponse = status(pending_uuid).await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.status, PendingApproval::ID as i32);
+        let response: proto::TradeStatusResponse = status(approved_uuid).await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.status, Approved::ID as i32);
+        assert_eq!(response.details.unwrap().subdetails.unwrap().style, "Some Style");
+    }
+
+    #[tokio::test]
+    async fn stale_trades_listed() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let uuid: Uuid = submit_trade(&service, "TestUser").await;
+
+        // The same trades, as seen by a service whose clock is a month ahead.
+        let later: TradeHandlerService = TradeHandlerService {
+            clock: Arc::new(FixedClock(Utc::now() + chrono::TimeDelta::days(30))),
+            ..service.clone()
+        };
+        let stale = |service: &TradeHandlerService, older_than_days: u32| {
+            let request = proto::StaleTradesRequest { older_than_days };
+            service.stale_trades(tonic::Request::new(request))
+        };
+        assert!(stale(&service, 7).await.unwrap().into_inner().uuids.is_empty());
+        let response: proto::StaleTradesResponse = stale(&later, 7).await.unwrap().into_inner();
+        assert_eq!(response.uuids, vec![TradeUuid { uuid: uuid.to_string() }]);
+        assert!(stale(&later, 60).await.unwrap().into_inner().uuids.is_empty());
+    }
+
+    #[tokio::test]
+    async fn masked_update() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let uuid: Uuid = submit_trade(&service, "TestUser").await;
+        let before: proto::MutableTradeDetails = service.trades
+            .lock(&uuid).await
+            .unwrap()
+            .to_response()
+            .unwrap()
+            .details.unwrap()
+            .subdetails.unwrap();
+        let update_request = |field_mask: Vec<&str>| {
+            authenticated(
+                proto::TradeUpdateRequest {
+                    info: Some(proto::Username {
+                        user_id: "Admin".to_string(),
+                        role: proto::Role::Approver as i32,
+                    }),
+                    uuid: Some(TradeUuid { uuid: uuid.to_string() }),
+                    // Only the direction is set, every other field being empty.
+                    details: Some(proto::MutableTradeDetails {
+                        direction: 1,
+                        ..proto::MutableTradeDetails::default()
+                    }),
+                    field_mask: field_mask.into_iter().map(String::from).collect(),
+                    note: String::new(),
+                    expected_version: None,
+                },
+                "Admin"
+            )
+        };
+
+        let status: Status = service.update(update_request(vec!["direction", "colour"])).await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(status.message(), "Unknown field \"colour\" in the mask.");
+        assert!(service.trades.lock(&uuid).await.unwrap().pending_approval.is_some());
+
+        let response: proto::TradeStatusResponse = service
+            .update(update_request(vec!["direction"])).await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.status, NeedsReapproval::ID as i32);
+        let after: proto::MutableTradeDetails = response.details.unwrap().subdetails.unwrap();
+        assert_eq!(after, proto::MutableTradeDetails { direction: 1, ..before });
+
+        // Without a mask, the empty fields would replace the trade's own.
+        let other: Uuid = submit_trade(&service, "TestUser").await;
+        let mut request = update_request(vec![]);
+        request.get_mut().uuid = Some(TradeUuid { uuid: other.to_string() });
+        assert_eq!(service.update(request).await.unwrap_err().code(), Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn versioned_update() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let uuid: Uuid = submit_trade(&service, "TestUser").await;
+        let version: u64 = service.trades
+            .lock(&uuid).await
+            .unwrap()
+            .to_response()
+            .unwrap()
+            .details.unwrap()
+            .version;
+        let update_request = |expected_version: u64| {
+            authenticated(
+                proto::TradeUpdateRequest {
+                    info: Some(proto::Username {
+                        user_id: "Admin".to_string(),
+                        role: proto::Role::Approver as i32,
+                    }),
+                    uuid: Some(TradeUuid { uuid: uuid.to_string() }),
+                    details: Some(proto::MutableTradeDetails {
+                        direction: 1,
+                        ..proto::MutableTradeDetails::default()
+                    }),
+                    field_mask: vec!["direction".to_string()],
+                    note: String::new(),
+                    expected_version: Some(expected_version),
+                },
+                "Admin"
+            )
+        };
+
+        let response: proto::TradeStatusResponse = service
+            .update(update_request(version)).await
+            .unwrap()
+            .into_inner();
+        assert_ne!(response.details.unwrap().version, version);
+        service.approve(action_request(&uuid, "TestUser", proto::Role::Requester)).await.unwrap();
+
+        // Another approver's update, read at the first version, is now stale.
+        let status: Status = service.update(update_request(version)).await.unwrap_err();
+        assert_eq!(status.code(), Code::Aborted);
+        assert_eq!(status.metadata().get(RETRYABLE_KEY).unwrap(), "false");
+        assert!(service.trades.lock(&uuid).await.unwrap().approved.is_some());
+    }
+
+    #[tokio::test]
+    async fn cancel_all_for_counterparty() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let submit_with = |counterparty: &str| {
+            let mut request: proto::TradeSubmitRequest = mock_submit_request("TestUser");
+            request.details.as_mut().unwrap().counterparty = counterparty.to_string();
+            service.submit(authenticated(request, "TestUser"))
+        };
+        let mut defaulted: Vec<Uuid> = Vec::new();
+        for _ in 0..2 {
+            let response: proto::TradeSubmitResponse = submit_with("Defaulted").await
+                .unwrap()
+                .into_inner();
+            defaulted.push(Uuid::from_str(&response.uuid.unwrap().uuid).unwrap());
+        }
+        defaulted.sort();
+        let other: proto::TradeSubmitResponse = submit_with("Other").await.unwrap().into_inner();
+        let other: Uuid = Uuid::from_str(&other.uuid.unwrap().uuid).unwrap();
+
+        // Already terminal, so skipped rather than an error.
+        let executed: Uuid = Uuid::from_str(
+            &submit_with("Defaulted").await.unwrap().into_inner().uuid.unwrap().uuid
+        ).unwrap();
+        let mut composed = service.trades.lock(&executed).await.unwrap();
+        let approver: User<Approver> = User::sign_in("Admin");
+        let cancelled: TradeDetails<Cancelled> = composed.pending_approval
+            .take()
+            .unwrap()
+            .cancel_as_approver(&approver, None);
+        composed.cancelled = Some(cancelled);
+        drop(composed);
+
+        let request = |role: proto::Role| {
+            authenticated(
+                proto::CounterpartyCancelRequest {
+                    info: Some(proto::Username { user_id: "Admin".to_string(), role: role as i32 }),
+                    counterparty: "Defaulted".to_string(),
+                    reason: "Counterparty defaulted.".to_string(),
+                },
+                "Admin"
+            )
+        };
+        let response = service.cancel_all_for_counterparty(request(proto::Role::Requester)).await;
+        assert_eq!(response.unwrap_err().code(), Code::PermissionDenied);
+
+        let response: proto::CounterpartyCancelResponse = service
+            .cancel_all_for_counterparty(request(proto::Role::Approver)).await
+            .unwrap()
+            .into_inner();
+        let cancelled: Vec<Uuid> = response.uuids
+            .iter()
+            .map(|uuid: &TradeUuid| Uuid::from_str(&uuid.uuid).unwrap())
+            .collect();
+        assert_eq!(cancelled, defaulted);
+        for uuid in &defaulted {
+            assert!(service.trades.lock(uuid).await.unwrap().cancelled.is_some());
+        }
+        assert!(service.trades.lock(&other).await.unwrap().pending_approval.is_some());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn independent_trades_transition_concurrently() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let first: Uuid = submit_trade(&service, "TestUser").await;
+        let second: Uuid = submit_trade(&service, "TestUser").await;
+        let accept = |uuid: Uuid| {
+            let service: TradeHandlerService = service.clone();
+            tokio::spawn(async move {
+                service.accept(action_request(&uuid, "Admin", proto::Role::Approver)).await
+            })
+        };
+
+        // As if the first trade were in the middle of a slow transition.
+        let held = service.trades.lock(&first).await.unwrap();
+
+        let response: proto::TradeStatusResponse = tokio::time::timeout(
+            Duration::from_secs(1),
+            accept(second)
+        ).await
+            .expect("Held up by another trade's lock.")
+            .unwrap()
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.status, Approved::ID as i32);
+        let status = service.status(
+            tonic::Request::new(proto::TradeStatusRequest {
+                uuid: Some(TradeUuid { uuid: second.to_string() }),
+            })
+        ).await;
+        assert!(status.is_ok());
+
+        // Whereas the first trade waits for its own lock.
+        let waiting = accept(first);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+        drop(held);
+        let response: proto::TradeStatusResponse = waiting.await
+            .unwrap()
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.status, Approved::ID as i32);
+
+        // Racing transitions of the same trade are applied exactly once.
+        let third: Uuid = submit_trade(&service, "TestUser").await;
+        let (left, right) = tokio::join!(accept(third), accept(third));
+        let outcomes: Vec<Result<_, Status>> = vec![left.unwrap(), right.unwrap()];
+        assert_eq!(outcomes.iter().filter(|outcome| outcome.is_ok()).count(), 1);
+        let refused: &Status = outcomes
+            .iter()
+            .find_map(|outcome| outcome.as_ref().err())
+            .unwrap();
+        assert_eq!(refused.code(), Code::FailedPrecondition);
+    }
+
+    #[tokio::test]
+    async fn swept_in_uuid_order() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let requester: User<Requester> = User::sign_in("TestUser");
+        for id in [3, 1, 2] {
+            let details: TradeDetails<PendingApproval> = mock_draft(&requester)
+                .with_id(Uuid::from_u128(id))
+                .expire_after(chrono::TimeDelta::zero())
+                .submit(&requester, None)
+                .unwrap();
+            service.trades.insert(*details.id(), ComposedTradeDetails {
+                pending_approval: Some(details),
+                ..ComposedTradeDetails::default()
+            });
+        }
+
+        let expected: Vec<Uuid> = (1..=3).map(Uuid::from_u128).collect();
+        assert_eq!(service.sweep_expired(Utc::now()).await, expected);
+    }
+
+    #[derive(Debug)]
+    struct FailingSink;
+
+    impl HistorySink for FailingSink {
+        fn record(&self, _: &Uuid, _: &TradeAction, _: &'static str) -> Result<(), SinkError> {
+            Err(SinkError("database offline".to_string()))
+        }
+    }
+
+    fn service_with_failing_sink(policy: BreakerPolicy) -> TradeHandlerService {
+        TradeHandlerService {
+            history_sink: Arc::new(FailingSink),
+            breaker: Arc::new(
+                CircuitBreaker::new(BreakerConfig {
+                    failure_threshold: 2,
+                    cooldown: Duration::from_secs(60),
+                    policy,
+                })
+            ),
+            ..TradeHandlerService::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn degraded_history_sink() {
+        let service: TradeHandlerService = service_with_failing_sink(BreakerPolicy::Degrade);
+        for _ in 0..3 {
+            let response = service.submit(
+                authenticated(mock_submit_request("TestUser"), "TestUser")
+            ).await;
+            assert!(response.is_ok());
+        }
+        assert!(service.breaker.is_open());
+        assert_eq!(service.trades.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn fail_fast_history_sink() {
+        let service: TradeHandlerService = service_with_failing_sink(BreakerPolicy::FailFast);
+        for _ in 0..3 {
+            let response = service.submit(
+                authenticated(mock_submit_request("TestUser"), "TestUser")
+            ).await;
+            assert_eq!(response.unwrap_err().code(), Code::Unavailable);
+        }
+        assert!(service.breaker.is_open());
+        assert!(service.trades.is_empty());
+    }
+
+    #[derive(Debug)]
+    /// Succeeds, but first tries to sneak a second call past the breaker.
+    struct ReentrantSink {
+        breaker: Arc<CircuitBreaker>,
+        nested: std::sync::Mutex<Option<Result<(), Status>>>,
+    }
+
+    impl HistorySink for ReentrantSink {
+        fn record(
+            &self,
+            uuid: &Uuid,
+            action: &TradeAction,
+            state: &'static str
+        ) -> Result<(), SinkError> {
+            let nested: Result<(), Status> = self.breaker.record(&FailingSink, uuid, action, state);
+            *self.nested.lock().unwrap() = Some(nested);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn single_half_open_trial() {
+        let breaker: Arc<CircuitBreaker> = Arc::new(
+            CircuitBreaker::new(BreakerConfig {
+                failure_threshold: 1,
+                cooldown: Duration::ZERO,
+                policy: BreakerPolicy::FailFast,
+            })
+        );
+        let uuid: Uuid = Uuid::new_v4();
+        assert!(breaker.record(&FailingSink, &uuid, &TradeAction::Submit, "Draft").is_err());
+
+        let sink: ReentrantSink = ReentrantSink {
+            breaker: breaker.clone(),
+            nested: std::sync::Mutex::new(None),
+        };
+        assert!(breaker.record(&sink, &uuid, &TradeAction::Submit, "Draft").is_ok());
+        let nested: Status = sink.nested.lock().unwrap().take().unwrap().unwrap_err();
+        assert!(nested.message().contains("circuit breaker is open"));
+        assert!(!breaker.is_open());
+    }
+
+    #[tokio::test]
+    async fn unrecorded_transitions_leave_no_history() {
+        let seed: u64 = Uuid::new_v4().as_u64_pair().0 >> 1;
+        let service: TradeHandlerService = TradeHandlerService {
+            uuids: Arc::new(SequentialUuids::starting_at(seed)),
+            ..service_with_failing_sink(BreakerPolicy::FailFast)
+        };
+        let response = service.submit(
+            authenticated(mock_submit_request("TestUser"), "TestUser")
+        ).await;
+        assert_eq!(response.unwrap_err().code(), Code::Unavailable);
+        let uuid: Uuid = Uuid::from_u128(seed as u128);
+        assert!(HISTORY.lock().unwrap().records_for(uuid).is_empty());
+    }
+
+    #[tokio::test]
+    async fn retryable_errors_marked() {
+        // The store being offline is transient, so worth retrying.
+        let service: TradeHandlerService = service_with_failing_sink(BreakerPolicy::FailFast);
+        let status: Status = service
+            .submit(authenticated(mock_submit_request("TestUser"), "TestUser")).await
+            .unwrap_err();
+        assert_eq!(status.metadata().get(RETRYABLE_KEY).unwrap(), "true");
+        assert_eq!(ErrorClass::from_status(&status), Some(ErrorClass::Retryable));
+
+        // Whereas invalid details will be rejected however often they're sent.
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let mut request: proto::TradeSubmitRequest = mock_submit_request("TestUser");
+        request.details.as_mut().unwrap().currency_amount = "0".to_string();
+        let status: Status = service.submit(authenticated(request, "TestUser")).await.unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(status.metadata().get(RETRYABLE_KEY).unwrap(), "false");
+        assert_eq!(ErrorClass::from_status(&status), Some(ErrorClass::NonRetryable));
+    }
+}
@@ -0,0 +1,69 @@
+use std::{ pin::Pin, task::{ Context, Poll } };
+
+use tonic::{ Code, codegen::http };
+use tower::{ Layer, Service };
+
+/// Header a trailers-only gRPC response, as for any error, carries its code in.
+pub(crate) const GRPC_STATUS_HEADER: &str = "grpc-status";
+
+#[derive(Debug, Clone)]
+/// Logs requests rejected for exceeding the server's message size limit.
+///
+/// tonic rejects them itself, with `OutOfRange`, while decoding the message,
+/// so before any handler is reached. An interceptor only sees a request's
+/// metadata, not its body, so the rejection is spotted in the response.
+pub struct OversizedMessageLayer {
+    limit: usize,
+}
+
+impl OversizedMessageLayer {
+    pub fn new(limit: usize) -> Self {
+        Self { limit }
+    }
+}
+
+impl<S> Layer<S> for OversizedMessageLayer {
+    type Service = OversizedMessageService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        OversizedMessageService { inner, limit: self.limit }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OversizedMessageService<S> {
+    inner: S,
+    limit: usize,
+}
+
+impl<S, RequestBody, ResponseBody> Service<http::Request<RequestBody>>
+    for OversizedMessageService<S>
+    where
+        S: Service<http::Request<RequestBody>, Response = http::Response<ResponseBody>>,
+        S::Future: Send + 'static
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<RequestBody>) -> Self::Future {
+        let method: String = request.uri().path().to_string();
+        let limit: usize = self.limit;
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let response = response.await?;
+            let code: Option<Code> = response
+                .headers()
+                .get(GRPC_STATUS_HEADER)
+                .map(|status: &http::HeaderValue| Code::from_bytes(status.as_bytes()));
+            if code == Some(Code::OutOfRange) {
+                tracing::warn!(method, limit, "Rejected a request over the message size limit.");
+            }
+            Ok(response)
+        })
+    }
+}
@@ -0,0 +1,216 @@
+use std::{ fmt::{ self, Debug }, time::Instant };
+
+use axum::{ Router, extract::State, http::StatusCode, routing::get };
+use library::state::{ STATES, TradeAction };
+use prometheus::{
+    HistogramOpts,
+    HistogramVec,
+    IntCounter,
+    IntCounterVec,
+    IntGaugeVec,
+    Opts,
+    Registry,
+    TextEncoder,
+};
+use tracing::{ Subscriber, field::{ Field, Visit }, span::{ Attributes, Id } };
+use tracing_subscriber::{ Layer, layer::Context, registry::LookupSpan };
+
+use crate::TradeHandlerService;
+
+/// Where `/metrics` is served, alongside the gRPC server.
+pub const METRICS_ADDRESS: &str = "[::1]:9090";
+
+#[derive(Clone)]
+/// Prometheus metrics of the server, registered into their own registry.
+pub struct Metrics {
+    registry: Registry,
+
+    /// Stored transitions, by action.
+    transitions: IntCounterVec,
+
+    /// Trades currently in each state, refreshed on every scrape.
+    trades: IntGaugeVec,
+
+    /// RPC latency in seconds, by method.
+    latency: HistogramVec,
+
+    /// Failed RPCs, by method.
+    errors: IntCounterVec,
+
+    /// Events lost by lagging subscribers, caught up with the bus on every scrape.
+    dropped_events: IntCounter,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        let transitions: IntCounterVec = IntCounterVec::new(
+            Opts::new("trade_transitions_total", "Stored trade transitions."),
+            &["action"]
+        ).unwrap();
+        let trades: IntGaugeVec = IntGaugeVec::new(
+            Opts::new("trades", "Trades currently in each state."),
+            &["state"]
+        ).unwrap();
+        let latency: HistogramVec = HistogramVec::new(
+            HistogramOpts::new("rpc_latency_seconds", "Latency of each RPC."),
+            &["method"]
+        ).unwrap();
+        let errors: IntCounterVec = IntCounterVec::new(
+            Opts::new("rpc_errors_total", "RPCs which returned an error."),
+            &["method"]
+        ).unwrap();
+        let dropped_events: IntCounter = IntCounter::new(
+            "trade_events_dropped_total",
+            "Trade events lost by lagging subscribers."
+        ).unwrap();
+
+        // Registration only fails on duplicate names, which are fixed above.
+        let registry: Registry = Registry::new();
+        registry.register(Box::new(transitions.clone())).unwrap();
+        registry.register(Box::new(trades.clone())).unwrap();
+        registry.register(Box::new(latency.clone())).unwrap();
+        registry.register(Box::new(errors.clone())).unwrap();
+        registry.register(Box::new(dropped_events.clone())).unwrap();
+
+        Self { registry, transitions, trades, latency, errors, dropped_events }
+    }
+}
+
+impl Debug for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Metrics").finish_non_exhaustive()
+    }
+}
+
+impl Metrics {
+    pub fn count_transition(&self, action: &TradeAction) {
+        self.transitions.with_label_values(&[action.to_string()]).inc();
+    }
+
+    /// Sets the gauge of every state, including those without any trades.
+    pub fn set_trades_per_state(&self, count: impl Fn(&'static str) -> i64) {
+        for (_, name, _) in STATES {
+            self.trades.with_label_values(&[name]).set(count(name));
+        }
+    }
+
+    /// Advances the dropped events counter to the bus's total, which only grows.
+    pub fn set_dropped_events(&self, total: u64) {
+        self.dropped_events.inc_by(total.saturating_sub(self.dropped_events.get()));
+    }
+
+    /// The metrics in the Prometheus text format.
+    pub fn encode(&self) -> Result<String, prometheus::Error> {
+        TextEncoder::new().encode_to_string(&self.registry.gather())
+    }
+}
+
+/// Serves `/metrics`, scraping the service's current trades on each request.
+pub fn router(service: TradeHandlerService) -> Router {
+    Router::new().route("/metrics", get(scrape)).with_state(service)
+}
+
+async fn scrape(State(service): State<TradeHandlerService>) -> Result<String, StatusCode> {
+    service.refresh_trade_gauges().await;
+    service.metrics.set_dropped_events(service.updates.dropped_events());
+    service.metrics.encode().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Times the RPC spans from `tracing::instrument`, so latency and errors
+/// are measured without each handler having to.
+pub struct MetricsLayer {
+    metrics: Metrics,
+}
+
+impl MetricsLayer {
+    pub fn new(metrics: Metrics) -> Self {
+        Self { metrics }
+    }
+}
+
+/// Whether an event was emitted for an error returned by an instrumented RPC.
+struct ErrorVisitor(bool);
+
+impl Visit for ErrorVisitor {
+    fn record_debug(&mut self, field: &Field, _: &dyn Debug) {
+        self.0 |= field.name() == "error";
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for MetricsLayer {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        // Only this crate's spans, not those of its dependencies (e.g. h2).
+        if !attrs.metadata().target().starts_with(env!("CARGO_CRATE_NAME")) {
+            return;
+        }
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Instant::now());
+        }
+    }
+
+    fn on_event(&self, event: &tracing::Event<'_>, ctx: Context<'_, S>) {
+        let mut visitor: ErrorVisitor = ErrorVisitor(false);
+        event.record(&mut visitor);
+        if let (true, Some(span)) = (visitor.0, ctx.event_span(event)) {
+            self.metrics.errors.with_label_values(&[span.name()]).inc();
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        if let Some(started) = span.extensions().get::<Instant>() {
+            self.metrics.latency
+                .with_label_values(&[span.name()])
+                .observe(started.elapsed().as_secs_f64());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{ Body, to_bytes };
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{ config::ServerConfig, events::EventReceiver, tests::submit_trade };
+
+    async fn scrape_metrics(service: &TradeHandlerService) -> String {
+        let response = router(service.clone())
+            .oneshot(axum::http::Request::get("/metrics").body(Body::empty()).unwrap()).await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn submit_counted() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let before: String = scrape_metrics(&service).await;
+        assert!(!before.contains("trade_transitions_total{action=\"submit\"}"));
+        assert!(before.contains("trades{state=\"PendingApproval\"} 0"));
+
+        submit_trade(&service, "TestUser").await;
+        let after: String = scrape_metrics(&service).await;
+        assert!(after.contains("trade_transitions_total{action=\"submit\"} 1"));
+        assert!(after.contains("trades{state=\"PendingApproval\"} 1"));
+    }
+    #[tokio::test]
+    async fn dropped_events_counted() {
+        let service: TradeHandlerService = TradeHandlerService::new(ServerConfig {
+            event_capacity: 2,
+            ..ServerConfig::default()
+        });
+        let mut lagging: EventReceiver = service.updates.subscribe();
+        assert!(scrape_metrics(&service).await.contains("trade_events_dropped_total 0"));
+
+        // The subscriber only finds out it lagged, dropping the oldest, once it reads.
+        for _ in 0..5 {
+            submit_trade(&service, "TestUser").await;
+        }
+        lagging.recv().await.unwrap();
+        assert!(scrape_metrics(&service).await.contains("trade_events_dropped_total 3"));
+    }
+}
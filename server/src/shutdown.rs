@@ -0,0 +1,364 @@
+use std::{
+    pin::Pin,
+    sync::{ Arc, atomic::{ AtomicUsize, Ordering } },
+    task::{ Context, Poll },
+};
+
+use http_body::{ Body, Frame, SizeHint };
+use tower::{ Layer, Service };
+
+#[derive(Debug, Clone, Default)]
+/// Counts the requests currently being handled, so shutdown can report
+/// how many it waited on.
+pub struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    pub fn count(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    fn enter(&self) -> InFlightGuard {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard(self.clone())
+    }
+}
+
+/// Marks a request as complete when dropped, including when it's aborted.
+struct InFlightGuard(InFlight);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InFlightLayer {
+    in_flight: InFlight,
+}
+
+impl InFlightLayer {
+    pub fn new(in_flight: InFlight) -> Self {
+        Self { in_flight }
+    }
+}
+
+impl<S> Layer<S> for InFlightLayer {
+    type Service = InFlightService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        InFlightService { inner, in_flight: self.in_flight.clone() }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InFlightService<S> {
+    inner: S,
+    in_flight: InFlight,
+}
+
+impl<S, Request, ResponseBody> Service<Request> for InFlightService<S>
+    where
+        S: Service<Request, Response = http::Response<ResponseBody>>,
+        S::Future: Send + 'static,
+        ResponseBody: Send + 'static
+{
+    type Response = http::Response<InFlightBody<ResponseBody>>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let guard: InFlightGuard = self.in_flight.enter();
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            // Handed to the body, so a streamed response counts until it ends.
+            let response = response.await?;
+            Ok(response.map(|inner: ResponseBody| InFlightBody {
+                inner: Box::pin(inner),
+                _guard: guard,
+            }))
+        })
+    }
+}
+
+/// A response body which is in flight until it's dropped.
+pub struct InFlightBody<B> {
+    inner: Pin<Box<B>>,
+    _guard: InFlightGuard,
+}
+
+impl<B: Body> Body for InFlightBody<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        self.inner.as_mut().poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Resolves on the first SIGINT (Ctrl+C) or, on unix, SIGTERM.
+pub async fn signal() {
+    let interrupt = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!(%e, "Failed to listen for SIGINT.");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut stream) => {
+                stream.recv().await;
+            }
+            Err(e) => {
+                tracing::error!(%e, "Failed to listen for SIGTERM.");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => {}
+        _ = terminate => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ collections::HashMap, time::Duration };
+
+    use chrono::{ TimeDelta, Utc };
+    use library::{ error::RETRYABLE_KEY, state::{ Cancelled, TradeState } };
+    use tokio::{ net::TcpListener, sync::oneshot, task::JoinHandle };
+    use iso_currency::Currency;
+    use tonic::{ Code, Status, Streaming, metadata::MetadataValue, server::NamedService };
+    use tonic_health::pb::{
+        HealthCheckRequest,
+        HealthCheckResponse,
+        health_check_response::ServingStatus,
+        health_client::HealthClient,
+    };
+    use tonic_reflection::pb::v1::{
+        ServerReflectionRequest,
+        ServerReflectionResponse,
+        server_reflection_client::ServerReflectionClient,
+        server_reflection_request::MessageRequest,
+        server_reflection_response::MessageResponse,
+    };
+    use uuid::Uuid;
+
+    use crate::{
+        TradeHandlerService,
+        auth::{ AUTHORIZATION_KEY, AuthInterceptor, StaticTokenVerifier },
+        config::ServerConfig,
+        proto::{
+            self,
+            trade_handler_client::TradeHandlerClient,
+            trade_handler_server::TradeHandlerServer,
+        },
+        serve,
+        shutdown::InFlight,
+        tests::{ mock_submit_request, submit_trade },
+    };
+
+    fn status_request(uuid: &Uuid) -> tonic::Request<proto::TradeStatusRequest> {
+        let mut request = tonic::Request::new(proto::TradeStatusRequest {
+            uuid: Some(proto::TradeUuid { uuid: uuid.to_string() }),
+        });
+        request
+            .metadata_mut()
+            .insert(AUTHORIZATION_KEY, MetadataValue::from_static("Bearer token"));
+        request
+    }
+
+    /// Serves on an ephemeral port, returning its endpoint and the trigger to shut it down.
+    async fn spawn_server(
+        service: TradeHandlerService,
+        verifier: StaticTokenVerifier,
+        in_flight: InFlight
+    ) -> (String, oneshot::Sender<()>, JoinHandle<Result<(), tonic::transport::Error>>) {
+        let interceptor: AuthInterceptor = AuthInterceptor::new(std::sync::Arc::new(verifier));
+        let listener: TcpListener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint: String = format!("http://{}", listener.local_addr().unwrap());
+        let (trigger, signal) = oneshot::channel::<()>();
+        let server = tokio::spawn(
+            serve(service, interceptor, listener, in_flight, async move {
+                signal.await.ok();
+            })
+        );
+        (endpoint, trigger, server)
+    }
+
+    #[tokio::test]
+    async fn draining_on_shutdown() {
+        let service: TradeHandlerService = TradeHandlerService::default();
+        let uuid: Uuid = submit_trade(&service, "TestUser").await;
+        let verifier: StaticTokenVerifier = StaticTokenVerifier::new(
+            HashMap::from([("token".to_string(), "TestUser".to_string())])
+        );
+        let in_flight: InFlight = InFlight::default();
+        let (endpoint, trigger, server) = spawn_server(
+            service.clone(),
+            verifier,
+            in_flight.clone()
+        ).await;
+
+        // An outstanding call, which is in flight for as long as it streams.
+        let mut client = TradeHandlerClient::connect(endpoint.clone()).await.unwrap();
+        let mut stream: Streaming<proto::TradeStatusResponse> = client
+            .subscribe(status_request(&uuid)).await
+            .unwrap()
+            .into_inner();
+        assert_eq!(in_flight.count(), 1);
+
+        // The health check stops serving once the shutdown has begun.
+        let mut health = HealthClient::connect(endpoint.clone()).await.unwrap();
+        let mut serving: Streaming<HealthCheckResponse> = health
+            .watch(HealthCheckRequest {
+                service: TradeHandlerServer::<TradeHandlerService>::NAME.to_string(),
+            }).await
+            .unwrap()
+            .into_inner();
+        trigger.send(()).unwrap();
+        while let Some(response) = serving.message().await.unwrap() {
+            if response.status == ServingStatus::NotServing as i32 {
+                break;
+            }
+        }
+        drop(serving);
+        drop(health);
+
+        let late_call = tokio::time::timeout(Duration::from_millis(500), async {
+            let mut client = TradeHandlerClient::connect(endpoint).await.ok()?;
+            client.status(status_request(&uuid)).await.ok()
+        }).await;
+        assert!(!matches!(late_call, Ok(Some(_))));
+        assert!(!server.is_finished());
+
+        // Completing the outstanding call lets the server stop.
+        service.sweep_expired(Utc::now() + TimeDelta::days(365)).await;
+        let response: proto::TradeStatusResponse = stream.message().await.unwrap().unwrap();
+        assert_eq!(response.status, Cancelled::ID as i32);
+        assert!(stream.message().await.unwrap().is_none());
+        server.await.unwrap().unwrap();
+        assert_eq!(in_flight.count(), 0);
+    }
+
+    #[tokio::test]
+    async fn health_check() {
+        let verifier: StaticTokenVerifier = StaticTokenVerifier::new(HashMap::new());
+        let (endpoint, trigger, server) = spawn_server(
+            TradeHandlerService::default(),
+            verifier,
+            InFlight::default()
+        ).await;
+
+        // Unauthenticated, as probes don't carry a token.
+        let mut client = HealthClient::connect(endpoint).await.unwrap();
+        let response = client
+            .check(HealthCheckRequest {
+                service: TradeHandlerServer::<TradeHandlerService>::NAME.to_string(),
+            }).await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.status, ServingStatus::Serving as i32);
+
+        drop(client);
+        trigger.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn oversized_message_rejected() {
+        let service: TradeHandlerService = TradeHandlerService::new(ServerConfig {
+            max_message_bytes: 1024,
+            ..ServerConfig::default()
+        });
+        let verifier: StaticTokenVerifier = StaticTokenVerifier::new(
+            HashMap::from([("token".to_string(), "TestUser".to_string())])
+        );
+        let (endpoint, trigger, server) = spawn_server(
+            service.clone(),
+            verifier,
+            InFlight::default()
+        ).await;
+        let mut client = TradeHandlerClient::connect(endpoint).await.unwrap();
+        let submit = |underlying: usize| {
+            let mut submission: proto::TradeSubmitRequest = mock_submit_request("TestUser");
+            submission.details.as_mut().unwrap().underlying_currency_codes = vec![
+                Currency::GBP.numeric() as u32;
+                underlying
+            ];
+            let mut request = tonic::Request::new(submission);
+            request
+                .metadata_mut()
+                .insert(AUTHORIZATION_KEY, MetadataValue::from_static("Bearer token"));
+            request
+        };
+
+        // Rejected while decoding, so the handler never stores the trade.
+        let status: Status = client.submit(submit(1000)).await.unwrap_err();
+        assert_eq!(status.code(), Code::OutOfRange);
+        assert_eq!(status.metadata().get(RETRYABLE_KEY).unwrap(), "false");
+        assert!(service.trades.is_empty());
+
+        assert!(client.submit(submit(1)).await.is_ok());
+        assert_eq!(service.trades.len(), 1);
+
+        drop(client);
+        trigger.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn listing_services_by_reflection() {
+        let verifier: StaticTokenVerifier = StaticTokenVerifier::new(HashMap::new());
+        let (endpoint, trigger, server) = spawn_server(
+            TradeHandlerService::default(),
+            verifier,
+            InFlight::default()
+        ).await;
+
+        let mut client = ServerReflectionClient::connect(endpoint).await.unwrap();
+        let request: ServerReflectionRequest = ServerReflectionRequest {
+            host: String::new(),
+            message_request: Some(MessageRequest::ListServices(String::new())),
+        };
+        let mut responses: Streaming<ServerReflectionResponse> = client
+            .server_reflection_info(tokio_stream::once(request)).await
+            .unwrap()
+            .into_inner();
+        let response: ServerReflectionResponse = responses.message().await.unwrap().unwrap();
+        let Some(MessageResponse::ListServicesResponse(listed)) = response.message_response else {
+            panic!("Expected the services to be listed.");
+        };
+        let services: Vec<String> = listed.service
+            .into_iter()
+            .map(|service| service.name)
+            .collect();
+        assert!(services.contains(&"trade.TradeHandler".to_string()));
+
+        drop(responses);
+        drop(client);
+        trigger.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,66 @@
+use std::{ collections::HashMap, sync::{ Arc, RwLock } };
+
+use tokio::sync::{ Mutex, OwnedMutexGuard };
+use uuid::Uuid;
+
+use crate::ComposedTradeDetails;
+
+/// A single trade, locked independently of every other trade.
+pub type TradeSlot = Arc<Mutex<ComposedTradeDetails>>;
+
+#[derive(Debug, Clone, Default)]
+/// The trades the server holds, each behind its own lock, so a slow
+/// transition of one trade doesn't hold up any other.
+///
+/// The map itself is only locked to look up or insert a slot, and never
+/// while a trade is locked, as trades are never removed.
+pub struct TradeStore {
+    slots: Arc<RwLock<HashMap<Uuid, TradeSlot>>>,
+}
+
+impl TradeStore {
+    pub fn get(&self, uuid: &Uuid) -> Option<TradeSlot> {
+        self.slots.read().unwrap().get(uuid).cloned()
+    }
+
+    /// Locks the trade, the guard owning its slot so it can outlive the lookup.
+    pub async fn lock(&self, uuid: &Uuid) -> Option<OwnedMutexGuard<ComposedTradeDetails>> {
+        Some(self.get(uuid)?.lock_owned().await)
+    }
+
+    /// Stores a new trade, unless one with the UUID already exists.
+    pub fn insert(&self, uuid: Uuid, composed: ComposedTradeDetails) -> bool {
+        let mut slots = self.slots.write().unwrap();
+        if slots.contains_key(&uuid) {
+            return false;
+        }
+        slots.insert(uuid, Arc::new(Mutex::new(composed)));
+        true
+    }
+
+    pub fn contains(&self, uuid: &Uuid) -> bool {
+        self.slots.read().unwrap().contains_key(uuid)
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.read().unwrap().is_empty()
+    }
+
+    /// Every trade's slot, ordered by UUID rather than the arbitrary order of
+    /// the `HashMap`, so anything assembled from them is deterministic. Only
+    /// the slots are cloned, and each is left for the caller to lock in turn.
+    pub fn slots(&self) -> Vec<(Uuid, TradeSlot)> {
+        let mut slots: Vec<(Uuid, TradeSlot)> = self.slots
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(uuid, slot)| (*uuid, slot.clone()))
+            .collect();
+        slots.sort_by_key(|(uuid, _)| *uuid);
+        slots
+    }
+}
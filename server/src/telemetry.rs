@@ -0,0 +1,71 @@
+use tracing::Span;
+use tracing_subscriber::{
+    EnvFilter,
+    Layer,
+    fmt::format::FmtSpan,
+    layer::SubscriberExt,
+    util::SubscriberInitExt,
+};
+use uuid::Uuid;
+
+use crate::metrics::MetricsLayer;
+
+/// Verbosity used when `RUST_LOG` isn't set.
+const DEFAULT_FILTER: &str = "info";
+
+/// Installs the global subscriber, logging as filtered by `RUST_LOG`
+/// (e.g. `RUST_LOG=server=debug`).
+/// Spans are logged as they close, which includes how long each RPC took.
+/// The metrics layer sees every span, regardless of the log filter.
+pub fn init(metrics: MetricsLayer) {
+    let filter: EnvFilter = EnvFilter::try_from_default_env().unwrap_or_else(|_| {
+        EnvFilter::new(DEFAULT_FILTER)
+    });
+    let logging = tracing_subscriber::fmt
+        ::layer()
+        .with_span_events(FmtSpan::CLOSE)
+        .with_filter(filter);
+    tracing_subscriber::registry().with(logging).with(metrics).init();
+}
+
+/// Records the trade acted upon against the current RPC span.
+pub fn record_uuid(uuid: &Uuid) {
+    Span::current().record("uuid", tracing::field::display(uuid));
+}
+
+/// Records the state a trade was left in against the current RPC span.
+pub fn record_state(status: i32) {
+    Span::current().record("state", status);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{ Arc, Mutex };
+
+    use tracing::{ Subscriber, span::{ Attributes, Id } };
+    use tracing_subscriber::{ layer::{ Context, SubscriberExt }, Layer };
+
+    use crate::{ TradeHandlerService, tests::submit_trade };
+
+    #[derive(Debug, Clone, Default)]
+    /// Captures the name of every span created.
+    struct SpanNames(Arc<Mutex<Vec<&'static str>>>);
+
+    impl<S: Subscriber> Layer<S> for SpanNames {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _: &Id, _: Context<'_, S>) {
+            self.0.lock().unwrap().push(attrs.metadata().name());
+        }
+    }
+
+    #[tokio::test]
+    async fn submit_is_traced() {
+        let spans: SpanNames = SpanNames::default();
+        let _guard = tracing::subscriber::set_default(
+            tracing_subscriber::registry().with(spans.clone())
+        );
+
+        let service: TradeHandlerService = TradeHandlerService::default();
+        submit_trade(&service, "TestUser").await;
+        assert!(spans.0.lock().unwrap().contains(&"submit"));
+    }
+}